@@ -4,7 +4,20 @@ use std::fs::create_dir_all;
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
 use collectxyz::nft::{
-    ExecuteMsg, InstantiateMsg, MoveParamsResponse, QueryMsg, XyzTokensResponse,
+    ActiveReservationsResponse, AllowlistEligibilityResponse, AllowlistMerkleRootResponse,
+    ApprovalResponse, ApprovalsResponse, CaptchaPublicKeysResponse, CheckRoyaltiesResponse,
+    CollisionCheckResponse, ConfigInfoResponse, ConfigResponse, CoordinatesStatusResponse,
+    CurrentMintFeeResponse, Cw20HookMsg, DensityResponse, ExecuteMsg, FeeBalancesResponse,
+    HooksResponse, InstantiateMsg, Listing, ListingsResponse, MintPricesResponse,
+    MintStatusResponse, MinterResponse, MoveHistoryResponse, MoveManyParamsResponse,
+    MoveParamsResponse, MoveQueueResponse, OffersResponse, OperatorsResponse,
+    OwnerLeaderboardResponse, OwnerTokenSummaryResponse, OwnershipDistributionResponse,
+    OwnershipResponse, PausedResponse, PilotResponse, PublicMintsRemainingResponse, QueryMsg,
+    ReservedCoordinatesResponse, RoyaltiesInfoResponse, SimulateMintResponse, StatsResponse,
+    SupplyResponse, SwapParamsResponse, SwapProposal, TokenEnergyResponse, TokenStatsResponse,
+    TokensInTransitResponse, VerifyCaptchaResponse, WalletLimitExemptionsResponse,
+    WithdrawableBalanceResponse, XyzHookExecuteMsg, XyzNftInfoBatchResponse,
+    XyzNftInfoByCoordsResponse, XyzTokensNearbyResponse, XyzTokensResponse,
 };
 
 fn main() {
@@ -16,6 +29,56 @@ fn main() {
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(ConfigInfoResponse), &out_dir);
     export_schema(&schema_for!(MoveParamsResponse), &out_dir);
+    export_schema(&schema_for!(MoveManyParamsResponse), &out_dir);
     export_schema(&schema_for!(XyzTokensResponse), &out_dir);
+    export_schema(&schema_for!(XyzNftInfoBatchResponse), &out_dir);
+    export_schema(&schema_for!(XyzNftInfoByCoordsResponse), &out_dir);
+    export_schema(&schema_for!(CoordinatesStatusResponse), &out_dir);
+    export_schema(&schema_for!(TokenEnergyResponse), &out_dir);
+    export_schema(&schema_for!(TokenStatsResponse), &out_dir);
+    export_schema(&schema_for!(OwnershipDistributionResponse), &out_dir);
+    export_schema(&schema_for!(OwnerTokenSummaryResponse), &out_dir);
+    export_schema(&schema_for!(CollisionCheckResponse), &out_dir);
+    export_schema(&schema_for!(VerifyCaptchaResponse), &out_dir);
+    export_schema(&schema_for!(PublicMintsRemainingResponse), &out_dir);
+    export_schema(&schema_for!(MoveQueueResponse), &out_dir);
+    export_schema(&schema_for!(MoveHistoryResponse), &out_dir);
+    export_schema(&schema_for!(TokensInTransitResponse), &out_dir);
+    export_schema(&schema_for!(XyzTokensNearbyResponse), &out_dir);
+    export_schema(&schema_for!(DensityResponse), &out_dir);
+    export_schema(&schema_for!(OwnerLeaderboardResponse), &out_dir);
+    export_schema(&schema_for!(AllowlistMerkleRootResponse), &out_dir);
+    export_schema(&schema_for!(AllowlistEligibilityResponse), &out_dir);
+    export_schema(&schema_for!(CurrentMintFeeResponse), &out_dir);
+    export_schema(&schema_for!(MintPricesResponse), &out_dir);
+    export_schema(&schema_for!(Cw20HookMsg), &out_dir);
+    export_schema(&schema_for!(RoyaltiesInfoResponse), &out_dir);
+    export_schema(&schema_for!(CheckRoyaltiesResponse), &out_dir);
+    export_schema(&schema_for!(WithdrawableBalanceResponse), &out_dir);
+    export_schema(&schema_for!(FeeBalancesResponse), &out_dir);
+    export_schema(&schema_for!(StatsResponse), &out_dir);
+    export_schema(&schema_for!(OwnershipResponse), &out_dir);
+    export_schema(&schema_for!(HooksResponse), &out_dir);
+    export_schema(&schema_for!(Listing), &out_dir);
+    export_schema(&schema_for!(ListingsResponse), &out_dir);
+    export_schema(&schema_for!(OffersResponse), &out_dir);
+    export_schema(&schema_for!(PilotResponse), &out_dir);
+    export_schema(&schema_for!(SwapProposal), &out_dir);
+    export_schema(&schema_for!(SupplyResponse), &out_dir);
+    export_schema(&schema_for!(MinterResponse), &out_dir);
+    export_schema(&schema_for!(MintStatusResponse), &out_dir);
+    export_schema(&schema_for!(OperatorsResponse), &out_dir);
+    export_schema(&schema_for!(ApprovalResponse), &out_dir);
+    export_schema(&schema_for!(ApprovalsResponse), &out_dir);
+    export_schema(&schema_for!(SimulateMintResponse), &out_dir);
+    export_schema(&schema_for!(PausedResponse), &out_dir);
+    export_schema(&schema_for!(ReservedCoordinatesResponse), &out_dir);
+    export_schema(&schema_for!(SwapParamsResponse), &out_dir);
+    export_schema(&schema_for!(ActiveReservationsResponse), &out_dir);
+    export_schema(&schema_for!(WalletLimitExemptionsResponse), &out_dir);
+    export_schema(&schema_for!(XyzHookExecuteMsg), &out_dir);
+    export_schema(&schema_for!(CaptchaPublicKeysResponse), &out_dir);
 }
@@ -1,6 +1,7 @@
-use collectxyz::nft::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use collectxyz::nft::{ExecuteMsg, InstantiateMsg, MigrateMsg, MintRequest, QueryMsg};
 use cosmwasm_std::{
-    entry_point, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
+    entry_point, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdError,
+    StdResult,
 };
 use cw2::{get_contract_version, set_contract_version};
 
@@ -8,19 +9,22 @@ use crate::error::ContractError;
 use crate::execute as ExecHandler;
 use crate::query as QueryHandler;
 
-const CONTRACT_NAME: &str = "crates.io:collectxyz-nft-contract";
-const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const CONTRACT_NAME: &str = "crates.io:collectxyz-nft-contract";
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The earliest contract version whose state migrate can upgrade
+const MIN_MIGRATABLE_VERSION: &str = "0.2.0";
 
 #[entry_point]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> StdResult<Response> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-    ExecHandler::instantiate(deps, info, msg)
+    ExecHandler::instantiate(deps, env, info, msg)
 }
 
 #[entry_point]
@@ -34,57 +38,495 @@ pub fn execute(
         ExecuteMsg::Mint {
             coordinates,
             captcha_signature,
-        } => ExecHandler::execute_mint(deps, env, info, coordinates, captcha_signature),
+            nonce,
+            expiry,
+            token_uri,
+            proof,
+        } => ExecHandler::execute_mint(
+            deps,
+            env,
+            info,
+            MintRequest {
+                coordinates,
+                captcha_signature,
+                nonce,
+                expiry,
+            },
+            token_uri,
+            proof,
+        ),
+        ExecuteMsg::MintRandom {
+            captcha_signature,
+            nonce,
+            expiry,
+        } => ExecHandler::execute_mint_random(deps, env, info, captcha_signature, nonce, expiry),
+        ExecuteMsg::BatchMint { mints } => ExecHandler::execute_batch_mint(deps, env, info, mints),
+        ExecuteMsg::MintFor {
+            recipient,
+            coordinates,
+        } => ExecHandler::execute_mint_for(deps, env, info, recipient, coordinates),
         ExecuteMsg::Move {
             token_id,
             coordinates,
         } => ExecHandler::execute_move(deps, env, info, token_id, coordinates),
+        ExecuteMsg::MoveMany { moves } => ExecHandler::execute_move_many(deps, env, info, moves),
+        ExecuteMsg::MoveFormation { token_ids, offset } => {
+            ExecHandler::execute_move_formation(deps, env, info, token_ids, offset)
+        }
+        ExecuteMsg::RerollCoordinates {
+            token_id,
+            coordinates,
+        } => ExecHandler::execute_reroll_coordinates(deps, env, info, token_id, coordinates),
+        ExecuteMsg::Teleport {
+            token_id,
+            coordinates,
+        } => ExecHandler::execute_teleport(deps, env, info, token_id, coordinates),
+        ExecuteMsg::ExpediteMove { token_id } => {
+            ExecHandler::execute_expedite_move(deps, env, info, token_id)
+        }
+        ExecuteMsg::CancelMove { token_id } => {
+            ExecHandler::execute_cancel_move(deps, env, info, token_id)
+        }
+        ExecuteMsg::SetTokenProfile {
+            token_id,
+            name,
+            description,
+        } => ExecHandler::execute_set_token_profile(deps, info, token_id, name, description),
+        ExecuteMsg::ListForSale { token_id, price } => {
+            ExecHandler::execute_list_for_sale(deps, info, token_id, price)
+        }
+        ExecuteMsg::CancelListing { token_id } => {
+            ExecHandler::execute_cancel_listing(deps, info, token_id)
+        }
+        ExecuteMsg::Buy { token_id } => ExecHandler::execute_buy(deps, env, info, token_id),
+        ExecuteMsg::MakeOffer { token_id, expires } => {
+            ExecHandler::execute_make_offer(deps, env, info, token_id, expires)
+        }
+        ExecuteMsg::WithdrawOffer { token_id, bidder } => {
+            ExecHandler::execute_withdraw_offer(deps, env, info, token_id, bidder)
+        }
+        ExecuteMsg::AcceptOffer { token_id, bidder } => {
+            ExecHandler::execute_accept_offer(deps, env, info, token_id, bidder)
+        }
+        ExecuteMsg::GrantPilot {
+            token_id,
+            pilot,
+            expires,
+        } => ExecHandler::execute_grant_pilot(deps, env, info, token_id, pilot, expires),
+        ExecuteMsg::RevokePilot { token_id } => {
+            ExecHandler::execute_revoke_pilot(deps, info, token_id)
+        }
+        ExecuteMsg::TransferMany {
+            recipient,
+            token_ids,
+        } => ExecHandler::execute_transfer_many(deps, env, info, recipient, token_ids),
+        ExecuteMsg::SendMany {
+            contract,
+            token_ids,
+            msg,
+        } => ExecHandler::execute_send_many(deps, env, info, contract, token_ids, msg),
+        ExecuteMsg::ProposeSwap {
+            my_token_id,
+            their_token_id,
+            expires,
+        } => {
+            ExecHandler::execute_propose_swap(deps, env, info, my_token_id, their_token_id, expires)
+        }
+        ExecuteMsg::AcceptSwap { proposal_id } => {
+            ExecHandler::execute_accept_swap(deps, env, info, proposal_id)
+        }
+        ExecuteMsg::CancelSwap { proposal_id } => {
+            ExecHandler::execute_cancel_swap(deps, env, info, proposal_id)
+        }
+        ExecuteMsg::FinalizeMove { token_id } => {
+            ExecHandler::execute_finalize_move(deps, env, token_id)
+        }
+        ExecuteMsg::Merge {
+            token_id_keep,
+            token_id_burn,
+        } => ExecHandler::execute_merge(deps, env, info, token_id_keep, token_id_burn),
+        ExecuteMsg::Burn { token_id } => ExecHandler::execute_burn(deps, env, info, token_id),
+        ExecuteMsg::SetTokenUri {
+            token_id,
+            token_uri,
+        } => ExecHandler::execute_set_token_uri(deps, info, token_id, token_uri),
         ExecuteMsg::UpdateConfig { config } => {
-            ExecHandler::execute_update_config(deps, info, config)
+            ExecHandler::execute_update_config(deps, env, info, config)
+        }
+        ExecuteMsg::UpdateConfigPartial { config } => {
+            ExecHandler::execute_update_config_partial(deps, env, info, config)
+        }
+        ExecuteMsg::UpdateCaptchaPublicKey {
+            public_key,
+            algorithm,
+        } => ExecHandler::execute_update_captcha_public_key(deps, info, public_key, algorithm),
+        ExecuteMsg::AddCaptchaPublicKey {
+            public_key,
+            algorithm,
+            expires,
+        } => ExecHandler::execute_add_captcha_public_key(
+            deps, env, info, public_key, algorithm, expires,
+        ),
+        ExecuteMsg::RemoveCaptchaPublicKey {
+            public_key,
+            algorithm,
+        } => ExecHandler::execute_remove_captcha_public_key(deps, env, info, public_key, algorithm),
+        ExecuteMsg::UpdateAllowlistMerkleRoot { merkle_root } => {
+            ExecHandler::execute_update_allowlist_merkle_root(deps, info, merkle_root)
+        }
+        ExecuteMsg::ProposeNewOwner { address, expires } => {
+            ExecHandler::execute_propose_new_owner(deps, env, info, address, expires)
         }
-        ExecuteMsg::UpdateCaptchaPublicKey { public_key } => {
-            ExecHandler::execute_update_captcha_public_key(deps, info, public_key)
+        ExecuteMsg::AcceptOwnership {} => ExecHandler::execute_accept_ownership(deps, env, info),
+        ExecuteMsg::RevokeOwnershipProposal {} => {
+            ExecHandler::execute_revoke_ownership_proposal(deps, info)
         }
-        ExecuteMsg::Withdraw { amount } => ExecHandler::execute_withdraw(deps, env, info, amount),
+        ExecuteMsg::FreezeConfig {} => ExecHandler::execute_freeze_config(deps, info),
+        ExecuteMsg::RenounceOwnership {} => {
+            ExecHandler::execute_renounce_ownership(deps, env, info)
+        }
+        ExecuteMsg::Pause { actions } => ExecHandler::execute_pause(deps, info, actions),
+        ExecuteMsg::Unpause {} => ExecHandler::execute_unpause(deps, info),
+        ExecuteMsg::AddHook { addr } => ExecHandler::execute_add_hook(deps, info, addr),
+        ExecuteMsg::RemoveHook { addr } => ExecHandler::execute_remove_hook(deps, info, addr),
+        ExecuteMsg::ReserveCoordinates { reservations } => {
+            ExecHandler::execute_reserve_coordinates(deps, env, info, reservations)
+        }
+        ExecuteMsg::ReleaseCoordinates { coordinates } => {
+            ExecHandler::execute_release_coordinates(deps, info, coordinates)
+        }
+        ExecuteMsg::SetWalletLimitExemption { address, exempt } => {
+            ExecHandler::execute_set_wallet_limit_exemption(deps, info, address, exempt)
+        }
+        ExecuteMsg::Withdraw {
+            amount,
+            cw20_amount,
+            recipient,
+            balance,
+        } => {
+            ExecHandler::execute_withdraw(deps, env, info, amount, cw20_amount, recipient, balance)
+        }
+        ExecuteMsg::Receive(msg) => ExecHandler::execute_receive(deps, env, info, msg),
         _ => ExecHandler::cw721_base_execute(deps, env, info, msg),
     }
 }
 
+#[entry_point]
+pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        ExecHandler::HOOK_REPLY_ID => ExecHandler::hook_reply(msg),
+        id => Err(ContractError::Std(StdError::generic_err(format!(
+            "unknown reply id {}",
+            id
+        )))),
+    }
+}
+
 #[entry_point]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Config {} => to_binary(&QueryHandler::query_config(deps)?),
-        QueryMsg::CaptchaPublicKey {} => to_binary(&QueryHandler::query_captcha_public_key(deps)?),
+        QueryMsg::ConfigInfo {} => to_binary(&QueryHandler::query_config_info(deps)?),
+        QueryMsg::CaptchaPublicKey {} => {
+            to_binary(&QueryHandler::query_captcha_public_key(deps, env)?)
+        }
+        QueryMsg::Hooks {} => to_binary(&QueryHandler::query_hooks(deps)?),
+        QueryMsg::Paused {} => to_binary(&QueryHandler::query_paused(deps)?),
+        QueryMsg::ReservedCoordinates { start_after, limit } => to_binary(
+            &QueryHandler::query_reserved_coordinates(deps, start_after, limit)?,
+        ),
+        QueryMsg::ActiveReservations { start_after, limit } => to_binary(
+            &QueryHandler::query_active_reservations(deps, env, start_after, limit)?,
+        ),
+        QueryMsg::WalletLimitExemptions { start_after, limit } => to_binary(
+            &QueryHandler::query_wallet_limit_exemptions(deps, start_after, limit)?,
+        ),
+        QueryMsg::CaptchaPublicKeys {} => {
+            to_binary(&QueryHandler::query_captcha_public_keys(deps, env)?)
+        }
+        QueryMsg::Ownership {} => to_binary(&QueryHandler::query_ownership(deps)?),
+        QueryMsg::Minter {} => to_binary(&QueryHandler::query_minter(deps)?),
+        QueryMsg::MintStatus {} => to_binary(&QueryHandler::query_mint_status(deps, env)?),
         QueryMsg::XyzNftInfo { token_id } => {
             to_binary(&QueryHandler::query_xyz_nft_info(deps, token_id)?)
         }
+        QueryMsg::XyzNftInfoBatch { token_ids } => {
+            to_binary(&QueryHandler::query_xyz_nft_info_batch(deps, token_ids)?)
+        }
         QueryMsg::XyzNftInfoByCoords { coordinates } => to_binary(
-            &QueryHandler::query_xyz_nft_info_by_coords(deps, coordinates)?,
+            &QueryHandler::query_xyz_nft_info_by_coords(deps, env, coordinates)?,
+        ),
+        QueryMsg::XyzNftInfosByCoords {
+            coordinates,
+            start_after,
+            limit,
+        } => to_binary(&QueryHandler::query_xyz_nft_infos_by_coords(
+            deps,
+            coordinates,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::CoordinatesStatus { coordinates } => to_binary(
+            &QueryHandler::query_coordinates_status(deps, env, coordinates)?,
         ),
         QueryMsg::XyzTokens {
             owner,
             start_after,
             limit,
+            order,
         } => to_binary(&QueryHandler::query_xyz_tokens(
             deps,
             owner,
             start_after,
             limit,
+            order.unwrap_or_default(),
+        )?),
+        QueryMsg::AllXyzTokens {
+            start_after,
+            limit,
+            order_by,
+            start_after_coords,
+            order,
+        } => to_binary(&QueryHandler::query_all_xyz_tokens(
+            deps,
+            start_after,
+            limit,
+            order_by.unwrap_or_default(),
+            start_after_coords,
+            order.unwrap_or_default(),
         )?),
-        QueryMsg::AllXyzTokens { start_after, limit } => to_binary(
-            &QueryHandler::query_all_xyz_tokens(deps, start_after, limit)?,
-        ),
         QueryMsg::NumTokensForOwner { owner } => {
             to_binary(&QueryHandler::query_num_tokens_for_owner(deps, owner)?)
         }
+        QueryMsg::OwnerTokenSummary { owner } => {
+            to_binary(&QueryHandler::query_owner_token_summary(deps, env, owner)?)
+        }
         QueryMsg::MoveParams {
             token_id,
             coordinates,
         } => to_binary(&QueryHandler::query_move_params(
+            deps,
+            env,
+            token_id,
+            coordinates,
+        )?),
+        QueryMsg::MoveManyParams { moves } => {
+            to_binary(&QueryHandler::query_move_many_params(deps, env, moves)?)
+        }
+        QueryMsg::TeleportParams {
+            token_id,
+            coordinates,
+        } => to_binary(&QueryHandler::query_teleport_params(
             deps,
             token_id,
             coordinates,
         )?),
+        QueryMsg::MoveQuote { start, end } => {
+            to_binary(&QueryHandler::query_move_quote(deps, start, end)?)
+        }
+        QueryMsg::TokenEnergy { token_id } => {
+            to_binary(&QueryHandler::query_token_energy(deps, env, token_id)?)
+        }
+        QueryMsg::TokenStats { token_id } => {
+            to_binary(&QueryHandler::query_token_stats(deps, env, token_id)?)
+        }
+        QueryMsg::OwnershipDistribution {} => {
+            to_binary(&QueryHandler::query_ownership_distribution(deps)?)
+        }
+        QueryMsg::SwapProposal { proposal_id } => {
+            to_binary(&QueryHandler::query_swap_proposal(deps, proposal_id)?)
+        }
+        QueryMsg::SwapParams {
+            token_id_a,
+            token_id_b,
+        } => to_binary(&QueryHandler::query_swap_params(
+            deps, env, token_id_a, token_id_b,
+        )?),
+        QueryMsg::Pilot { token_id } => to_binary(&QueryHandler::query_pilot(deps, env, token_id)?),
+        QueryMsg::Listing { token_id } => to_binary(&QueryHandler::query_listing(deps, token_id)?),
+        QueryMsg::Listings { start_after, limit } => {
+            to_binary(&QueryHandler::query_listings(deps, start_after, limit)?)
+        }
+        QueryMsg::OffersByToken {
+            token_id,
+            start_after,
+            limit,
+        } => to_binary(&QueryHandler::query_offers_by_token(
+            deps,
+            token_id,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::OffersByBidder {
+            bidder,
+            start_after,
+            limit,
+        } => to_binary(&QueryHandler::query_offers_by_bidder(
+            deps,
+            bidder,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::OwnerLeaderboard { start_after, limit } => to_binary(
+            &QueryHandler::query_owner_leaderboard(deps, start_after, limit)?,
+        ),
+        QueryMsg::CollisionCheck {
+            token_id_a,
+            token_id_b,
+        } => to_binary(&QueryHandler::query_collision_check(
+            deps, env, token_id_a, token_id_b,
+        )?),
+        QueryMsg::SignablePayload {
+            sender,
+            coordinates,
+            nonce,
+            expiry,
+        } => to_binary(&QueryHandler::query_signable_payload(
+            deps,
+            sender,
+            coordinates,
+            nonce,
+            expiry,
+        )?),
+        QueryMsg::VerifyCaptcha {
+            minter,
+            coordinates,
+            captcha_signature,
+            nonce,
+            expiry,
+        } => to_binary(&QueryHandler::query_verify_captcha(
+            deps,
+            env,
+            minter,
+            MintRequest {
+                coordinates,
+                captcha_signature,
+                nonce,
+                expiry,
+            },
+        )?),
+        QueryMsg::MoveQueue { token_id } => {
+            to_binary(&QueryHandler::query_move_queue(deps, env, token_id)?)
+        }
+        QueryMsg::MoveHistory {
+            token_id,
+            start_after,
+            limit,
+        } => to_binary(&QueryHandler::query_move_history(
+            deps,
+            token_id,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::TokensInTransit { start_after, limit } => to_binary(
+            &QueryHandler::query_tokens_in_transit(deps, env, start_after, limit)?,
+        ),
+        QueryMsg::PublicMintsRemaining {} => {
+            to_binary(&QueryHandler::query_public_mints_remaining(deps, env)?)
+        }
+        QueryMsg::XyzTokensInRange {
+            min,
+            max,
+            transit_match,
+            start_after,
+            limit,
+        } => to_binary(&QueryHandler::query_xyz_tokens_in_range(
+            deps,
+            env,
+            min,
+            max,
+            transit_match,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::Supply {} => to_binary(&QueryHandler::query_supply(deps)?),
+        QueryMsg::CurrentMintFee {} => to_binary(&QueryHandler::query_current_mint_fee(deps)?),
+        QueryMsg::MintPrice {
+            coordinates,
+            minter,
+        } => to_binary(&QueryHandler::query_mint_price(deps, coordinates, minter)?),
+        QueryMsg::MintPrices {} => to_binary(&QueryHandler::query_mint_prices(deps)?),
+        QueryMsg::SimulateMint {
+            minter,
+            coordinates,
+        } => to_binary(&QueryHandler::query_simulate_mint(
+            deps,
+            env,
+            minter,
+            coordinates,
+        )?),
+        QueryMsg::AllowlistMerkleRoot {} => {
+            to_binary(&QueryHandler::query_allowlist_merkle_root(deps)?)
+        }
+        QueryMsg::AllowlistEligibility { address, proof } => to_binary(
+            &QueryHandler::query_allowlist_eligibility(deps, address, proof)?,
+        ),
+        QueryMsg::RoyaltyInfo {
+            token_id,
+            sale_price,
+        } => to_binary(&QueryHandler::query_royalty_info(
+            deps, token_id, sale_price,
+        )?),
+        QueryMsg::CheckRoyalties {} => to_binary(&QueryHandler::query_check_royalties(deps)?),
+        QueryMsg::WithdrawableBalance {} => {
+            to_binary(&QueryHandler::query_withdrawable_balance(deps, env)?)
+        }
+        QueryMsg::FeeBalances {} => to_binary(&QueryHandler::query_fee_balances(deps, env)?),
+        QueryMsg::Stats {} => to_binary(&QueryHandler::query_stats(deps)?),
+        QueryMsg::Density {
+            min,
+            max,
+            bucket_size,
+        } => to_binary(&QueryHandler::query_density(deps, min, max, bucket_size)?),
+        QueryMsg::XyzTokensNearby {
+            coordinates,
+            max_distance,
+            start_after,
+            limit,
+        } => to_binary(&QueryHandler::query_xyz_tokens_nearby(
+            deps,
+            env,
+            coordinates,
+            max_distance,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::OccupancyBitmap { min, max } => {
+            to_binary(&QueryHandler::query_occupancy_bitmap(deps, min, max)?)
+        }
+        QueryMsg::Approval {
+            token_id,
+            spender,
+            include_expired,
+        } => to_binary(&QueryHandler::query_approval(
+            deps,
+            env,
+            token_id,
+            spender,
+            include_expired.unwrap_or(false),
+        )?),
+        QueryMsg::Approvals {
+            token_id,
+            include_expired,
+        } => to_binary(&QueryHandler::query_approvals(
+            deps,
+            env,
+            token_id,
+            include_expired.unwrap_or(false),
+        )?),
+        QueryMsg::AllOperators {
+            owner,
+            include_expired,
+            start_after,
+            limit,
+        } => to_binary(&QueryHandler::query_all_operators(
+            deps,
+            env,
+            owner,
+            include_expired.unwrap_or(false),
+            start_after,
+            limit,
+        )?),
         _ => QueryHandler::cw721_base_query(deps, env, msg),
     }
 }
@@ -97,8 +539,40 @@ pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> StdResult<Response>
             "can't migrate to contract with different name",
         ));
     }
+    let from_version = parse_version(&version.version)?;
+    if from_version > parse_version(CONTRACT_VERSION)? {
+        return Err(StdError::generic_err(format!(
+            "can't migrate from newer version {}",
+            version.version
+        )));
+    }
+    if from_version < parse_version(MIN_MIGRATABLE_VERSION)? {
+        return Err(StdError::generic_err(format!(
+            "can't migrate from unsupported version {}",
+            version.version
+        )));
+    }
 
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-    ExecHandler::migrate(deps, env, msg)
+    Ok(ExecHandler::migrate(deps, env, msg, from_version)?
+        .add_attribute("from_version", version.version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
+/// Parses a major.minor.patch version into a tuple that orders the same way
+/// as the versions.
+fn parse_version(version: &str) -> StdResult<(u64, u64, u64)> {
+    let parts: Vec<u64> = version
+        .split('.')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| StdError::generic_err(format!("invalid contract version {}", version)))?;
+    match parts.as_slice() {
+        [major, minor, patch] => Ok((*major, *minor, *patch)),
+        _ => Err(StdError::generic_err(format!(
+            "invalid contract version {}",
+            version
+        ))),
+    }
 }
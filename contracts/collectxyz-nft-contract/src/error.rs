@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Timestamp};
 use cw721_base::ContractError as CW721ContractError;
 use thiserror::Error;
 
@@ -20,6 +20,9 @@ pub enum ContractError {
     #[error("xyz token supply has been exhausted")]
     SupplyExhausted {},
 
+    #[error("No free coordinates found within {probes} probes")]
+    NoFreeCoordinates { probes: u32 },
+
     #[error("Per-wallet token allotment exceeded")]
     WalletLimit {},
 
@@ -28,6 +31,135 @@ pub enum ContractError {
 
     #[error("Move already in progress")]
     MoveInProgress {},
+
+    #[error("No move in progress")]
+    NoMoveInProgress {},
+
+    #[error("Token is still moving and arrives in {remaining_nanos} nanoseconds")]
+    MoveNotArrived { remaining_nanos: u64 },
+
+    #[error("Token has no move left to finalize")]
+    MoveAlreadyFinalized {},
+
+    #[error("Token can't move for another {remaining_nanos} nanoseconds")]
+    MoveCooldown { remaining_nanos: u64 },
+
+    #[error("Move distance {distance} exceeds the maximum of {max_distance}")]
+    MoveTooFar { distance: u64, max_distance: u64 },
+
+    #[error("Teleporting is disabled")]
+    TeleportDisabled {},
+
+    #[error("Re-rolling coordinates is disabled")]
+    RerollDisabled {},
+
+    #[error("Token's coordinates can't be re-rolled")]
+    CannotReroll {},
+
+    #[error("Move can't be expedited")]
+    CannotExpedite {},
+
+    #[error("Public mint limit for this block has been reached")]
+    BlockMintLimit {},
+
+    #[error("Token URI must be a URI of at most {max_length} characters")]
+    InvalidTokenUri { max_length: usize },
+
+    #[error("Token {field} must be at most {max_length} characters")]
+    InvalidTokenProfile { field: String, max_length: usize },
+
+    #[error("Token profiles are disabled")]
+    TokenProfilesDisabled {},
+
+    #[error("Token isn't listed for sale")]
+    NotListed {},
+
+    #[error("No offer from this bidder on this token")]
+    NoOffer {},
+
+    #[error("Token has no pilot")]
+    NoPilot {},
+
+    #[error("No swap proposal with this id")]
+    NoSwapProposal {},
+
+    #[error("Merged tokens must be adjacent")]
+    NotAdjacent {},
+
+    #[error("Token has reached the maximum merge level")]
+    MaxMergeLevel {},
+
+    #[error("Sender is not on the allowlist")]
+    NotAllowlisted {},
+
+    #[error("Allowlist mint quota exceeded")]
+    AllowlistQuotaExceeded {},
+
+    #[error("Captcha signature has already been used")]
+    CaptchaNonceUsed {},
+
+    #[error("Captcha signature has expired")]
+    CaptchaExpired {},
+
+    #[error("Captcha signature expiry must be within {max_age_nanos} nanoseconds")]
+    CaptchaExpiryTooFar { max_age_nanos: u64 },
+
+    #[error("No ownership transfer has been proposed")]
+    NoPendingOwner {},
+
+    #[error("Ownership proposal has expired")]
+    OwnershipProposalExpired {},
+
+    #[error("CW20 token not accepted for this fee")]
+    UnacceptedCw20Token {},
+
+    #[error("Hook is already registered")]
+    HookAlreadyRegistered {},
+
+    #[error("Hook is not registered")]
+    HookNotRegistered {},
+
+    #[error("Can't register more than {max} hooks")]
+    TooManyHooks { max: usize },
+
+    #[error("Token supply can't be lowered below the {minted} tokens already minted")]
+    SupplyBelowMinted { minted: u64 },
+
+    #[error("Bounds can't exclude the coordinates of {token_id} or its moves")]
+    BoundsExcludeToken { token_id: String },
+
+    #[error("Wallet limit can't be lowered below the {held} tokens held by {address}")]
+    WalletLimitBelowHoldings { address: String, held: u64 },
+
+    #[error("Coordinates can't be made unique while tokens share ({x}, {y}, {z})")]
+    CoordinatesStacked { x: i64, y: i64, z: i64 },
+
+    #[error("Contract is paused")]
+    Paused {},
+
+    #[error("Config is frozen and can no longer be changed")]
+    ConfigFrozen {},
+
+    #[error("Coordinates are reserved")]
+    Reserved {},
+
+    #[error("Can't reserve or release more than {max} coordinates at once")]
+    ReservationBatchTooLarge { max: usize },
+
+    #[error("Insufficient energy: move requires {required} but only {available} is available")]
+    InsufficientEnergy { required: u64, available: u32 },
+
+    #[error("Token is moving and can't be transferred until it arrives at {arrival}")]
+    TransferLockedDuringMove { arrival: Timestamp },
+
+    #[error("Public minting opens at {start}")]
+    MintNotStarted { start: Timestamp },
+
+    #[error("Public minting closed at {end}")]
+    MintEnded { end: Timestamp },
+
+    #[error("This address can't mint again until {available_at}")]
+    MintCooldown { available_at: Timestamp },
 }
 
 impl From<CW721ContractError> for ContractError {
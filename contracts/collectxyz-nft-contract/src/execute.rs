@@ -1,24 +1,51 @@
 use rsa::{hash::Hash, padding::PaddingScheme, PublicKey};
 use serde_json;
 use sha2::{Digest, Sha256};
+use std::convert::TryInto;
 
+use collectxyz::cw20::{Cw20Coin, Cw20ExecuteMsg, Cw20ReceiveMsg};
 use collectxyz::nft::{
-    base64_token_image, full_token_id, numeric_token_id, Config, Coordinates, ExecuteMsg,
-    InstantiateMsg, MigrateMsg, XyzExtension, XyzTokenInfo,
+    apply_discount, full_token_id, move_attributes, numeric_token_id, CaptchaKeyAlgorithm,
+    CaptchaPayload, Config, ConfigUpdate, CoordinateBounds, Coordinates, Cw20HookMsg, ExecuteMsg,
+    FeeDestination, InstantiateMsg, Listing, MigrateMsg, MintRequest, MoveRecord, Offer,
+    PausableAction, PilotGrant, QueuedMove, Reservation, SwapLeg, SwapProposal, WithdrawBalance,
+    XyzExtension, XyzHookEvent, XyzHookMsg, XyzTokenInfo,
 };
 use cosmwasm_std::{
-    Attribute, BankMsg, Binary, Coin, DepsMut, Empty, Env, MessageInfo, Order, Response, StdError,
-    StdResult, Storage,
+    attr, from_binary, Addr, Api, Attribute, BankMsg, Binary, Coin, ContractResult, CosmosMsg,
+    Deps, DepsMut, Empty, Env, Event, MessageInfo, Order, Reply, Response, StdError, StdResult,
+    Storage, SubMsg, Timestamp, Uint128,
 };
-use cw721::{ContractInfoResponse, Cw721ReceiveMsg};
+use cw721::{ContractInfoResponse, Cw721ReceiveMsg, Expiration};
 use cw721_base::{msg::ExecuteMsg as Cw721ExecuteMsg, Cw721Contract};
+use cw_storage_plus::U64Key;
 
 use crate::error::ContractError;
-use crate::state::{load_captcha_public_key, save_captcha_public_key, tokens, CONFIG, OWNER};
+use crate::state::{
+    add_captcha_public_key, add_escrow, append_move_history, coordinate_key, coordinates_of_key,
+    decrement_owner_token_count, find_stacked_coordinates, increment_owner_token_count,
+    is_reserved, load_captcha_public_keys, load_cooldown_until, load_held_nanos,
+    load_public_mints_in_block, load_token_at, load_tokens_at, load_tokens_minted,
+    migrate_captcha_public_keys, migrate_config_bounds, migrate_held_since,
+    migrate_token_extensions, rebuild_coordinate_keys, rebuild_owner_token_counts, record_cw20_fee,
+    record_fee, record_move, record_public_mint, release_escrow, remove_captcha_public_key,
+    remove_offer, save_captcha_public_key, save_config, save_offer, start_lifetime_stats, tokens,
+    CaptchaVerifier, ALLOWLIST_MERKLE_ROOT, ALLOWLIST_MINTS, CAPTCHA_NONCES, CONFIG, CONFIG_FROZEN,
+    COORDINATE_TOKENS, ESCROW, HELD_SINCE, HOOKS, LAST_PUBLIC_MINTS, LISTINGS, MAX_HOOKS,
+    MAX_RESERVATION_BATCH, MOVE_FEES_PAID, MOVE_FEE_PAYERS, MOVE_QUEUES, MOVE_STARTED_AT,
+    NEXT_SWAP_PROPOSAL_ID, OFFERS, OWNER, OWNER_TOKEN_COUNTS, PAUSED_ACTIONS, PENDING_OWNER,
+    PILOTS, PUBLIC_MINTS_IN_BLOCK, QUEUED_COORDINATES, RESERVED_COORDINATES, SWAP_PROPOSALS,
+    TOKENS_MINTED, WALLET_LIMIT_EXEMPTIONS,
+};
 
 const XYZ: &str = "xyz";
 
-pub fn instantiate(deps: DepsMut, info: MessageInfo, msg: InstantiateMsg) -> StdResult<Response> {
+pub fn instantiate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> StdResult<Response> {
     let cw721_contract = Cw721Contract::<Coordinates, Empty>::default();
 
     let contract_info = ContractInfoResponse {
@@ -29,242 +56,4013 @@ pub fn instantiate(deps: DepsMut, info: MessageInfo, msg: InstantiateMsg) -> Std
         .contract_info
         .save(deps.storage, &contract_info)?;
 
-    CONFIG.save(deps.storage, &msg.config)?;
+    check_config(deps.api, &msg.config)?;
+    save_config(deps.storage, &env.block, &msg.config)?;
     OWNER.save(deps.storage, &info.sender.to_string())?;
+    start_lifetime_stats(deps.storage)?;
 
-    save_captcha_public_key(deps.storage, &msg.captcha_public_key)?;
+    save_captcha_public_key(
+        deps.storage,
+        &msg.captcha_public_key,
+        msg.captcha_key_algorithm,
+    )?;
 
     Ok(Response::default())
 }
 
+/// Checks that the config is internally consistent and names valid CW20
+/// token, royalty payment, and fee split addresses.
+fn check_config(api: &dyn Api, config: &Config) -> StdResult<()> {
+    config.validate()?;
+    if let Some(fee) = &config.cw20_mint_fee {
+        api.addr_validate(&fee.address)?;
+    }
+    if let Some(fee) = &config.cw20_move_fee {
+        api.addr_validate(&fee.address)?;
+    }
+    if let Some(address) = &config.royalty_payment_address {
+        api.addr_validate(address)?;
+    }
+    for (address, _) in &config.fee_split {
+        api.addr_validate(address)?;
+    }
+    for (destination, _) in &config.fee_distribution {
+        if let FeeDestination::Address(address) = destination {
+            api.addr_validate(address)?;
+        }
+    }
+    Ok(())
+}
+
 pub fn execute_mint(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    coordinates: Coordinates,
+    mint: MintRequest,
+    token_uri: Option<String>,
+    proof: Option<Vec<String>>,
+) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage, PausableAction::Mint)?;
+    let owner = OWNER.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+
+    let mut response = Response::new();
+    let allowlist_mint = info.sender != owner && proof.is_some();
+    if info.sender != owner {
+        if token_uri.is_some() {
+            return Err(ContractError::Unauthorized {});
+        }
+        response = match &proof {
+            Some(proof) => {
+                check_allowlist_mint(deps.storage, &env, &info, &config, proof, mint.coordinates)?
+            }
+            None => check_public_mint(deps.storage, &env, &info, &config, &[mint.coordinates])?,
+        };
+    } else {
+        response = refund_owner_payment(response, &config, &info);
+    }
+
+    if let Some(token_uri) = &token_uri {
+        check_token_uri(token_uri)?;
+    }
+
+    let (token_ids, hooks) = mint_tokens(
+        deps.storage,
+        deps.api,
+        &env,
+        &info,
+        &config,
+        info.sender != owner,
+        vec![(mint, token_uri.clone())],
+    )?;
+
+    if allowlist_mint {
+        ALLOWLIST_MINTS.update(deps.storage, &info.sender, |minted| -> StdResult<_> {
+            Ok(minted.unwrap_or_default() + 1)
+        })?;
+    }
+
+    Ok(response
+        .add_submessages(hooks)
+        .add_attribute("action", "mint")
+        .add_attribute("minter", info.sender)
+        .add_attributes(minted_token_attributes(
+            &config,
+            token_ids[0].clone(),
+            token_uri.as_ref(),
+        )?))
+}
+
+pub fn execute_mint_random(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
     captcha_signature: String,
+    nonce: u64,
+    expiry: Timestamp,
 ) -> Result<Response, ContractError> {
-    let cw721_contract = Cw721Contract::<Coordinates, Empty>::default();
+    check_not_paused(deps.storage, PausableAction::Mint)?;
+    let owner = OWNER.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+
+    let public_mint = info.sender != owner;
+    let claim = CaptchaClaim {
+        coordinates: None,
+        captcha_signature: &captcha_signature,
+        nonce,
+        expiry,
+    };
+    check_captcha_claim(deps.storage, deps.api, &env, &config, &info.sender, &claim)?;
+    let coordinates =
+        random_free_coordinates(deps.storage, &env, &config, public_mint, &info.sender)?;
+    // the price can depend on the coordinates, so it's charged once they're
+    // chosen
+    let response = if public_mint {
+        check_public_mint(deps.storage, &env, &info, &config, &[coordinates])?
+    } else {
+        refund_owner_payment(Response::new(), &config, &info)
+    };
+    check_mint_targets(
+        deps.storage,
+        &config,
+        public_mint,
+        &info.sender,
+        &[coordinates],
+        env.block.time,
+    )?;
+
+    CAPTCHA_NONCES.save(deps.storage, (&info.sender, nonce.into()), &Empty {})?;
+    let (token_ids, hooks) = create_tokens(
+        deps.storage,
+        &env,
+        &config,
+        &info.sender,
+        public_mint,
+        vec![(coordinates, None)],
+    )?;
+
+    Ok(response
+        .add_submessages(hooks)
+        .add_attribute("action", "mint")
+        .add_attribute("minter", info.sender)
+        .add_attributes(minted_token_attributes(
+            &config,
+            token_ids[0].clone(),
+            None,
+        )?)
+        .add_attribute("x", coordinates.x.to_string())
+        .add_attribute("y", coordinates.y.to_string())
+        .add_attribute("z", coordinates.z.to_string()))
+}
 
+pub fn execute_mint_for(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    coordinates: Coordinates,
+) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage, PausableAction::Mint)?;
     let owner = OWNER.load(deps.storage)?;
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
     let config = CONFIG.load(deps.storage)?;
-    let num_tokens = cw721_contract.token_count(deps.storage)?;
+    let recipient = deps.api.addr_validate(&recipient)?;
 
-    if num_tokens >= config.token_supply {
-        return Err(ContractError::SupplyExhausted {});
+    check_mint_targets(
+        deps.storage,
+        &config,
+        false,
+        &recipient,
+        &[coordinates],
+        env.block.time,
+    )?;
+    if config.mint_for_wallet_limit {
+        check_wallet_limit(deps.storage, recipient.clone(), config.wallet_limit, 1)?;
+    }
+    let (token_ids, hooks) = create_tokens(
+        deps.storage,
+        &env,
+        &config,
+        &recipient,
+        false,
+        vec![(coordinates, None)],
+    )?;
+
+    Ok(refund_owner_payment(Response::new(), &config, &info)
+        .add_submessages(hooks)
+        .add_attribute("action", "mint")
+        .add_attribute("minter", info.sender)
+        .add_attribute("owner", recipient)
+        .add_attributes(minted_token_attributes(
+            &config,
+            token_ids[0].clone(),
+            None,
+        )?))
+}
+
+pub fn execute_batch_mint(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    mints: Vec<MintRequest>,
+) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage, PausableAction::Mint)?;
+    let owner = OWNER.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+
+    if mints.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "batch mint requires at least one entry",
+        )));
     }
 
+    let mut response = Response::new();
     if info.sender != owner {
-        if !config.public_minting_enabled {
-            return Err(ContractError::Unauthorized {});
+        let coordinates: Vec<_> = mints.iter().map(|mint| mint.coordinates).collect();
+        response = check_public_mint(deps.storage, &env, &info, &config, &coordinates)?;
+    } else {
+        response = refund_owner_payment(response, &config, &info);
+    }
+
+    let (token_ids, hooks) = mint_tokens(
+        deps.storage,
+        deps.api,
+        &env,
+        &info,
+        &config,
+        info.sender != owner,
+        mints.into_iter().map(|mint| (mint, None)).collect(),
+    )?;
+
+    response = response
+        .add_submessages(hooks)
+        .add_attribute("action", "batch_mint")
+        .add_attribute("minter", info.sender);
+    for token_id in token_ids {
+        response = response.add_attributes(minted_token_attributes(&config, token_id, None)?);
+    }
+    Ok(response)
+}
+
+/// Returns the attributes identifying a newly minted token: its numeric id
+/// and, if it has one, its token URI.
+fn minted_token_attributes(
+    config: &Config,
+    token_id: String,
+    token_uri: Option<&String>,
+) -> StdResult<Vec<Attribute>> {
+    let token_uri = config.token_uri(&token_id, token_uri);
+    let mut attributes = vec![attr("token_id", numeric_token_id(token_id)?)];
+    if let Some(token_uri) = token_uri {
+        attributes.push(attr("token_uri", token_uri));
+    }
+    Ok(attributes)
+}
+
+/// Checks that a non-owner sender may publicly mint a token at each of the
+/// given coordinates, returning a response carrying any fee burn and
+/// overpayment refund messages.
+fn check_public_mint(
+    storage: &mut dyn Storage,
+    env: &Env,
+    info: &MessageInfo,
+    config: &Config,
+    coordinates: &[Coordinates],
+) -> Result<Response, ContractError> {
+    check_public_mint_open(storage, env, config, &info.sender, coordinates.len() as u32)?;
+    let mints = priced_mints(storage, config, &info.sender, coordinates)?;
+    let mint_prices = config.mint_prices_at(load_tokens_minted(storage)?, &mints)?;
+    charge_mint_fee(storage, info, config, &mint_prices)
+}
+
+/// Pairs each of the coordinates minter is minting at with whether they're
+/// adjacent to a token minter already owns, as Config::mint_prices_at takes
+/// them.
+pub fn priced_mints(
+    storage: &dyn Storage,
+    config: &Config,
+    minter: &Addr,
+    coordinates: &[Coordinates],
+) -> StdResult<Vec<(Coordinates, bool)>> {
+    coordinates
+        .iter()
+        .map(|coords| {
+            Ok((
+                *coords,
+                adjacent_to_owned(storage, config, minter, *coords)?,
+            ))
+        })
+        .collect()
+}
+
+/// Returns whether coords share a face with the coordinates of a token owner
+/// holds, probing the six neighboring points in COORDINATE_TOKENS rather than
+/// owner's holdings. Without an adjacency discount, nothing is probed.
+fn adjacent_to_owned(
+    storage: &dyn Storage,
+    config: &Config,
+    owner: &Addr,
+    coords: Coordinates,
+) -> StdResult<bool> {
+    if config.adjacent_mint_discount_bps == 0 {
+        return Ok(false);
+    }
+    for neighbor in coords.face_neighbors() {
+        let occupants = load_tokens_at(storage, &COORDINATE_TOKENS, &neighbor, None, usize::MAX)?;
+        for token_id in occupants {
+            if tokens().load(storage, &token_id)?.owner == *owner {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Checks that public minting is enabled, that the block time is within the
+/// mint window, and that the mint rate limits let minter publicly mint count
+/// more tokens.
+pub fn check_public_mint_open(
+    storage: &dyn Storage,
+    env: &Env,
+    config: &Config,
+    minter: &Addr,
+    count: u32,
+) -> Result<(), ContractError> {
+    check_public_minting_enabled(env, config)?;
+    check_mint_rate_limits(storage, env, config, minter, count)
+}
+
+/// Checks that public minting is enabled and the block time is within the
+/// mint window.
+fn check_public_minting_enabled(env: &Env, config: &Config) -> Result<(), ContractError> {
+    if !config.public_minting_enabled {
+        return Err(ContractError::Unauthorized {});
+    }
+    if let Some(start) = config.mint_start {
+        if env.block.time < start {
+            return Err(ContractError::MintNotStarted { start });
+        }
+    }
+    if let Some(end) = config.mint_end {
+        if env.block.time >= end {
+            return Err(ContractError::MintEnded { end });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a non-owner sender may mint a token at coordinates through
+/// the allowlist with the given proof, returning a response carrying any fee
+/// burn and overpayment refund messages.
+fn check_allowlist_mint(
+    storage: &mut dyn Storage,
+    env: &Env,
+    info: &MessageInfo,
+    config: &Config,
+    proof: &[String],
+    coordinates: Coordinates,
+) -> Result<Response, ContractError> {
+    if !config.allowlist_mint_enabled {
+        return Err(ContractError::Unauthorized {});
+    }
+    let merkle_root = ALLOWLIST_MERKLE_ROOT
+        .may_load(storage)?
+        .ok_or(ContractError::NotAllowlisted {})?;
+    if !verify_allowlist_proof(&merkle_root, info.sender.as_str(), proof)? {
+        return Err(ContractError::NotAllowlisted {});
+    }
+    let minted = ALLOWLIST_MINTS
+        .may_load(storage, &info.sender)?
+        .unwrap_or_default();
+    if minted >= config.allowlist_mint_quota {
+        return Err(ContractError::AllowlistQuotaExceeded {});
+    }
+    check_mint_rate_limits(storage, env, config, &info.sender, 1)?;
+
+    let mint_prices = match &config.allowlist_mint_fee {
+        Some(fee) => vec![fee.clone()],
+        None => {
+            let mints = priced_mints(storage, config, &info.sender, &[coordinates])?;
+            config.mint_prices_at(load_tokens_minted(storage)?, &mints)?
         }
+    };
+    charge_mint_fee(storage, info, config, &mint_prices)
+}
 
-        // check that mint fee is covered if sender isn't an owner
-        check_sufficient_funds(info.funds, config.mint_fee)?;
+/// Checks that a CW20 sender may publicly mint a token, paying mint_fee out
+/// of amount of the token contract's tokens, returning a response carrying
+/// any fee burn and overpayment refund messages.
+fn check_cw20_mint(
+    storage: &mut dyn Storage,
+    env: &Env,
+    sender: &Addr,
+    token: &Addr,
+    amount: Uint128,
+    config: &Config,
+    mint_fee: Uint128,
+) -> Result<Response, ContractError> {
+    check_public_minting_enabled(env, config)?;
+    let refund = charge_cw20_fee(config, sender, token, amount, mint_fee)?;
+    let mut response = Response::new().add_messages(refund);
 
-        // check that wallet limit isn't exceeded if sender isn't an owner
-        check_wallet_limit(deps.storage, info.sender.clone(), config.wallet_limit)?;
+    // burn the collected fee rather than keeping it in the contract
+    if config.burn_mint_fees && !mint_fee.is_zero() {
+        record_cw20_fee(storage, token, mint_fee)?;
+        let burn = Cw20ExecuteMsg::Burn { amount: mint_fee };
+        response = response.add_message(burn.into_cosmos_msg(token)?);
+    } else {
+        response = distribute_cw20_fee(storage, response, config, token, mint_fee)?;
     }
 
-    // check that the coordinates are valid and available
-    check_coordinates(deps.storage, &coordinates)?;
+    check_mint_rate_limits(storage, env, config, sender, 1)?;
+    Ok(response)
+}
 
-    // check that the recaptcha lambda signature is valid
-    check_captcha_signature(deps.storage, &coordinates, &captcha_signature)?;
+/// Checks that the sent funds cover one of mint_prices, the accepted total
+/// fees for the mint, returning a response carrying any fee burn and
+/// overpayment refund messages.
+fn charge_mint_fee(
+    storage: &mut dyn Storage,
+    info: &MessageInfo,
+    config: &Config,
+    mint_prices: &[Coin],
+) -> Result<Response, ContractError> {
+    let mint_fee = check_mint_payment(&info.funds, mint_prices)?;
+    let mut response = refund_overpayment(Response::new(), config, info, &mint_fee);
 
-    // create the token
-    let num_tokens = 1 + num_tokens;
-    let token_id = format!("xyz #{}", &num_tokens);
-    let token = XyzTokenInfo {
-        owner: info.sender.clone(),
-        approvals: vec![],
-        name: token_id.clone(),
-        description: String::from("Explore the metaverse, starting with xyz."),
-        image: Some(base64_token_image(&coordinates)),
-        extension: XyzExtension {
-            coordinates,
-            prev_coordinates: None,
-            arrival: env.block.time,
-        },
+    // burn the collected fee rather than keeping it in the contract
+    if config.burn_mint_fees && !mint_fee.amount.is_zero() {
+        record_fee(storage, &mint_fee)?;
+        response = response.add_message(BankMsg::Burn {
+            amount: vec![mint_fee],
+        });
+    } else {
+        response = distribute_fee(storage, response, config, &mint_fee)?;
+    }
+
+    Ok(response)
+}
+
+/// Checks that count more public mints fit in the current block and that
+/// minter's mint cooldown has passed. A batch of mints counts once toward
+/// the cooldown.
+fn check_mint_rate_limits(
+    storage: &dyn Storage,
+    env: &Env,
+    config: &Config,
+    minter: &Addr,
+    count: u32,
+) -> Result<(), ContractError> {
+    if let Some(max_mints) = config.max_public_mints_per_block {
+        let block_mints = load_public_mints_in_block(storage, env.block.height)?;
+        if block_mints.saturating_add(count) > max_mints {
+            return Err(ContractError::BlockMintLimit {});
+        }
+    }
+    if config.per_address_mint_cooldown_nanos > 0 {
+        if let Some(last_mint) = LAST_PUBLIC_MINTS.may_load(storage, minter)? {
+            let available_at = last_mint.plus_nanos(config.per_address_mint_cooldown_nanos);
+            if env.block.time < available_at {
+                return Err(ContractError::MintCooldown { available_at });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Creates a token for the sender at each of the given coordinates. Every
+/// entry's supply, wallet limit (for public mints), coordinates, and captcha
+/// signature are checked before any token is saved, so either all of the
+/// tokens are minted or none are.
+fn mint_tokens(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    env: &Env,
+    info: &MessageInfo,
+    config: &Config,
+    public_mint: bool,
+    mints: Vec<(MintRequest, Option<String>)>,
+) -> Result<(Vec<String>, Vec<SubMsg>), ContractError> {
+    let coordinates: Vec<Coordinates> = mints.iter().map(|(mint, _)| mint.coordinates).collect();
+    for coords in coordinates.iter() {
+        settle_coordinates(storage, config, coords, env.block.time)?;
+    }
+    check_mint_targets(
+        storage,
+        config,
+        public_mint,
+        &info.sender,
+        &coordinates,
+        env.block.time,
+    )?;
+
+    for (i, (mint, _)) in mints.iter().enumerate() {
+        // check that the recaptcha lambda signature is valid and unused,
+        // including among earlier entries of the same batch
+        check_captcha_signature(storage, api, env, config, &info.sender, mint)?;
+        if mints[..i]
+            .iter()
+            .any(|(other, _)| other.nonce == mint.nonce)
+        {
+            return Err(ContractError::CaptchaNonceUsed {});
+        }
+    }
+
+    let mut targets = vec![];
+    for (mint, token_uri) in mints {
+        CAPTCHA_NONCES.save(storage, (&info.sender, mint.nonce.into()), &Empty {})?;
+        targets.push((mint.coordinates, token_uri));
+    }
+    create_tokens(storage, env, config, &info.sender, public_mint, targets)
+}
+
+/// Creates a token for owner at each of the given coordinates, which must
+/// already have been checked with check_mint_targets.
+fn create_tokens(
+    storage: &mut dyn Storage,
+    env: &Env,
+    config: &Config,
+    owner: &Addr,
+    public_mint: bool,
+    targets: Vec<(Coordinates, Option<String>)>,
+) -> Result<(Vec<String>, Vec<SubMsg>), ContractError> {
+    let cw721_contract = Cw721Contract::<Coordinates, Empty>::default();
+    let count = targets.len() as u32;
+    let num_tokens = load_tokens_minted(storage)?;
+
+    let mut token_ids = vec![];
+    let mut hooks = vec![];
+    for (i, (coordinates, token_uri)) in targets.into_iter().enumerate() {
+        RESERVED_COORDINATES.remove(storage, &coordinates.to_bytes());
+
+        let token_id = format!("xyz #{}", num_tokens + 1 + i as u64);
+        let token = XyzTokenInfo {
+            owner: owner.clone(),
+            approvals: vec![],
+            name: token_id.clone(),
+            description: String::from("Explore the metaverse, starting with xyz."),
+            image: config.token_image(&coordinates),
+            extension: XyzExtension {
+                coordinates,
+                prev_coordinates: None,
+                arrival: env.block.time,
+                energy: config.energy_max,
+                energy_updated_at: env.block.time,
+                level: 0,
+                token_uri,
+                minted_at: env.block.time,
+                minted_height: env.block.height,
+                rerolled: false,
+                name: None,
+                description: None,
+                moves_count: 0,
+                total_distance: 0,
+            },
+        };
+        tokens().update(storage, &token_id, |old| match old {
+            Some(_) => Err(ContractError::Claimed {}),
+            None => Ok(token),
+        })?;
+        occupy_coordinates(storage, &coordinates, &token_id)?;
+
+        cw721_contract.increment_tokens(storage)?;
+        increment_owner_token_count(storage, owner)?;
+        HELD_SINCE.save(storage, &token_id, &env.block.time)?;
+        hooks.extend(hook_messages(
+            storage,
+            XyzHookMsg {
+                event: XyzHookEvent::Mint,
+                token_id: numeric_token_id(token_id.clone())?,
+                owner: owner.to_string(),
+                prev_coordinates: None,
+                coordinates: Some(coordinates),
+                arrival: env.block.time,
+            },
+        )?);
+        token_ids.push(token_id);
+    }
+
+    TOKENS_MINTED.save(storage, &(num_tokens + count as u64))?;
+
+    if public_mint && config.max_public_mints_per_block.is_some() {
+        let block_mints = load_public_mints_in_block(storage, env.block.height)?;
+        PUBLIC_MINTS_IN_BLOCK.save(storage, &(env.block.height, block_mints + count))?;
+    }
+    if public_mint {
+        record_public_mint(storage, config, owner, env.block.time)?;
+    }
+
+    Ok((token_ids, hooks))
+}
+
+/// Checks that minter can mint a token at each of the given coordinates:
+/// that the supply and, for public mints, the minter's wallet limit leave
+/// room for them, and that each is valid, unreserved at time (for public
+/// mints), and available, including among earlier entries of the same batch. Captcha
+/// signatures and fees are checked separately.
+pub fn check_mint_targets(
+    storage: &dyn Storage,
+    config: &Config,
+    public_mint: bool,
+    minter: &Addr,
+    coordinates: &[Coordinates],
+    time: Timestamp,
+) -> Result<(), ContractError> {
+    let count = coordinates.len() as u32;
+    if load_tokens_minted(storage)?.saturating_add(count.into()) > config.token_supply {
+        return Err(ContractError::SupplyExhausted {});
+    }
+
+    // check that wallet limit isn't exceeded if sender isn't an owner
+    if public_mint {
+        check_wallet_limit(storage, minter.clone(), config.wallet_limit, count)?;
+    }
+
+    for (i, coords) in coordinates.iter().enumerate() {
+        check_coordinates(storage, coords, public_mint, time)?;
+        if coordinates[..i].contains(coords) {
+            return Err(ContractError::Claimed {});
+        }
+    }
+    Ok(())
+}
+
+fn check_sufficient_funds(funds: Vec<Coin>, required: Coin) -> Result<(), ContractError> {
+    if required.amount.u128() == 0 {
+        return Ok(());
+    }
+    let sent_sufficient_funds = funds.iter().any(|coin| {
+        // check if a given sent coin matches denom
+        // and has sufficient amount
+        coin.denom == required.denom && coin.amount.u128() >= required.amount.u128()
+    });
+    if sent_sufficient_funds {
+        Ok(())
+    } else {
+        Err(ContractError::Std(StdError::generic_err(
+            "insufficient funds sent",
+        )))
+    }
+}
+
+/// Checks that the sent funds cover one of the given prices, returning the
+/// price they're paying. Funds in more than one of the prices' denoms are
+/// rejected, since it's ambiguous which price is being paid.
+fn check_mint_payment(funds: &[Coin], prices: &[Coin]) -> Result<Coin, ContractError> {
+    if let [price] = prices {
+        check_sufficient_funds(funds.to_vec(), price.clone())?;
+        return Ok(price.clone());
+    }
+    if let Some(price) = prices.iter().find(|price| price.amount.is_zero()) {
+        return Ok(price.clone());
+    }
+
+    let sent = |denom: &str| {
+        funds
+            .iter()
+            .filter(|coin| coin.denom == denom)
+            .map(|coin| coin.amount)
+            .sum::<Uint128>()
     };
-    tokens().update(deps.storage, &token_id, |old| match old {
-        Some(_) => Err(ContractError::Claimed {}),
-        None => Ok(token),
-    })?;
+    let paid: Vec<&Coin> = prices
+        .iter()
+        .filter(|price| !sent(&price.denom).is_zero())
+        .collect();
+    let price_list = prices
+        .iter()
+        .map(|price| price.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    match paid.as_slice() {
+        [price] if sent(&price.denom) >= price.amount => Ok((*price).clone()),
+        [_, _, ..] => Err(ContractError::Std(StdError::generic_err(format!(
+            "mint fee must be paid in a single denom, one of: {}",
+            price_list
+        )))),
+        _ => Err(ContractError::Std(StdError::generic_err(format!(
+            "insufficient funds sent, mint costs one of: {}",
+            price_list
+        )))),
+    }
+}
+
+/// Adds a refund of any funds sent in excess of fee back to the sender to
+/// the response, if config allows refunding them, along with a refund
+/// attribute listing the refunded coins. Assumes the sent funds already
+/// cover the fee.
+fn refund_overpayment(
+    response: Response,
+    config: &Config,
+    info: &MessageInfo,
+    fee: &Coin,
+) -> Response {
+    if !config.refund_overpayment {
+        return response;
+    }
+    let mut fee_remaining = fee.amount;
+    let excess: Vec<Coin> = info
+        .funds
+        .iter()
+        .filter_map(|coin| {
+            let mut coin = coin.clone();
+            if coin.denom == fee.denom {
+                let paid = coin.amount.min(fee_remaining);
+                fee_remaining -= paid;
+                coin.amount -= paid;
+            }
+            if coin.amount.is_zero() {
+                None
+            } else {
+                Some(coin)
+            }
+        })
+        .collect();
+    if excess.is_empty() {
+        return response;
+    }
+    let refund: Vec<String> = excess.iter().map(Coin::to_string).collect();
+    response
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: excess,
+        })
+        .add_attribute("refund", refund.join(","))
+}
+
+/// Records a fee collected in native coins in the lifetime fee totals, and
+/// adds a send or burn of each of config.fee_distribution's shares of it to
+/// the response, along with fee_recipient and fee_share attributes for each
+/// share.
+fn distribute_fee(
+    storage: &mut dyn Storage,
+    mut response: Response,
+    config: &Config,
+    fee: &Coin,
+) -> StdResult<Response> {
+    record_fee(storage, fee)?;
+    for (destination, amount) in config.fee_distribution_shares(fee.amount) {
+        let share = Coin::new(amount.u128(), &fee.denom);
+        let (recipient, msg) = match destination {
+            FeeDestination::Address(address) => (
+                address.as_str(),
+                BankMsg::Send {
+                    to_address: address.clone(),
+                    amount: vec![share.clone()],
+                },
+            ),
+            FeeDestination::Burn => (
+                "burn",
+                BankMsg::Burn {
+                    amount: vec![share.clone()],
+                },
+            ),
+        };
+        response = response
+            .add_message(msg)
+            .add_attribute("fee_recipient", recipient)
+            .add_attribute("fee_share", share.to_string());
+    }
+    Ok(response)
+}
+
+/// Records a fee of amount collected in the token in the lifetime fee
+/// totals, and adds a transfer or burn of each of config.fee_distribution's
+/// shares of it to the response, along with fee_recipient and fee_share
+/// attributes for each share.
+fn distribute_cw20_fee(
+    storage: &mut dyn Storage,
+    mut response: Response,
+    config: &Config,
+    token: &Addr,
+    amount: Uint128,
+) -> StdResult<Response> {
+    record_cw20_fee(storage, token, amount)?;
+    for (destination, share) in config.fee_distribution_shares(amount) {
+        let (recipient, msg) = match destination {
+            FeeDestination::Address(address) => (
+                address.as_str(),
+                Cw20ExecuteMsg::Transfer {
+                    recipient: address.clone(),
+                    amount: share,
+                },
+            ),
+            FeeDestination::Burn => ("burn", Cw20ExecuteMsg::Burn { amount: share }),
+        };
+        response = response
+            .add_message(msg.into_cosmos_msg(token)?)
+            .add_attribute("fee_recipient", recipient)
+            .add_attribute("fee_share", format!("{}{}", share, token));
+    }
+    Ok(response)
+}
+
+/// Adds a refund of everything the owner sent to the response, if config
+/// allows refunding overpayment, since the owner is never charged a fee.
+fn refund_owner_payment(response: Response, config: &Config, info: &MessageInfo) -> Response {
+    let no_fee = Coin::new(0, &config.base_move_fee.denom);
+    refund_overpayment(response, config, info, &no_fee)
+}
+
+/// Adds a refund of everything the owner paid for a move to the response,
+/// whether in native coins or CW20 tokens, under the same rule as
+/// refund_owner_payment.
+fn refund_owner_move_payment(
+    response: Response,
+    config: &Config,
+    sender: &Addr,
+    payment: &MovePayment,
+) -> Result<Response, ContractError> {
+    match payment {
+        MovePayment::Native(funds) => {
+            let info = MessageInfo {
+                sender: sender.clone(),
+                funds: funds.clone(),
+            };
+            Ok(refund_owner_payment(response, config, &info))
+        }
+        MovePayment::Cw20 { token, amount } => Ok(response.add_messages(charge_cw20_fee(
+            config,
+            sender,
+            token,
+            *amount,
+            Uint128::zero(),
+        )?)),
+    }
+}
+
+/// Checks that a CW20 payment of amount covers fee, returning a transfer of
+/// any excess back to the sender if config allows refunding it.
+fn charge_cw20_fee(
+    config: &Config,
+    sender: &Addr,
+    token: &Addr,
+    amount: Uint128,
+    fee: Uint128,
+) -> Result<Option<CosmosMsg>, ContractError> {
+    if amount < fee {
+        return Err(ContractError::Std(StdError::generic_err(
+            "insufficient funds sent",
+        )));
+    }
+    let excess = amount - fee;
+    if !config.refund_overpayment || excess.is_zero() {
+        return Ok(None);
+    }
+    let refund = Cw20ExecuteMsg::Transfer {
+        recipient: sender.to_string(),
+        amount: excess,
+    };
+    Ok(Some(refund.into_cosmos_msg(token)?))
+}
+
+fn check_wallet_limit(
+    storage: &dyn Storage,
+    owner: cosmwasm_std::Addr,
+    limit: u32,
+    new_tokens: u32,
+) -> Result<(), ContractError> {
+    if WALLET_LIMIT_EXEMPTIONS.has(storage, &owner) {
+        return Ok(());
+    }
+
+    let num_wallet_tokens = tokens()
+        .idx
+        .owner
+        .prefix(owner)
+        .range(storage, None, None, Order::Ascending)
+        .count();
+
+    if num_wallet_tokens + new_tokens as usize > limit as usize {
+        Err(ContractError::WalletLimit {})
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that a mint's captcha signature was signed for the sender,
+/// hasn't expired, and hasn't been used before. Shared with the
+/// VerifyCaptcha query, so that it reports exactly what minting would.
+pub fn check_captcha_signature(
+    storage: &dyn Storage,
+    api: &dyn Api,
+    env: &Env,
+    config: &Config,
+    sender: &Addr,
+    mint: &MintRequest,
+) -> Result<(), ContractError> {
+    let claim = CaptchaClaim {
+        coordinates: Some(mint.coordinates),
+        captcha_signature: &mint.captcha_signature,
+        nonce: mint.nonce,
+        expiry: mint.expiry,
+    };
+    check_captcha_claim(storage, api, env, config, sender, &claim)
+}
+
+/// What a captcha signature authorizes: a mint at the given coordinates, or
+/// at coordinates the contract picks if None.
+struct CaptchaClaim<'a> {
+    coordinates: Option<Coordinates>,
+    captcha_signature: &'a str,
+    nonce: u64,
+    expiry: Timestamp,
+}
+
+fn check_captcha_claim(
+    storage: &dyn Storage,
+    api: &dyn Api,
+    env: &Env,
+    config: &Config,
+    sender: &Addr,
+    mint: &CaptchaClaim,
+) -> Result<(), ContractError> {
+    let keys = load_captcha_public_keys(storage, &env.block)?;
+
+    let signature_bytes = base64::decode(mint.captcha_signature)
+        .map_err(|e| StdError::invalid_base64(e.to_string()))?;
+
+    let payload = encode_captcha_payload(sender, mint.coordinates, mint.nonce, mint.expiry);
+    let digest = Sha256::digest(&payload);
+
+    // a signature that doesn't parse under a key's algorithm simply fails
+    // to verify under that key
+    let verified = keys.iter().any(|key| match key {
+        CaptchaVerifier::Rsa(key) => key
+            .verify(
+                PaddingScheme::PKCS1v15Sign {
+                    hash: Some(Hash::SHA2_256),
+                },
+                &digest,
+                &signature_bytes,
+            )
+            .is_ok(),
+        CaptchaVerifier::Secp256k1(key) => api
+            .secp256k1_verify(&digest, &signature_bytes, key)
+            .unwrap_or(false),
+        CaptchaVerifier::Ed25519(key) => api
+            .ed25519_verify(&payload, &signature_bytes, key)
+            .unwrap_or(false),
+    });
+    if !verified {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if env.block.time > mint.expiry {
+        return Err(ContractError::CaptchaExpired {});
+    }
+    if mint.expiry.nanos().saturating_sub(env.block.time.nanos()) > config.max_captcha_age_nanos {
+        return Err(ContractError::CaptchaExpiryTooFar {
+            max_age_nanos: config.max_captcha_age_nanos,
+        });
+    }
+    if CAPTCHA_NONCES.has(storage, (sender, mint.nonce.into())) {
+        return Err(ContractError::CaptchaNonceUsed {});
+    }
+    Ok(())
+}
+
+/// Returns whether proof shows that address is a leaf of the merkle tree with
+/// the given hex-encoded root (see ExecuteMsg::UpdateAllowlistMerkleRoot).
+pub fn verify_allowlist_proof(
+    merkle_root: &str,
+    address: &str,
+    proof: &[String],
+) -> StdResult<bool> {
+    let root = decode_merkle_hash(merkle_root)?;
+    let mut hash: [u8; 32] = Sha256::digest(address.as_bytes()).into();
+    for sibling in proof {
+        let sibling = decode_merkle_hash(sibling)?;
+        let (first, second) = if hash <= sibling {
+            (hash, sibling)
+        } else {
+            (sibling, hash)
+        };
+        hash = Sha256::new().chain(first).chain(second).finalize().into();
+    }
+    Ok(hash == root)
+}
+
+fn decode_merkle_hash(hash: &str) -> StdResult<[u8; 32]> {
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(hash, &mut bytes)
+        .map_err(|_| StdError::generic_err("merkle hashes must be 32 hex-encoded bytes"))?;
+    Ok(bytes)
+}
+
+/// Returns the bytes a captcha signature must cover for the given coordinates.
+pub fn captcha_payload(
+    sender: &Addr,
+    coordinates: &Coordinates,
+    nonce: u64,
+    expiry: Timestamp,
+) -> Vec<u8> {
+    encode_captcha_payload(sender, Some(*coordinates), nonce, expiry)
+}
+
+/// Returns the bytes a captcha signature must cover for a MintRandom.
+pub fn random_mint_captcha_payload(sender: &Addr, nonce: u64, expiry: Timestamp) -> Vec<u8> {
+    encode_captcha_payload(sender, None, nonce, expiry)
+}
+
+fn encode_captcha_payload(
+    sender: &Addr,
+    coordinates: Option<Coordinates>,
+    nonce: u64,
+    expiry: Timestamp,
+) -> Vec<u8> {
+    let payload = CaptchaPayload {
+        sender: sender.to_string(),
+        coordinates,
+        nonce,
+        expiry,
+    };
+    serde_json::to_vec(&payload).unwrap()
+}
+
+/// The most points MintRandom examines before giving up
+pub const MAX_RANDOM_MINT_PROBES: u32 = 64;
+
+/// Picks a free point for a MintRandom. The first point is derived from the
+/// block, the minter, and the number of tokens minted so far; if it's taken,
+/// points are examined in order from it, stepping along the x axis first and
+/// wrapping within the bounds, up to MAX_RANDOM_MINT_PROBES points in all.
+/// Each point is settled before it's examined, so points that tokens have
+/// since departed from count as free.
+pub fn random_free_coordinates(
+    storage: &mut dyn Storage,
+    env: &Env,
+    config: &Config,
+    public_mint: bool,
+    minter: &Addr,
+) -> Result<Coordinates, ContractError> {
+    let seed = Sha256::new()
+        .chain(env.block.time.nanos().to_be_bytes())
+        .chain(env.block.height.to_be_bytes())
+        .chain(minter.as_bytes())
+        .chain(load_tokens_minted(storage)?.to_be_bytes())
+        .finalize();
+    let pick = |chunk: &[u8], min: i64, max: i64| {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(chunk);
+        let span = (max as i128 - min as i128) as u128 + 1;
+        (min as i128 + (u64::from_be_bytes(bytes) as u128 % span) as i128) as i64
+    };
+    let bounds = &config.bounds;
+    let mut coords = Coordinates {
+        x: pick(&seed[0..8], bounds.x_min, bounds.x_max),
+        y: pick(&seed[8..16], bounds.y_min, bounds.y_max),
+        z: pick(&seed[16..24], bounds.z_min, bounds.z_max),
+    };
+    for _ in 0..MAX_RANDOM_MINT_PROBES {
+        settle_coordinates(storage, config, &coords, env.block.time)?;
+        if check_coordinates(storage, &coords, public_mint, env.block.time).is_ok() {
+            return Ok(coords);
+        }
+        coords = next_coordinates(bounds, coords);
+    }
+    Err(ContractError::NoFreeCoordinates {
+        probes: MAX_RANDOM_MINT_PROBES,
+    })
+}
+
+/// Returns the point after coords within bounds, stepping along the x axis
+/// and carrying into y and then z, wrapping back to the minimum corner.
+fn next_coordinates(bounds: &CoordinateBounds, coords: Coordinates) -> Coordinates {
+    let step = |value: i64, min: i64, max: i64| {
+        if value >= max {
+            (min, true)
+        } else {
+            (value + 1, false)
+        }
+    };
+    let (x, carry) = step(coords.x, bounds.x_min, bounds.x_max);
+    if !carry {
+        return Coordinates { x, ..coords };
+    }
+    let (y, carry) = step(coords.y, bounds.y_min, bounds.y_max);
+    if !carry {
+        return Coordinates { x, y, ..coords };
+    }
+    let (z, _) = step(coords.z, bounds.z_min, bounds.z_max);
+    Coordinates { x, y, z }
+}
+
+const MAX_TOKEN_URI_LENGTH: usize = 512;
+const MAX_TOKEN_NAME_LENGTH: usize = 64;
+const MAX_TOKEN_DESCRIPTION_LENGTH: usize = 512;
+
+/// Removes control characters and surrounding whitespace from a token
+/// profile field, returning None if nothing is left, and checks that it's
+/// at most max_length characters.
+fn clean_profile_field(
+    value: &str,
+    field: &str,
+    max_length: usize,
+) -> Result<Option<String>, ContractError> {
+    let value: String = value.chars().filter(|c| !c.is_control()).collect();
+    let value = value.trim();
+    if value.chars().count() > max_length {
+        return Err(ContractError::InvalidTokenProfile {
+            field: field.to_string(),
+            max_length,
+        });
+    }
+    Ok(Some(value.to_string()).filter(|value| !value.is_empty()))
+}
+
+/// Checks that a token URI is of reasonable length and starts with a scheme,
+/// e.g. "https:" or "ipfs:".
+fn check_token_uri(token_uri: &str) -> Result<(), ContractError> {
+    let has_scheme = match token_uri.split_once(':') {
+        Some((scheme, rest)) => {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                && !rest.is_empty()
+        }
+        None => false,
+    };
+    if has_scheme
+        && token_uri.len() <= MAX_TOKEN_URI_LENGTH
+        && !token_uri.contains(char::is_whitespace)
+    {
+        Ok(())
+    } else {
+        Err(ContractError::InvalidTokenUri {
+            max_length: MAX_TOKEN_URI_LENGTH,
+        })
+    }
+}
+
+/// Checks that a token can be placed at coords at the given time: they're
+/// in bounds, free while tokens can't stack, and unreserved unless the
+/// contract owner is placing it.
+fn check_coordinates(
+    storage: &dyn Storage,
+    coords: &Coordinates,
+    public: bool,
+    time: Timestamp,
+) -> Result<(), ContractError> {
+    let config = CONFIG.load(storage)?;
+    config.check_bounds(*coords).map_err(ContractError::Std)?;
+    if config.unique_coordinates && coordinates_claimed(storage, coords)? {
+        Err(ContractError::Claimed {})
+    } else if public && is_reserved(storage, coords, time)? {
+        Err(ContractError::Reserved {})
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns whether any token occupies, is moving to, or is queued to move to
+/// the coordinates.
+fn coordinates_claimed(storage: &dyn Storage, coords: &Coordinates) -> StdResult<bool> {
+    Ok(
+        load_token_at(storage, &COORDINATE_TOKENS, coords)?.is_some()
+            || load_token_at(storage, &QUEUED_COORDINATES, coords)?.is_some(),
+    )
+}
+
+/// Starts any queued moves due by the given time for the token at coords,
+/// so that coordinates it has since departed from are freed before they're
+/// checked. Coordinates aren't checked while tokens can stack, so there's
+/// nothing to settle.
+fn settle_coordinates(
+    storage: &mut dyn Storage,
+    config: &Config,
+    coords: &Coordinates,
+    time: Timestamp,
+) -> Result<(), ContractError> {
+    if !config.unique_coordinates {
+        return Ok(());
+    }
+    if let Some(token_id) = load_token_at(storage, &COORDINATE_TOKENS, coords)? {
+        advance_move_queue(storage, config, &token_id, time)?;
+    }
+    Ok(())
+}
+
+/// Checks that a move from start to end is within config.max_move_distance.
+fn check_move_distance(
+    config: &Config,
+    start: Coordinates,
+    end: Coordinates,
+) -> Result<(), ContractError> {
+    let distance = config.distance(start, end);
+    if config.max_move_distance != 0 && distance > config.max_move_distance {
+        return Err(ContractError::MoveTooFar {
+            distance,
+            max_distance: config.max_move_distance,
+        });
+    }
+    Ok(())
+}
+
+/// Records the token as occupying the coordinates. Every write that places a
+/// token at new coordinates goes through here, and while
+/// Config::unique_coordinates is set, the check-and-set fails with Claimed if
+/// another token holds the coordinates or is queued to move there, whatever
+/// checks ran beforehand.
+fn occupy_coordinates(
+    storage: &mut dyn Storage,
+    coords: &Coordinates,
+    token_id: &str,
+) -> Result<(), ContractError> {
+    if CONFIG.load(storage)?.unique_coordinates && coordinates_claimed(storage, coords)? {
+        return Err(ContractError::Claimed {});
+    }
+    COORDINATE_TOKENS.save(
+        storage,
+        &coordinate_key(coords, token_id),
+        &token_id.to_string(),
+    )?;
+    Ok(())
+}
+
+/// Moves the token's record in COORDINATE_TOKENS from one set of coordinates
+/// to another, failing with Claimed if the destination is taken.
+fn relocate_coordinates(
+    storage: &mut dyn Storage,
+    from: &Coordinates,
+    to: &Coordinates,
+    token_id: &str,
+) -> Result<(), ContractError> {
+    COORDINATE_TOKENS.remove(storage, &coordinate_key(from, token_id));
+    occupy_coordinates(storage, to, token_id)
+}
+
+/// Starts any of the token's queued moves that became due by the given time,
+/// without writing to storage. Each queued move starts when the token
+/// arrives at the previous destination, consuming its energy at that time.
+/// Returns the queued moves that were started.
+pub fn apply_queued_moves(
+    config: &Config,
+    token: &mut XyzTokenInfo,
+    queue: &mut Vec<QueuedMove>,
+    time: Timestamp,
+) -> Vec<QueuedMove> {
+    let mut started = vec![];
+    while !queue.is_empty() && token.extension.has_arrived(time) {
+        let queued_move = queue.remove(0);
+        let departure = token.extension.coordinates;
+        let departure_time = token.extension.arrival;
+
+        let available_energy = token.extension.current_energy(config, departure_time);
+        let required_energy = config.get_move_energy(departure, queued_move.coordinates);
+        token.extension.energy = (available_energy as u64).saturating_sub(required_energy) as u32;
+        token.extension.energy_updated_at = departure_time;
+
+        token.extension.record_move(config, queued_move.coordinates);
+        token.image = config.token_image(&queued_move.coordinates);
+        token.extension.coordinates = queued_move.coordinates;
+        token.extension.prev_coordinates = Some(departure);
+        token.extension.arrival = queued_move.arrival;
+        started.push(queued_move);
+    }
+    started
+}
+
+/// Loads a token after starting any of its queued moves that became due by
+/// the given time.
+fn advance_move_queue(
+    storage: &mut dyn Storage,
+    config: &Config,
+    token_id: &str,
+    time: Timestamp,
+) -> Result<XyzTokenInfo, ContractError> {
+    let token = tokens().load(storage, token_id)?;
+    let mut queue = match MOVE_QUEUES.may_load(storage, token_id)? {
+        Some(queue) => queue,
+        None => return Ok(token),
+    };
+
+    let mut new_token = token.clone();
+    let started = apply_queued_moves(config, &mut new_token, &mut queue, time);
+    if let Some(current_move) = started.last() {
+        for queued_move in started.iter() {
+            QUEUED_COORDINATES.remove(storage, &coordinate_key(&queued_move.coordinates, token_id));
+        }
+        relocate_coordinates(
+            storage,
+            &token.extension.coordinates,
+            &new_token.extension.coordinates,
+            token_id,
+        )?;
+        tokens().replace(storage, token_id, Some(&new_token), Some(&token))?;
+        save_move_fee_paid(
+            storage,
+            token_id,
+            &current_move.fee,
+            current_move.payer.as_ref(),
+        )?;
+        if queue.is_empty() {
+            MOVE_QUEUES.remove(storage, token_id);
+        } else {
+            MOVE_QUEUES.save(storage, token_id, &queue)?;
+        }
+    }
+    Ok(new_token)
+}
+
+fn save_move_fee_paid(
+    storage: &mut dyn Storage,
+    token_id: &str,
+    fee: &Coin,
+    payer: Option<&Addr>,
+) -> StdResult<()> {
+    if fee.amount.is_zero() {
+        remove_move_fee_paid(storage, token_id);
+        return Ok(());
+    }
+    MOVE_FEES_PAID.save(storage, token_id, fee)?;
+    match payer {
+        Some(payer) => MOVE_FEE_PAYERS.save(storage, token_id, payer),
+        // a fee queued before payers were recorded goes back to the owner
+        None => {
+            MOVE_FEE_PAYERS.remove(storage, token_id);
+            Ok(())
+        }
+    }
+}
+
+fn remove_move_fee_paid(storage: &mut dyn Storage, token_id: &str) {
+    MOVE_FEES_PAID.remove(storage, token_id);
+    MOVE_FEE_PAYERS.remove(storage, token_id);
+}
+
+/// The funds paying for a move
+enum MovePayment {
+    /// Native funds sent with the message
+    Native(Vec<Coin>),
+    /// CW20 tokens sent to the contract through Receive
+    Cw20 { token: Addr, amount: Uint128 },
+}
+
+/// Checks that a non-owner's payment covers the fee for a move from start to
+/// end, returning a response refunding any overpayment, the fee charged, and the
+/// fee to refund if the move is cancelled. A CW20 fee is returned as a coin
+/// denominated in the token's address. CW20 move fees aren't refunded on
+/// cancellation, so they're recorded as a zero fee.
+fn charge_move_fee(
+    storage: &mut dyn Storage,
+    config: &Config,
+    sender: &Addr,
+    payment: &MovePayment,
+    start: Coordinates,
+    end: Coordinates,
+    held_nanos: u64,
+) -> Result<(Response, Coin, Coin), ContractError> {
+    match payment {
+        MovePayment::Native(funds) => {
+            let move_fee = config.get_discounted_move_fee(start, end, held_nanos)?;
+            check_sufficient_funds(funds.clone(), move_fee.clone())?;
+            let info = MessageInfo {
+                sender: sender.clone(),
+                funds: funds.clone(),
+            };
+            let response = refund_overpayment(Response::new(), config, &info, &move_fee);
+            Ok((
+                distribute_fee(storage, response, config, &move_fee)?,
+                move_fee.clone(),
+                move_fee,
+            ))
+        }
+        MovePayment::Cw20 { token, amount } => {
+            let move_fee = config
+                .cw20_move_fee
+                .as_ref()
+                .filter(|fee| fee.address == token.as_str())
+                .ok_or(ContractError::UnacceptedCw20Token {})?;
+            let fee = apply_discount(
+                move_fee.get_fee(config.distance(start, end))?,
+                config.move_fee_discount_bps(held_nanos),
+            );
+            let refund = charge_cw20_fee(config, sender, token, *amount, fee)?;
+            let response = Response::new().add_messages(refund);
+            Ok((
+                distribute_cw20_fee(storage, response, config, token, fee)?,
+                Coin::new(fee.u128(), token.as_str()),
+                Coin::new(0, &config.base_move_fee.denom),
+            ))
+        }
+    }
+}
+
+/// Returns the response attributes describing a move, keyed as documented
+/// in move_attributes.
+#[allow(clippy::too_many_arguments)]
+fn move_response_attributes(
+    config: &Config,
+    action: &str,
+    sender: &Addr,
+    token_id: String,
+    departure: Coordinates,
+    destination: Coordinates,
+    fee: &Coin,
+    arrival: Timestamp,
+) -> StdResult<Vec<Attribute>> {
+    Ok(vec![
+        attr(move_attributes::ACTION, action),
+        attr(move_attributes::MOVER, sender),
+        attr(move_attributes::TOKEN_ID, numeric_token_id(token_id)?),
+        attr(move_attributes::FROM_X, departure.x.to_string()),
+        attr(move_attributes::FROM_Y, departure.y.to_string()),
+        attr(move_attributes::FROM_Z, departure.z.to_string()),
+        attr(move_attributes::TO_X, destination.x.to_string()),
+        attr(move_attributes::TO_Y, destination.y.to_string()),
+        attr(move_attributes::TO_Z, destination.z.to_string()),
+        attr(
+            move_attributes::DISTANCE,
+            config.distance(departure, destination).to_string(),
+        ),
+        attr(move_attributes::FEE_AMOUNT, fee.amount),
+        attr(move_attributes::FEE_DENOM, &fee.denom),
+        attr(move_attributes::ARRIVAL, arrival.nanos().to_string()),
+    ])
+}
+
+pub fn execute_move(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    coordinates: Coordinates,
+) -> Result<Response, ContractError> {
+    let payment = MovePayment::Native(info.funds);
+    move_token(deps, env, info.sender, payment, token_id, coordinates)
+}
+
+fn move_token(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    payment: MovePayment,
+    token_id: String,
+    coordinates: Coordinates,
+) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage, PausableAction::Move)?;
+    let owner = OWNER.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+    let token = advance_move_queue(deps.storage, &config, &token_id, env.block.time)?;
+
+    // check that the sender owns, is approved to send or pilots the token
+    check_can_move(deps.as_ref(), &env, &sender, &token_id, &token)?;
+
+    // queue the move if one is currently in progress; it waits out the
+    // cooldown once the token arrives
+    if !token.extension.has_arrived(env.block.time) {
+        return queue_move(
+            deps,
+            env,
+            sender,
+            payment,
+            &config,
+            owner,
+            token_id,
+            token,
+            coordinates,
+        );
+    }
+
+    // check that the token isn't cooling down from its last move
+    check_no_move_cooldown(deps.storage, &config, &token_id, &token, env.block.time)?;
+
+    // check that a non-owner paid at least the move fee
+    let mut response = Response::default();
+    let mut charged_fee = Coin::new(0, &config.base_move_fee.denom);
+    let mut move_fee = charged_fee.clone();
+    if owner != sender {
+        let held_nanos = load_held_nanos(deps.storage, &token_id, env.block.time)?;
+        let (refund, charged, fee) = charge_move_fee(
+            deps.storage,
+            &config,
+            &sender,
+            &payment,
+            token.extension.coordinates,
+            coordinates,
+            held_nanos,
+        )?;
+        response = refund;
+        charged_fee = charged;
+        move_fee = fee;
+    } else {
+        response = refund_owner_move_payment(response, &config, &sender, &payment)?;
+    }
+
+    let (new_token, hooks) = start_move(
+        deps.storage,
+        &config,
+        &env,
+        &token_id,
+        &token,
+        coordinates,
+        &sender,
+        &move_fee,
+        &charged_fee,
+    )?;
+    let attributes = move_response_attributes(
+        &config,
+        "move",
+        &sender,
+        token_id,
+        token.extension.coordinates,
+        coordinates,
+        &charged_fee,
+        new_token.extension.arrival,
+    )?;
+    Ok(response.add_submessages(hooks).add_attributes(attributes))
+}
+
+/// Checks that the token isn't cooling down from its last move.
+fn check_no_move_cooldown(
+    storage: &dyn Storage,
+    config: &Config,
+    token_id: &str,
+    token: &XyzTokenInfo,
+    time: Timestamp,
+) -> Result<(), ContractError> {
+    match load_cooldown_until(storage, config, token_id, token, time)? {
+        Some(until) => Err(ContractError::MoveCooldown {
+            remaining_nanos: until.nanos() - time.nanos(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Starts moving an arrived token to coordinates for mover, recording
+/// move_fee as the fee to refund mover if the move is cancelled and fee_paid
+/// in the token's move history. Checks that the coordinates are free, in
+/// bounds, unreserved unless mover is the owner, and in reach of the token's
+/// distance limit and energy, and returns the moved token and the hook
+/// submessages reporting the move.
+#[allow(clippy::too_many_arguments)]
+fn start_move(
+    storage: &mut dyn Storage,
+    config: &Config,
+    env: &Env,
+    token_id: &str,
+    token: &XyzTokenInfo,
+    coordinates: Coordinates,
+    mover: &Addr,
+    move_fee: &Coin,
+    fee_paid: &Coin,
+) -> Result<(XyzTokenInfo, Vec<SubMsg>), ContractError> {
+    // check that move target is unoccupied, unreserved and in bounds
+    settle_coordinates(storage, config, &coordinates, env.block.time)?;
+    let public = *mover != OWNER.load(storage)?;
+    check_coordinates(storage, &coordinates, public, env.block.time)?;
+    check_move_distance(config, token.extension.coordinates, coordinates)?;
+
+    // check that the token has regenerated enough energy for the move
+    let available_energy = token.extension.current_energy(config, env.block.time);
+    let required_energy = config.get_move_energy(token.extension.coordinates, coordinates);
+    if required_energy > available_energy as u64 {
+        return Err(ContractError::InsufficientEnergy {
+            required: required_energy,
+            available: available_energy,
+        });
+    }
+
+    // update token with new coordinates, prev coordinates, and arrival time
+    let mut new_token = token.clone();
+    new_token.extension.record_move(config, coordinates);
+    new_token.image = config.token_image(&coordinates);
+    new_token.extension.coordinates = coordinates;
+    new_token.extension.prev_coordinates = Some(token.extension.coordinates);
+    new_token.extension.arrival =
+        config.get_move_arrival(token.extension.coordinates, coordinates, env.block.time)?;
+    new_token.extension.energy = available_energy - required_energy as u32;
+    new_token.extension.energy_updated_at = env.block.time;
+    relocate_coordinates(
+        storage,
+        &token.extension.coordinates,
+        &coordinates,
+        token_id,
+    )?;
+    tokens().replace(storage, token_id, Some(&new_token), Some(token))?;
+    save_move_fee_paid(storage, token_id, move_fee, Some(mover))?;
+    MOVE_STARTED_AT.save(storage, token_id, &env.block.time)?;
+    record_move(
+        storage,
+        config.distance(token.extension.coordinates, coordinates),
+    )?;
+    append_move_history(
+        storage,
+        token_id,
+        &MoveRecord::Move {
+            origin: token.extension.coordinates,
+            destination: coordinates,
+            departure: env.block.time,
+            arrival: new_token.extension.arrival,
+            fee: fee_paid.clone(),
+        },
+    )?;
+
+    let hooks = hook_messages(
+        storage,
+        XyzHookMsg {
+            event: XyzHookEvent::Move,
+            token_id: numeric_token_id(token_id.to_string())?,
+            owner: new_token.owner.to_string(),
+            prev_coordinates: Some(token.extension.coordinates),
+            coordinates: Some(coordinates),
+            arrival: new_token.extension.arrival,
+        },
+    )?;
+    Ok((new_token, hooks))
+}
+
+/// Moves several arrived tokens in one transaction, charging the sum of
+/// their move fees against the attached funds once. If any move fails, none
+/// of them are made.
+pub fn execute_move_many(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    moves: Vec<(String, Coordinates)>,
+) -> Result<Response, ContractError> {
+    move_many(deps, env, info, moves, "move_many")
+}
+
+/// Moves several arrived tokens by the same offset, as with MoveMany. The
+/// moves are made in descending order of each token's projection onto the
+/// offset, so a token whose destination is held by another token in the
+/// formation moves after that token has left.
+pub fn execute_move_formation(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_ids: Vec<String>,
+    offset: Coordinates,
+) -> Result<Response, ContractError> {
+    if offset == (Coordinates { x: 0, y: 0, z: 0 }) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "formation offset must be nonzero",
+        )));
+    }
+    let config = CONFIG.load(deps.storage)?;
+
+    let mut moves: Vec<(i128, String, Coordinates)> = vec![];
+    for token_id in token_ids {
+        if moves.iter().any(|(_, id, _)| *id == token_id) {
+            return Err(ContractError::Std(StdError::generic_err(format!(
+                "token {} is listed more than once",
+                numeric_token_id(token_id)?
+            ))));
+        }
+        let token = advance_move_queue(deps.storage, &config, &token_id, env.block.time)?;
+        let origin = token.extension.coordinates;
+        let target = origin
+            .checked_translate(offset)
+            .ok_or(ContractError::InvalidMoveTarget {})?;
+        let projection = origin.x as i128 * offset.x as i128
+            + origin.y as i128 * offset.y as i128
+            + origin.z as i128 * offset.z as i128;
+        moves.push((projection, token_id, target));
+    }
+    moves.sort_by_key(|(projection, _, _)| std::cmp::Reverse(*projection));
+
+    let moves = moves
+        .into_iter()
+        .map(|(_, token_id, target)| (token_id, target))
+        .collect();
+    move_many(deps, env, info, moves, "move_formation")
+}
+
+fn move_many(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    moves: Vec<(String, Coordinates)>,
+    action: &str,
+) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage, PausableAction::Move)?;
+    let owner = OWNER.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+
+    if moves.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "move many requires at least one move",
+        )));
+    }
+
+    let mut total_fee = Coin::new(0, &config.base_move_fee.denom);
+    let mut hooks = vec![];
+    let mut leg_attributes = vec![];
+    for (token_id, coordinates) in moves {
+        let token = advance_move_queue(deps.storage, &config, &token_id, env.block.time)?;
+
+        // check that the sender owns, is approved to send or pilots the
+        // token, and that it isn't moving or cooling down from its last move
+        check_can_move(deps.as_ref(), &env, &info.sender, &token_id, &token)?;
+        check_no_move_cooldown(deps.storage, &config, &token_id, &token, env.block.time)?;
+        if !token.extension.has_arrived(env.block.time) {
+            return Err(ContractError::MoveInProgress {});
+        }
+
+        let move_fee = if owner != info.sender {
+            let held_nanos = load_held_nanos(deps.storage, &token_id, env.block.time)?;
+            let fee = config.get_discounted_move_fee(
+                token.extension.coordinates,
+                coordinates,
+                held_nanos,
+            )?;
+            total_fee.amount = total_fee
+                .amount
+                .checked_add(fee.amount)
+                .map_err(StdError::from)?;
+            fee
+        } else {
+            Coin::new(0, &config.base_move_fee.denom)
+        };
+
+        let (new_token, leg_hooks) = start_move(
+            deps.storage,
+            &config,
+            &env,
+            &token_id,
+            &token,
+            coordinates,
+            &info.sender,
+            &move_fee,
+            &move_fee,
+        )?;
+        hooks.extend(leg_hooks);
+        leg_attributes.push(attr(move_attributes::TOKEN_ID, numeric_token_id(token_id)?));
+        leg_attributes.push(attr(
+            move_attributes::ARRIVAL,
+            new_token.extension.arrival.nanos().to_string(),
+        ));
+    }
+
+    // check that a non-owner paid at least the total move fee
+    check_sufficient_funds(info.funds.clone(), total_fee.clone())?;
+    let response = refund_overpayment(Response::new(), &config, &info, &total_fee);
+    let response = distribute_fee(deps.storage, response, &config, &total_fee)?;
+
+    Ok(response
+        .add_submessages(hooks)
+        .add_attribute(move_attributes::ACTION, action)
+        .add_attribute(move_attributes::MOVER, &info.sender)
+        .add_attribute(move_attributes::FEE_AMOUNT, total_fee.amount)
+        .add_attribute(move_attributes::FEE_DENOM, &total_fee.denom)
+        .add_attributes(leg_attributes))
+}
+
+/// Returns the move fee the sender owes for moving the token from start to
+/// end, which is nothing for the contract owner.
+pub fn sender_move_fee(
+    storage: &dyn Storage,
+    env: &Env,
+    config: &Config,
+    sender: &Addr,
+    token_id: &str,
+    start: Coordinates,
+    end: Coordinates,
+) -> StdResult<Coin> {
+    if OWNER.load(storage)? == *sender {
+        return Ok(Coin::new(0, &config.base_move_fee.denom));
+    }
+    let held_nanos = load_held_nanos(storage, token_id, env.block.time)?;
+    config.get_discounted_move_fee(start, end, held_nanos)
+}
+
+/// Checks that two tokens can be proposed for a swap: they're different
+/// tokens, both have arrived, and they're within Config::max_move_distance
+/// of each other. Both tokens should have had their due queued moves
+/// started.
+pub fn check_swap_tokens(
+    config: &Config,
+    time: Timestamp,
+    (my_token_id, mine): (&str, &XyzTokenInfo),
+    (their_token_id, theirs): (&str, &XyzTokenInfo),
+) -> Result<(), ContractError> {
+    if my_token_id == their_token_id {
+        return Err(ContractError::Std(StdError::generic_err(
+            "can't swap a token with itself",
+        )));
+    }
+    if !mine.extension.has_arrived(time) || !theirs.extension.has_arrived(time) {
+        return Err(ContractError::MoveInProgress {});
+    }
+    check_move_distance(
+        config,
+        mine.extension.coordinates,
+        theirs.extension.coordinates,
+    )
+}
+
+/// Checks that two tokens proposed for a swap can start moving toward each
+/// other's coordinates: neither is cooling down, each has the energy for its
+/// leg, and neither is headed for coordinates reserved from its owner.
+pub fn check_swap_legs(
+    storage: &dyn Storage,
+    config: &Config,
+    time: Timestamp,
+    legs: [(&str, &XyzTokenInfo); 2],
+) -> Result<(), ContractError> {
+    let owner = OWNER.load(storage)?;
+    for (i, (token_id, token)) in legs.iter().enumerate() {
+        let target = legs[1 - i].1.extension.coordinates;
+        check_no_move_cooldown(storage, config, token_id, token, time)?;
+        let available_energy = token.extension.current_energy(config, time);
+        let required_energy = config.get_move_energy(token.extension.coordinates, target);
+        if required_energy > available_energy as u64 {
+            return Err(ContractError::InsufficientEnergy {
+                required: required_energy,
+                available: available_energy,
+            });
+        }
+        if token.owner != owner && is_reserved(storage, &target, time)? {
+            return Err(ContractError::Reserved {});
+        }
+    }
+    Ok(())
+}
+
+pub fn execute_propose_swap(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    my_token_id: String,
+    their_token_id: String,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage, PausableAction::Move)?;
+    let config = CONFIG.load(deps.storage)?;
+    let expires = expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    let mine = advance_move_queue(deps.storage, &config, &my_token_id, env.block.time)?;
+    let theirs = advance_move_queue(deps.storage, &config, &their_token_id, env.block.time)?;
+    if mine.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_swap_tokens(
+        &config,
+        env.block.time,
+        (&my_token_id, &mine),
+        (&their_token_id, &theirs),
+    )?;
+    let (origin, target) = (mine.extension.coordinates, theirs.extension.coordinates);
+
+    // escrow the proposer's move fee until the swap is accepted or cancelled
+    let fee = sender_move_fee(
+        deps.storage,
+        &env,
+        &config,
+        &info.sender,
+        &my_token_id,
+        origin,
+        target,
+    )?;
+    check_sufficient_funds(info.funds.clone(), fee.clone())?;
+    add_escrow(deps.storage, &fee)?;
+
+    let id = NEXT_SWAP_PROPOSAL_ID
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        + 1;
+    NEXT_SWAP_PROPOSAL_ID.save(deps.storage, &id)?;
+    let leg = |token_id: String, token: &XyzTokenInfo| SwapLeg {
+        token_id,
+        owner: token.owner.clone(),
+        coordinates: token.extension.coordinates,
+        arrival: token.extension.arrival,
+    };
+    SWAP_PROPOSALS.save(
+        deps.storage,
+        U64Key::new(id),
+        &SwapProposal {
+            id,
+            proposer: leg(my_token_id.clone(), &mine),
+            counterparty: leg(their_token_id.clone(), &theirs),
+            fee: fee.clone(),
+            expires,
+        },
+    )?;
+
+    Ok(refund_overpayment(Response::new(), &config, &info, &fee)
+        .add_attribute("action", "propose_swap")
+        .add_attribute("proposal_id", id.to_string())
+        .add_attribute("proposer", info.sender)
+        .add_attribute("token_id", numeric_token_id(my_token_id)?)
+        .add_attribute("their_token_id", numeric_token_id(their_token_id)?)
+        .add_attribute("fee", fee.to_string()))
+}
+
+/// Returns whether the swap can still be accepted: it hasn't expired, and
+/// neither token has been burned, changed owner, or moved since it was
+/// proposed.
+fn swap_is_live(
+    storage: &mut dyn Storage,
+    env: &Env,
+    config: &Config,
+    proposal: &SwapProposal,
+) -> Result<bool, ContractError> {
+    if proposal.expires.is_expired(&env.block) {
+        return Ok(false);
+    }
+    let mut legs = vec![];
+    for token_id in [&proposal.proposer.token_id, &proposal.counterparty.token_id] {
+        if tokens().may_load(storage, token_id)?.is_none() {
+            return Ok(false);
+        }
+        legs.push(advance_move_queue(
+            storage,
+            config,
+            token_id,
+            env.block.time,
+        )?);
+    }
+    Ok(proposal.is_current(&legs[0], &legs[1]))
+}
+
+/// Removes the swap proposal, refunding its escrowed fee to the proposer.
+fn close_swap(storage: &mut dyn Storage, proposal: &SwapProposal) -> StdResult<Response> {
+    SWAP_PROPOSALS.remove(storage, U64Key::new(proposal.id));
+    release_escrow(storage, &proposal.fee)?;
+    let mut response = Response::new();
+    if !proposal.fee.amount.is_zero() {
+        response = response
+            .add_message(BankMsg::Send {
+                to_address: proposal.proposer.owner.to_string(),
+                amount: vec![proposal.fee.clone()],
+            })
+            .add_attribute("refund", proposal.fee.to_string());
+    }
+    Ok(response.add_attribute("proposal_id", proposal.id.to_string()))
+}
+
+pub fn execute_accept_swap(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let proposal = SWAP_PROPOSALS
+        .may_load(deps.storage, U64Key::new(proposal_id))?
+        .ok_or(ContractError::NoSwapProposal {})?;
+    if proposal.counterparty.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // a proposal that can't be accepted is refunded rather than failing,
+    // which would leave the escrow in place
+    if !swap_is_live(deps.storage, &env, &config, &proposal)? {
+        return Ok(close_swap(deps.storage, &proposal)?.add_attribute("action", "refund_swap"));
+    }
+    check_not_paused(deps.storage, PausableAction::Move)?;
+    let (proposer, counterparty) = (&proposal.proposer, &proposal.counterparty);
+    let mine = tokens().load(deps.storage, &proposer.token_id)?;
+    let theirs = tokens().load(deps.storage, &counterparty.token_id)?;
+    check_swap_legs(
+        deps.storage,
+        &config,
+        env.block.time,
+        [
+            (&proposer.token_id, &mine),
+            (&counterparty.token_id, &theirs),
+        ],
+    )?;
+
+    // the accepter pays for their leg now; the proposer's fee is released
+    // from escrow to the contract
+    let fee = sender_move_fee(
+        deps.storage,
+        &env,
+        &config,
+        &info.sender,
+        &counterparty.token_id,
+        counterparty.coordinates,
+        proposer.coordinates,
+    )?;
+    check_sufficient_funds(info.funds.clone(), fee.clone())?;
+    SWAP_PROPOSALS.remove(deps.storage, U64Key::new(proposal_id));
+    release_escrow(deps.storage, &proposal.fee)?;
+
+    // vacate the counterparty's coordinates so the proposer's leg finds
+    // them free; the proposer's leg vacates the counterparty's destination
+    COORDINATE_TOKENS.remove(
+        deps.storage,
+        &coordinate_key(&counterparty.coordinates, &counterparty.token_id),
+    );
+    let (new_mine, mut hooks) = start_move(
+        deps.storage,
+        &config,
+        &env,
+        &proposer.token_id,
+        &mine,
+        counterparty.coordinates,
+        &proposer.owner,
+        &proposal.fee,
+        &proposal.fee,
+    )?;
+    let (new_theirs, their_hooks) = start_move(
+        deps.storage,
+        &config,
+        &env,
+        &counterparty.token_id,
+        &theirs,
+        proposer.coordinates,
+        &info.sender,
+        &fee,
+        &fee,
+    )?;
+    hooks.extend(their_hooks);
+
+    // both fees are collected once the swap goes ahead
+    let response = refund_overpayment(Response::new(), &config, &info, &fee);
+    let response = distribute_fee(deps.storage, response, &config, &proposal.fee)?;
+    Ok(distribute_fee(deps.storage, response, &config, &fee)?
+        .add_submessages(hooks)
+        .add_attribute("action", "accept_swap")
+        .add_attribute("proposal_id", proposal_id.to_string())
+        .add_attribute("fee", fee.to_string())
+        .add_attribute("token_id", numeric_token_id(proposer.token_id.clone())?)
+        .add_attribute("arrival", new_mine.extension.arrival.nanos().to_string())
+        .add_attribute("token_id", numeric_token_id(counterparty.token_id.clone())?)
+        .add_attribute("arrival", new_theirs.extension.arrival.nanos().to_string()))
+}
+
+pub fn execute_cancel_swap(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let proposal = SWAP_PROPOSALS
+        .may_load(deps.storage, U64Key::new(proposal_id))?
+        .ok_or(ContractError::NoSwapProposal {})?;
+    if proposal.proposer.owner != info.sender
+        && swap_is_live(deps.storage, &env, &config, &proposal)?
+    {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    Ok(close_swap(deps.storage, &proposal)?.add_attribute("action", "cancel_swap"))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_move(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    payment: MovePayment,
+    config: &Config,
+    owner: String,
+    token_id: String,
+    token: XyzTokenInfo,
+    coordinates: Coordinates,
+) -> Result<Response, ContractError> {
+    let mut queue = MOVE_QUEUES
+        .may_load(deps.storage, &token_id)?
+        .unwrap_or_default();
+    if queue.len() >= config.max_queued_moves as usize {
+        return Err(ContractError::MoveInProgress {});
+    }
+
+    // the move departs from the previous move's destination once it arrives
+    let (departure, departure_time) = match queue.last() {
+        Some(queued_move) => (queued_move.coordinates, queued_move.arrival),
+        None => (token.extension.coordinates, token.extension.arrival),
+    };
+
+    // check that a non-owner paid at least the move fee
+    let mut response = Response::default();
+    let mut charged_fee = Coin::new(0, &config.base_move_fee.denom);
+    let mut move_fee = charged_fee.clone();
+    if owner != sender {
+        let held_nanos = load_held_nanos(deps.storage, &token_id, env.block.time)?;
+        let (refund, charged, fee) = charge_move_fee(
+            deps.storage,
+            config,
+            &sender,
+            &payment,
+            departure,
+            coordinates,
+            held_nanos,
+        )?;
+        response = refund;
+        charged_fee = charged;
+        move_fee = fee;
+    } else {
+        response = refund_owner_move_payment(response, config, &sender, &payment)?;
+    }
+
+    // check that move target is unoccupied, unreserved and in bounds
+    settle_coordinates(deps.storage, config, &coordinates, env.block.time)?;
+    check_coordinates(deps.storage, &coordinates, owner != sender, env.block.time)?;
+    check_move_distance(config, departure, coordinates)?;
+
+    // check that the token's current energy covers every queued move
+    let mut required_energy = config.get_move_energy(departure, coordinates);
+    let mut leg_departure = token.extension.coordinates;
+    for queued_move in queue.iter() {
+        required_energy += config.get_move_energy(leg_departure, queued_move.coordinates);
+        leg_departure = queued_move.coordinates;
+    }
+    let available_energy = token.extension.current_energy(config, env.block.time);
+    if required_energy > available_energy as u64 {
+        return Err(ContractError::InsufficientEnergy {
+            required: required_energy,
+            available: available_energy,
+        });
+    }
+
+    // the move starts when the previous one arrives, but waits out the
+    // cooldown at its departure point along with base_move_nanos
+    let arrival = config.get_move_arrival(
+        departure,
+        coordinates,
+        departure_time.plus_nanos(config.move_cooldown_nanos),
+    )?;
+    queue.push(QueuedMove {
+        coordinates,
+        arrival,
+        fee: move_fee,
+        payer: Some(sender.clone()),
+    });
+    MOVE_QUEUES.save(deps.storage, &token_id, &queue)?;
+    QUEUED_COORDINATES.save(
+        deps.storage,
+        &coordinate_key(&coordinates, &token_id),
+        &token_id,
+    )?;
+    MOVE_STARTED_AT.save(deps.storage, &token_id, &env.block.time)?;
+    record_move(deps.storage, config.distance(departure, coordinates))?;
+    append_move_history(
+        deps.storage,
+        &token_id,
+        &MoveRecord::Move {
+            origin: departure,
+            destination: coordinates,
+            departure: departure_time,
+            arrival,
+            fee: charged_fee.clone(),
+        },
+    )?;
+
+    let hooks = hook_messages(
+        deps.storage,
+        XyzHookMsg {
+            event: XyzHookEvent::Move,
+            token_id: numeric_token_id(token_id.clone())?,
+            owner: token.owner.to_string(),
+            prev_coordinates: Some(departure),
+            coordinates: Some(coordinates),
+            arrival,
+        },
+    )?;
+    let attributes = move_response_attributes(
+        config,
+        "queue_move",
+        &sender,
+        token_id,
+        departure,
+        coordinates,
+        &charged_fee,
+        arrival,
+    )?;
+    Ok(response.add_submessages(hooks).add_attributes(attributes))
+}
+
+pub fn execute_reroll_coordinates(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    coordinates: Coordinates,
+) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage, PausableAction::Move)?;
+    let config = CONFIG.load(deps.storage)?;
+    if !config.reroll_enabled {
+        return Err(ContractError::RerollDisabled {});
+    }
+    let token = tokens().load(deps.storage, &token_id)?;
+
+    // check that the sender owns the token
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // check that the token hasn't been re-rolled, moved, or transferred, and
+    // is still within the window. A transfer restarts the holding period,
+    // which otherwise starts at the mint.
+    let moved =
+        token.extension.prev_coordinates.is_some() || MOVE_STARTED_AT.has(deps.storage, &token_id);
+    let transferred =
+        HELD_SINCE.may_load(deps.storage, &token_id)? != Some(token.extension.minted_at);
+    let window_end = token
+        .extension
+        .minted_at
+        .plus_nanos(config.reroll_window_nanos);
+    if token.extension.rerolled || moved || transferred || env.block.time > window_end {
+        return Err(ContractError::CannotReroll {});
+    }
+
+    // check that the new coordinates are unoccupied, unreserved and in bounds
+    settle_coordinates(deps.storage, &config, &coordinates, env.block.time)?;
+    check_coordinates(deps.storage, &coordinates, true, env.block.time)?;
+
+    let mut new_token = token.clone();
+    new_token.image = config.token_image(&coordinates);
+    new_token.extension.coordinates = coordinates;
+    new_token.extension.rerolled = true;
+    relocate_coordinates(
+        deps.storage,
+        &token.extension.coordinates,
+        &coordinates,
+        &token_id,
+    )?;
+    tokens().replace(deps.storage, &token_id, Some(&new_token), Some(&token))?;
+
+    let hooks = hook_messages(
+        deps.storage,
+        XyzHookMsg {
+            event: XyzHookEvent::Move,
+            token_id: numeric_token_id(token_id.clone())?,
+            owner: new_token.owner.to_string(),
+            prev_coordinates: Some(token.extension.coordinates),
+            coordinates: Some(coordinates),
+            arrival: new_token.extension.arrival,
+        },
+    )?;
+    let attributes = move_response_attributes(
+        &config,
+        "reroll_coordinates",
+        &info.sender,
+        token_id,
+        token.extension.coordinates,
+        coordinates,
+        &Coin::new(0, &config.base_move_fee.denom),
+        new_token.extension.arrival,
+    )?;
+    Ok(Response::new()
+        .add_submessages(hooks)
+        .add_attributes(attributes))
+}
+
+pub fn execute_teleport(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    coordinates: Coordinates,
+) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage, PausableAction::Move)?;
+    let owner = OWNER.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+    if !config.teleport_enabled {
+        return Err(ContractError::TeleportDisabled {});
+    }
+    let token = advance_move_queue(deps.storage, &config, &token_id, env.block.time)?;
+
+    // check that the sender owns the token
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // a teleport can't interrupt or be queued behind a regular move
+    if !token.extension.has_arrived(env.block.time) {
+        return Err(ContractError::MoveInProgress {});
+    }
+
+    // check that the token isn't cooling down from its last move
+    check_no_move_cooldown(deps.storage, &config, &token_id, &token, env.block.time)?;
+
+    // check that the teleport is within the distance limit
+    check_move_distance(&config, token.extension.coordinates, coordinates)?;
+
+    // check that a non-owner paid at least the teleport fee
+    let mut response = Response::default();
+    let mut teleport_fee = Coin::new(0, &config.base_move_fee.denom);
+    if owner != info.sender {
+        teleport_fee = config.get_teleport_fee(token.extension.coordinates, coordinates)?;
+        check_sufficient_funds(info.funds.clone(), teleport_fee.clone())?;
+        response = refund_overpayment(response, &config, &info, &teleport_fee);
+        response = distribute_fee(deps.storage, response, &config, &teleport_fee)?;
+    }
+
+    // check that teleport target is unoccupied, unreserved and in bounds
+    settle_coordinates(deps.storage, &config, &coordinates, env.block.time)?;
+    check_coordinates(
+        deps.storage,
+        &coordinates,
+        owner != info.sender,
+        env.block.time,
+    )?;
+
+    // check that the token has regenerated enough energy for the teleport
+    let available_energy = token.extension.current_energy(&config, env.block.time);
+    let required_energy = config.get_move_energy(token.extension.coordinates, coordinates);
+    if required_energy > available_energy as u64 {
+        return Err(ContractError::InsufficientEnergy {
+            required: required_energy,
+            available: available_energy,
+        });
+    }
+
+    // update token with new coordinates and prev coordinates, arriving now
+    let mut new_token = token.clone();
+    new_token.extension.record_move(&config, coordinates);
+    new_token.image = config.token_image(&coordinates);
+    new_token.extension.coordinates = coordinates;
+    new_token.extension.prev_coordinates = Some(token.extension.coordinates);
+    new_token.extension.arrival = env.block.time;
+    new_token.extension.energy = available_energy - required_energy as u32;
+    new_token.extension.energy_updated_at = env.block.time;
+    relocate_coordinates(
+        deps.storage,
+        &token.extension.coordinates,
+        &coordinates,
+        &token_id,
+    )?;
+    tokens().replace(deps.storage, &token_id, Some(&new_token), Some(&token))?;
+    // an arrived token has no move to cancel, so there's no fee to refund
+    remove_move_fee_paid(deps.storage, &token_id);
+    MOVE_STARTED_AT.save(deps.storage, &token_id, &env.block.time)?;
+    record_move(
+        deps.storage,
+        config.distance(token.extension.coordinates, coordinates),
+    )?;
+    append_move_history(
+        deps.storage,
+        &token_id,
+        &MoveRecord::Move {
+            origin: token.extension.coordinates,
+            destination: coordinates,
+            departure: env.block.time,
+            arrival: env.block.time,
+            fee: teleport_fee.clone(),
+        },
+    )?;
+
+    let hooks = hook_messages(
+        deps.storage,
+        XyzHookMsg {
+            event: XyzHookEvent::Move,
+            token_id: numeric_token_id(token_id.clone())?,
+            owner: new_token.owner.to_string(),
+            prev_coordinates: Some(token.extension.coordinates),
+            coordinates: Some(coordinates),
+            arrival: new_token.extension.arrival,
+        },
+    )?;
+    let attributes = move_response_attributes(
+        &config,
+        "teleport",
+        &info.sender,
+        token_id,
+        token.extension.coordinates,
+        coordinates,
+        &teleport_fee,
+        new_token.extension.arrival,
+    )?;
+    Ok(response.add_submessages(hooks).add_attributes(attributes))
+}
+
+pub fn execute_expedite_move(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage, PausableAction::Move)?;
+    let config = CONFIG.load(deps.storage)?;
+    let token = advance_move_queue(deps.storage, &config, &token_id, env.block.time)?;
+
+    // check that the sender owns the token
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // check that a move is currently in progress
+    if token.extension.has_arrived(env.block.time) {
+        return Err(ContractError::NoMoveInProgress {});
+    }
+
+    // the time cut is relative to the fee paid for the move
+    let move_fee = MOVE_FEES_PAID
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::CannotExpedite {})?;
+    let payment = info
+        .funds
+        .iter()
+        .filter(|coin| coin.denom == move_fee.denom)
+        .map(|coin| coin.amount)
+        .sum();
+    let remaining_nanos = token.extension.arrival.nanos() - env.block.time.nanos();
+    let (cut_nanos, charged) = config.get_expedite(remaining_nanos, move_fee.amount, payment)?;
+    if cut_nanos == 0 {
+        return Err(ContractError::CannotExpedite {});
+    }
+    let fee = Coin {
+        amount: charged,
+        denom: move_fee.denom,
+    };
+
+    // bring the arrival forward, along with any queued moves behind it
+    let mut new_token = token.clone();
+    new_token.extension.arrival = token.extension.arrival.minus_nanos(cut_nanos);
+    tokens().replace(deps.storage, &token_id, Some(&new_token), Some(&token))?;
+    if let Some(mut queue) = MOVE_QUEUES.may_load(deps.storage, &token_id)? {
+        for queued_move in queue.iter_mut() {
+            queued_move.arrival = queued_move.arrival.minus_nanos(cut_nanos);
+        }
+        MOVE_QUEUES.save(deps.storage, &token_id, &queue)?;
+    }
+    append_move_history(
+        deps.storage,
+        &token_id,
+        &MoveRecord::Expedite {
+            arrival: new_token.extension.arrival,
+            fee: fee.clone(),
+        },
+    )?;
+
+    let response = refund_overpayment(Response::new(), &config, &info, &fee);
+    Ok(distribute_fee(deps.storage, response, &config, &fee)?
+        .add_attribute("action", "expedite_move")
+        .add_attribute("mover", info.sender)
+        .add_attribute("token_id", numeric_token_id(token_id)?)
+        .add_attribute("fee_amount", fee.amount)
+        .add_attribute("fee_denom", fee.denom)
+        .add_attribute("arrival", new_token.extension.arrival.nanos().to_string()))
+}
+
+pub fn execute_cancel_move(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let token = advance_move_queue(deps.storage, &config, &token_id, env.block.time)?;
+
+    // check that the sender owns the token
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // check that a move is currently in progress
+    let prev_coordinates = match token.extension.prev_coordinates {
+        Some(prev_coordinates) if !token.extension.has_arrived(env.block.time) => prev_coordinates,
+        _ => return Err(ContractError::NoMoveInProgress {}),
+    };
+
+    // return the token to where it departed from, which fails if anything
+    // has claimed the departure coordinates in the meantime
+    let mut new_token = token.clone();
+    new_token.image = config.token_image(&prev_coordinates);
+    new_token.extension.coordinates = prev_coordinates;
+    new_token.extension.prev_coordinates = None;
+    new_token.extension.arrival = env.block.time;
+    relocate_coordinates(
+        deps.storage,
+        &token.extension.coordinates,
+        &prev_coordinates,
+        &token_id,
+    )?;
+    tokens().replace(deps.storage, &token_id, Some(&new_token), Some(&token))?;
+
+    // refund the configured fraction of the fee paid for the move, up to the
+    // share of it the contract kept after paying out fee_distribution, to
+    // whoever paid it
+    let mut refunds: Vec<Coin> = vec![];
+    let mut payer_refunds: Vec<(Addr, Vec<Coin>)> = vec![];
+    let mut refund_payer = |payer: Option<Addr>, coin: Coin| {
+        if coin.amount.is_zero() {
+            return;
+        }
+        let payer = payer.unwrap_or_else(|| token.owner.clone());
+        add_coin(&mut refunds, coin.clone());
+        match payer_refunds.iter_mut().find(|(addr, _)| *addr == payer) {
+            Some((_, coins)) => add_coin(coins, coin),
+            None => payer_refunds.push((payer, vec![coin])),
+        }
+    };
+    if let Some(fee_paid) = MOVE_FEES_PAID.may_load(deps.storage, &token_id)? {
+        let payer = MOVE_FEE_PAYERS.may_load(deps.storage, &token_id)?;
+        remove_move_fee_paid(deps.storage, &token_id);
+        let refund_bps = config.move_cancel_refund_bps.min(10_000);
+        let refund = fee_paid.amount.multiply_ratio(refund_bps, 10_000u128);
+        refund_payer(
+            payer,
+            Coin {
+                amount: refund.min(config.retained_fee(fee_paid.amount)),
+                denom: fee_paid.denom,
+            },
+        );
+    }
+
+    // clear the queue, refunding queued moves in full since they never
+    // started, less any shares already paid out
+    for queued_move in MOVE_QUEUES
+        .may_load(deps.storage, &token_id)?
+        .unwrap_or_default()
+    {
+        QUEUED_COORDINATES.remove(
+            deps.storage,
+            &coordinate_key(&queued_move.coordinates, &token_id),
+        );
+        refund_payer(
+            queued_move.payer,
+            Coin {
+                amount: config.retained_fee(queued_move.fee.amount),
+                denom: queued_move.fee.denom,
+            },
+        );
+    }
+    MOVE_QUEUES.remove(deps.storage, &token_id);
+    append_move_history(
+        deps.storage,
+        &token_id,
+        &MoveRecord::Cancel {
+            origin: prev_coordinates,
+            destination: token.extension.coordinates,
+            cancelled_at: env.block.time,
+            refund: refunds.clone(),
+        },
+    )?;
+
+    let mut response = Response::new().add_submessages(hook_messages(
+        deps.storage,
+        XyzHookMsg {
+            event: XyzHookEvent::CancelMove,
+            token_id: numeric_token_id(token_id.clone())?,
+            owner: new_token.owner.to_string(),
+            prev_coordinates: Some(token.extension.coordinates),
+            coordinates: Some(prev_coordinates),
+            arrival: new_token.extension.arrival,
+        },
+    )?);
+    let refund_attr = if refunds.is_empty() {
+        Coin::new(0, &config.base_move_fee.denom).to_string()
+    } else {
+        let refund_attr = refunds
+            .iter()
+            .map(|coin| coin.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        for (payer, amount) in payer_refunds {
+            response = response.add_message(BankMsg::Send {
+                to_address: payer.to_string(),
+                amount,
+            });
+        }
+        refund_attr
+    };
+
+    Ok(response
+        .add_attribute("action", "cancel_move")
+        .add_attribute("mover", info.sender)
+        .add_attribute("token_id", numeric_token_id(token_id)?)
+        .add_attribute("refund", refund_attr)
+        .add_attribute("x", prev_coordinates.x.to_string())
+        .add_attribute("y", prev_coordinates.y.to_string())
+        .add_attribute("z", prev_coordinates.z.to_string()))
+}
+
+pub fn execute_finalize_move(
+    deps: DepsMut,
+    env: Env,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let token = advance_move_queue(deps.storage, &config, &token_id, env.block.time)?;
+
+    // check that the token has arrived from a move not yet finalized
+    if !token.extension.has_arrived(env.block.time) {
+        return Err(ContractError::MoveNotArrived {
+            remaining_nanos: token.extension.arrival.nanos() - env.block.time.nanos(),
+        });
+    }
+    let prev_coordinates = token
+        .extension
+        .prev_coordinates
+        .ok_or(ContractError::MoveAlreadyFinalized {})?;
+
+    let mut new_token = token.clone();
+    new_token.extension.prev_coordinates = None;
+    tokens().replace(deps.storage, &token_id, Some(&new_token), Some(&token))?;
+    // the move can no longer be cancelled, so its fee won't be refunded
+    remove_move_fee_paid(deps.storage, &token_id);
+
+    let coordinates = new_token.extension.coordinates;
+    let arrival = new_token.extension.arrival;
+    let hooks = hook_messages(
+        deps.storage,
+        XyzHookMsg {
+            event: XyzHookEvent::MoveCompleted,
+            token_id: numeric_token_id(token_id.clone())?,
+            owner: new_token.owner.to_string(),
+            prev_coordinates: Some(prev_coordinates),
+            coordinates: Some(coordinates),
+            arrival,
+        },
+    )?;
+    let event = Event::new("move_completed")
+        .add_attribute("token_id", numeric_token_id(token_id)?)
+        .add_attribute("x", coordinates.x.to_string())
+        .add_attribute("y", coordinates.y.to_string())
+        .add_attribute("z", coordinates.z.to_string())
+        .add_attribute("arrival", arrival.nanos().to_string());
+    Ok(Response::new()
+        .add_submessages(hooks)
+        .add_event(event)
+        .add_attribute("action", "finalize_move"))
+}
+
+pub fn execute_set_token_profile(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    name: Option<String>,
+    description: Option<String>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if !config.token_profiles_enabled {
+        return Err(ContractError::TokenProfilesDisabled {});
+    }
+    let token = tokens().load(deps.storage, &token_id)?;
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut new_token = token.clone();
+    if let Some(name) = name {
+        new_token.extension.name = clean_profile_field(&name, "name", MAX_TOKEN_NAME_LENGTH)?;
+    }
+    if let Some(description) = description {
+        new_token.extension.description =
+            clean_profile_field(&description, "description", MAX_TOKEN_DESCRIPTION_LENGTH)?;
+    }
+    tokens().replace(deps.storage, &token_id, Some(&new_token), Some(&token))?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_token_profile")
+        .add_attribute("owner", info.sender)
+        .add_attribute("token_id", numeric_token_id(token_id)?))
+}
+
+pub fn execute_list_for_sale(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    price: Coin,
+) -> Result<Response, ContractError> {
+    let token = tokens().load(deps.storage, &token_id)?;
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if price.amount.is_zero() || price.denom.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "listing price must be a positive amount of a named denom",
+        )));
+    }
+
+    LISTINGS.save(
+        deps.storage,
+        &token_id,
+        &Listing {
+            token_id: token_id.clone(),
+            seller: info.sender.clone(),
+            price: price.clone(),
+        },
+    )?;
+    Ok(Response::new()
+        .add_attribute("action", "list_for_sale")
+        .add_attribute("seller", info.sender)
+        .add_attribute("token_id", numeric_token_id(token_id)?)
+        .add_attribute("price", price.to_string()))
+}
+
+pub fn execute_cancel_listing(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let token = tokens().load(deps.storage, &token_id)?;
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !LISTINGS.has(deps.storage, &token_id) {
+        return Err(ContractError::NotListed {});
+    }
+
+    LISTINGS.remove(deps.storage, &token_id);
+    Ok(Response::new()
+        .add_attribute("action", "cancel_listing")
+        .add_attribute("seller", info.sender)
+        .add_attribute("token_id", numeric_token_id(token_id)?))
+}
+
+pub fn execute_buy(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage, PausableAction::Transfer)?;
+    let config = CONFIG.load(deps.storage)?;
+    let listing = LISTINGS
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::NotListed {})?;
+    let token = advance_move_queue(deps.storage, &config, &token_id, env.block.time)?;
+
+    // check that the seller still owns the token and isn't buying it back
+    if token.owner != listing.seller {
+        return Err(ContractError::NotListed {});
+    }
+    if info.sender == listing.seller {
+        return Err(ContractError::Std(StdError::generic_err(
+            "can't buy your own token",
+        )));
+    }
+    if config.transfers_locked_during_move && !token.extension.has_arrived(env.block.time) {
+        return Err(ContractError::TransferLockedDuringMove {
+            arrival: token.extension.arrival,
+        });
+    }
+    check_sufficient_funds(info.funds.clone(), listing.price.clone())?;
+
+    let (response, royalty) = settle_sale(
+        deps.storage,
+        &env,
+        &config,
+        &token_id,
+        token,
+        &info.sender,
+        &listing.price,
+    )?;
+    Ok(refund_overpayment(response, &config, &info, &listing.price)
+        .add_attribute("action", "buy")
+        .add_attribute("buyer", info.sender)
+        .add_attribute("seller", listing.seller)
+        .add_attribute("token_id", numeric_token_id(token_id)?)
+        .add_attribute("price", listing.price.to_string())
+        .add_attribute("royalty", royalty))
+}
+
+/// Hands a sold token to the buyer, dropping its approvals, listing and pilot, and
+/// pays out the price: the royalty, if there's anyone to pay it to, and the
+/// rest to the seller. Returns the response with the hook and payment
+/// messages, along with the royalty paid.
+fn settle_sale(
+    storage: &mut dyn Storage,
+    env: &Env,
+    config: &Config,
+    token_id: &str,
+    token: XyzTokenInfo,
+    buyer: &Addr,
+    price: &Coin,
+) -> Result<(Response, Uint128), ContractError> {
+    let seller = token.owner.clone();
+    let mut new_token = token.clone();
+    new_token.owner = buyer.clone();
+    new_token.approvals = vec![];
+    tokens().replace(storage, token_id, Some(&new_token), Some(&token))?;
+    LISTINGS.remove(storage, token_id);
+    PILOTS.remove(storage, token_id);
+    decrement_owner_token_count(storage, &seller)?;
+    increment_owner_token_count(storage, buyer)?;
+    HELD_SINCE.save(storage, token_id, &env.block.time)?;
+    let hooks = hook_messages(
+        storage,
+        XyzHookMsg {
+            event: XyzHookEvent::Transfer,
+            token_id: numeric_token_id(token_id.to_string())?,
+            owner: buyer.to_string(),
+            prev_coordinates: Some(token.extension.coordinates),
+            coordinates: Some(token.extension.coordinates),
+            arrival: token.extension.arrival,
+        },
+    )?;
+
+    let royalty_address = match &config.royalty_payment_address {
+        Some(address) => address.clone(),
+        None => OWNER.load(storage)?,
+    };
+    let royalty = match royalty_address.is_empty() {
+        true => Uint128::zero(),
+        false => config.royalty_amount(price.amount),
+    };
+    let proceeds = price.amount - royalty;
+    let mut response = Response::new().add_submessages(hooks);
+    for (address, amount) in [(seller.to_string(), proceeds), (royalty_address, royalty)] {
+        if !amount.is_zero() {
+            response = response.add_message(BankMsg::Send {
+                to_address: address,
+                amount: vec![Coin::new(amount.u128(), &price.denom)],
+            });
+        }
+    }
+    Ok((response, royalty))
+}
+
+pub fn execute_make_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let token = tokens().load(deps.storage, &token_id)?;
+    if token.owner == info.sender {
+        return Err(ContractError::Std(StdError::generic_err(
+            "can't make an offer on your own token",
+        )));
+    }
+    let expires = expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+    let price = match info.funds.as_slice() {
+        [price] if !price.amount.is_zero() => price.clone(),
+        _ => {
+            return Err(ContractError::Std(StdError::generic_err(
+                "offer must be paid in a single denom",
+            )))
+        }
+    };
+
+    let old = save_offer(
+        deps.storage,
+        &Offer {
+            token_id: token_id.clone(),
+            bidder: info.sender.clone(),
+            price: price.clone(),
+            expires,
+        },
+    )?;
+    let mut response = Response::new();
+    if let Some(old) = old {
+        response = response
+            .add_message(BankMsg::Send {
+                to_address: old.bidder.to_string(),
+                amount: vec![old.price.clone()],
+            })
+            .add_attribute("refund", old.price.to_string());
+    }
+    Ok(response
+        .add_attribute("action", "make_offer")
+        .add_attribute("bidder", info.sender)
+        .add_attribute("token_id", numeric_token_id(token_id)?)
+        .add_attribute("price", price.to_string()))
+}
+
+pub fn execute_withdraw_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    bidder: Option<String>,
+) -> Result<Response, ContractError> {
+    let bidder = match bidder {
+        Some(bidder) => deps.api.addr_validate(&bidder)?,
+        None => info.sender.clone(),
+    };
+    let offer = OFFERS
+        .may_load(deps.storage, (&token_id, &bidder))?
+        .ok_or(ContractError::NoOffer {})?;
+    if info.sender != bidder && !offer.expires.is_expired(&env.block) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    remove_offer(deps.storage, &token_id, &bidder)?;
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: bidder.to_string(),
+            amount: vec![offer.price.clone()],
+        })
+        .add_attribute("action", "withdraw_offer")
+        .add_attribute("bidder", bidder)
+        .add_attribute("token_id", numeric_token_id(token_id)?)
+        .add_attribute("refund", offer.price.to_string()))
+}
+
+pub fn execute_accept_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    bidder: String,
+) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage, PausableAction::Transfer)?;
+    let config = CONFIG.load(deps.storage)?;
+    let bidder = deps.api.addr_validate(&bidder)?;
+    let token = advance_move_queue(deps.storage, &config, &token_id, env.block.time)?;
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    let offer = remove_offer(deps.storage, &token_id, &bidder)?.ok_or(ContractError::NoOffer {})?;
+
+    // an expired offer can't be accepted, so refund it rather than fail
+    // and leave the escrow in place
+    if offer.expires.is_expired(&env.block) {
+        return Ok(Response::new()
+            .add_message(BankMsg::Send {
+                to_address: bidder.to_string(),
+                amount: vec![offer.price.clone()],
+            })
+            .add_attribute("action", "refund_expired_offer")
+            .add_attribute("bidder", bidder)
+            .add_attribute("token_id", numeric_token_id(token_id)?)
+            .add_attribute("refund", offer.price.to_string()));
+    }
+    if config.transfers_locked_during_move && !token.extension.has_arrived(env.block.time) {
+        return Err(ContractError::TransferLockedDuringMove {
+            arrival: token.extension.arrival,
+        });
+    }
+
+    let (response, royalty) = settle_sale(
+        deps.storage,
+        &env,
+        &config,
+        &token_id,
+        token,
+        &bidder,
+        &offer.price,
+    )?;
+    Ok(response
+        .add_attribute("action", "accept_offer")
+        .add_attribute("buyer", bidder)
+        .add_attribute("seller", info.sender)
+        .add_attribute("token_id", numeric_token_id(token_id)?)
+        .add_attribute("price", offer.price.to_string())
+        .add_attribute("royalty", royalty))
+}
+
+/// Adds a nonzero coin to a list of coins, merging it with any coin of the
+/// same denom.
+fn add_coin(coins: &mut Vec<Coin>, coin: Coin) {
+    if coin.amount.is_zero() {
+        return;
+    }
+    match coins.iter_mut().find(|c| c.denom == coin.denom) {
+        Some(existing) => existing.amount += coin.amount,
+        None => coins.push(coin),
+    }
+}
+
+pub fn execute_merge(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id_keep: String,
+    token_id_burn: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let keep = advance_move_queue(deps.storage, &config, &token_id_keep, env.block.time)?;
+    let burn = advance_move_queue(deps.storage, &config, &token_id_burn, env.block.time)?;
+
+    // check that the sender owns both tokens
+    if keep.owner != info.sender || burn.owner != info.sender || token_id_keep == token_id_burn {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // check that neither token is mid-move
+    if !keep.extension.has_arrived(env.block.time) || !burn.extension.has_arrived(env.block.time) {
+        return Err(ContractError::MoveInProgress {});
+    }
+
+    // check that the tokens are adjacent
+    if config.distance(keep.extension.coordinates, burn.extension.coordinates) > 1 {
+        return Err(ContractError::NotAdjacent {});
+    }
+
+    // check that the kept token can still level up
+    if keep.extension.level >= config.max_merge_level {
+        return Err(ContractError::MaxMergeLevel {});
+    }
+
+    // burn the merged token, which frees its coordinates
+    let hooks = burn_token(deps.storage, &env, &token_id_burn, &burn)?;
+
+    let mut new_keep = keep.clone();
+    new_keep.extension.level += 1;
+    tokens().replace(deps.storage, &token_id_keep, Some(&new_keep), Some(&keep))?;
+
+    Ok(Response::default()
+        .add_submessages(hooks)
+        .add_attribute("action", "merge")
+        .add_attribute("owner", info.sender)
+        .add_attribute("token_id", numeric_token_id(token_id_keep)?)
+        .add_attribute("burned_token_id", numeric_token_id(token_id_burn)?)
+        .add_attribute("level", new_keep.extension.level.to_string()))
+}
+
+pub fn execute_burn(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let token = advance_move_queue(deps.storage, &config, &token_id, env.block.time)?;
+
+    // check that the sender owns or is approved to send the token
+    check_can_send(deps.as_ref(), &env, &info.sender, &token)?;
+
+    // check that a move isn't currently in progess, so the token occupies
+    // only its own coordinates
+    if !token.extension.has_arrived(env.block.time) {
+        return Err(ContractError::MoveInProgress {});
+    }
+
+    let hooks = burn_token(deps.storage, &env, &token_id, &token)?;
+
+    let coordinates = token.extension.coordinates;
+    Ok(Response::new()
+        .add_submessages(hooks)
+        .add_attribute("action", "burn")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", numeric_token_id(token_id)?)
+        .add_attribute("x", coordinates.x.to_string())
+        .add_attribute("y", coordinates.y.to_string())
+        .add_attribute("z", coordinates.z.to_string()))
+}
+
+/// Removes a token, freeing its coordinates, and updates the live token
+/// counts. The token's id is never reused. Returns the hook submessages
+/// reporting the burn.
+fn burn_token(
+    storage: &mut dyn Storage,
+    env: &Env,
+    token_id: &str,
+    token: &XyzTokenInfo,
+) -> Result<Vec<SubMsg>, ContractError> {
+    let cw721_contract = Cw721Contract::<XyzExtension, Empty>::default();
+
+    tokens().remove(storage, token_id)?;
+    COORDINATE_TOKENS.remove(
+        storage,
+        &coordinate_key(&token.extension.coordinates, token_id),
+    );
+    remove_move_fee_paid(storage, token_id);
+    MOVE_STARTED_AT.remove(storage, token_id);
+    HELD_SINCE.remove(storage, token_id);
+    LISTINGS.remove(storage, token_id);
+    PILOTS.remove(storage, token_id);
+    let num_tokens = cw721_contract.token_count(storage)?;
+    cw721_contract
+        .token_count
+        .save(storage, &num_tokens.saturating_sub(1))?;
+    decrement_owner_token_count(storage, &token.owner)?;
+    Ok(hook_messages(
+        storage,
+        XyzHookMsg {
+            event: XyzHookEvent::Burn,
+            token_id: numeric_token_id(token_id.to_string())?,
+            owner: token.owner.to_string(),
+            prev_coordinates: Some(token.extension.coordinates),
+            coordinates: None,
+            arrival: env.block.time,
+        },
+    )?)
+}
+
+/// Checks that the sender is the token's owner, holds an unexpired approval
+/// for it, or is an unexpired operator for its owner.
+fn check_can_send(
+    deps: Deps,
+    env: &Env,
+    sender: &Addr,
+    token: &XyzTokenInfo,
+) -> Result<(), ContractError> {
+    if token.owner == *sender {
+        return Ok(());
+    }
+    if token
+        .approvals
+        .iter()
+        .any(|approval| approval.spender == *sender && !approval.is_expired(&env.block))
+    {
+        return Ok(());
+    }
+    let cw721_contract = Cw721Contract::<XyzExtension, Empty>::default();
+    match cw721_contract
+        .operators
+        .may_load(deps.storage, (&token.owner, sender))?
+    {
+        Some(expiration) if !expiration.is_expired(&env.block) => Ok(()),
+        _ => Err(ContractError::Unauthorized {}),
+    }
+}
+
+/// Checks that the sender can send the token, as with check_can_send, or is
+/// its unexpired pilot.
+fn check_can_move(
+    deps: Deps,
+    env: &Env,
+    sender: &Addr,
+    token_id: &str,
+    token: &XyzTokenInfo,
+) -> Result<(), ContractError> {
+    match PILOTS.may_load(deps.storage, token_id)? {
+        Some(grant) if grant.pilot == *sender && !grant.expires.is_expired(&env.block) => Ok(()),
+        _ => check_can_send(deps, env, sender, token),
+    }
+}
+
+pub fn execute_grant_pilot(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    pilot: String,
+    expires: Expiration,
+) -> Result<Response, ContractError> {
+    let token = tokens().load(deps.storage, &token_id)?;
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+
+    let pilot = deps.api.addr_validate(&pilot)?;
+    PILOTS.save(
+        deps.storage,
+        &token_id,
+        &PilotGrant {
+            pilot: pilot.clone(),
+            expires,
+        },
+    )?;
+    Ok(Response::new()
+        .add_attribute("action", "grant_pilot")
+        .add_attribute("token_id", numeric_token_id(token_id)?)
+        .add_attribute("pilot", pilot)
+        .add_attribute("expires", expires.to_string()))
+}
+
+pub fn execute_revoke_pilot(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let token = tokens().load(deps.storage, &token_id)?;
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !PILOTS.has(deps.storage, &token_id) {
+        return Err(ContractError::NoPilot {});
+    }
+
+    PILOTS.remove(deps.storage, &token_id);
+    Ok(Response::new()
+        .add_attribute("action", "revoke_pilot")
+        .add_attribute("token_id", numeric_token_id(token_id)?))
+}
+
+pub fn execute_set_token_uri(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    token_uri: Option<String>,
+) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_config_not_frozen(deps.storage)?;
+    if let Some(token_uri) = &token_uri {
+        check_token_uri(token_uri)?;
+    }
+
+    let token = tokens().load(deps.storage, &token_id)?;
+    let mut new_token = token.clone();
+    new_token.extension.token_uri = token_uri;
+    tokens().replace(deps.storage, &token_id, Some(&new_token), Some(&token))?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_token_uri")
+        .add_attribute("token_id", numeric_token_id(token_id)?))
+}
+
+pub fn execute_update_config(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    config: Config,
+) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_config_not_frozen(deps.storage)?;
+    check_config(deps.api, &config)?;
+    let old_config = CONFIG.load(deps.storage)?;
+    check_config_fits_state(deps.storage, &env, &owner, &old_config, &config)?;
+    save_config(deps.storage, &env.block, &config)?;
+    Ok(Response::new().add_attribute("action", "update_config"))
+}
+
+pub fn execute_update_config_partial(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    update: ConfigUpdate,
+) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_config_not_frozen(deps.storage)?;
+    let old_config = CONFIG.load(deps.storage)?;
+    let mut config = old_config.clone();
+    let changed = config.apply_update(update);
+    check_config(deps.api, &config)?;
+    check_config_fits_state(deps.storage, &env, &owner, &old_config, &config)?;
+    save_config(deps.storage, &env.block, &config)?;
+
+    let values = serde_json::to_value(&config)
+        .map_err(|e| StdError::serialize_err("config", e.to_string()))?;
+    let mut response = Response::new()
+        .add_attribute("action", "update_config_partial")
+        .add_attribute("changed", changed.join(","));
+    for field in changed {
+        response = response.add_attribute(field, values[field].to_string());
+    }
+    Ok(response)
+}
+
+/// Checks that replacing the old config with the new one leaves every
+/// existing token valid: the supply still covers the tokens minted, the
+/// bounds still cover each token's position, departure while it's moving,
+/// and queued destinations, and the wallet limit still covers each limited
+/// wallet. Changing base_move_fee's denom is allowed while moves are in
+/// progress or queued, since expediting and cancelling them use the denom
+/// of the fee recorded when each was paid for.
+fn check_config_fits_state(
+    storage: &dyn Storage,
+    env: &Env,
+    owner: &str,
+    old: &Config,
+    new: &Config,
+) -> Result<(), ContractError> {
+    let minted = load_tokens_minted(storage)?;
+    if new.token_supply < minted {
+        return Err(ContractError::SupplyBelowMinted { minted });
+    }
+
+    // tokens can only be stranded by bounds narrower than the old ones
+    if !new.bounds.contains(&old.bounds) {
+        for item in tokens().range(storage, None, None, Order::Ascending) {
+            let (token_id, token) = item?;
+            let out_of_bounds = |coords: Coordinates| new.check_bounds(coords).is_err();
+            if out_of_bounds(token.extension.coordinates)
+                || (!token.extension.has_arrived(env.block.time)
+                    && token
+                        .extension
+                        .prev_coordinates
+                        .map_or(false, out_of_bounds))
+            {
+                return Err(ContractError::BoundsExcludeToken {
+                    token_id: String::from_utf8(token_id).map_err(StdError::invalid_utf8)?,
+                });
+            }
+        }
+        for item in QUEUED_COORDINATES.range(storage, None, None, Order::Ascending) {
+            let (key, token_id) = item?;
+            if new.check_bounds(coordinates_of_key(&key)?).is_err() {
+                return Err(ContractError::BoundsExcludeToken { token_id });
+            }
+        }
+    }
+
+    if new.wallet_limit < old.wallet_limit {
+        for item in OWNER_TOKEN_COUNTS.range(storage, None, None, Order::Ascending) {
+            let (address, held) = item?;
+            let address =
+                Addr::unchecked(String::from_utf8(address).map_err(StdError::invalid_utf8)?);
+            if held > new.wallet_limit.into()
+                && address != owner
+                && !WALLET_LIMIT_EXEMPTIONS.has(storage, &address)
+            {
+                return Err(ContractError::WalletLimitBelowHoldings {
+                    address: address.into(),
+                    held,
+                });
+            }
+        }
+    }
+
+    if new.unique_coordinates && !old.unique_coordinates {
+        if let Some(coords) = find_stacked_coordinates(storage)? {
+            return Err(ContractError::CoordinatesStacked {
+                x: coords.x,
+                y: coords.y,
+                z: coords.z,
+            });
+        }
+    }
+    Ok(())
+}
+
+pub fn execute_update_captcha_public_key(
+    deps: DepsMut,
+    info: MessageInfo,
+    public_key: String,
+    algorithm: CaptchaKeyAlgorithm,
+) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_config_not_frozen(deps.storage)?;
+
+    save_captcha_public_key(deps.storage, &public_key, algorithm)?;
+
+    Ok(Response::new().add_attribute("action", "update_captcha_public_key"))
+}
+
+pub fn execute_add_captcha_public_key(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    public_key: String,
+    algorithm: CaptchaKeyAlgorithm,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_config_not_frozen(deps.storage)?;
+
+    add_captcha_public_key(deps.storage, &env.block, &public_key, algorithm, expires)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_captcha_public_key")
+        .add_attribute("public_key", public_key)
+        .add_attribute(
+            "expires",
+            expires.map_or_else(|| "never".to_string(), |expires| expires.to_string()),
+        ))
+}
+
+pub fn execute_remove_captcha_public_key(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    public_key: String,
+    algorithm: CaptchaKeyAlgorithm,
+) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_config_not_frozen(deps.storage)?;
+
+    remove_captcha_public_key(deps.storage, &env.block, &public_key, algorithm)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "remove_captcha_public_key")
+        .add_attribute("public_key", public_key))
+}
+
+pub fn execute_update_allowlist_merkle_root(
+    deps: DepsMut,
+    info: MessageInfo,
+    merkle_root: Option<String>,
+) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_config_not_frozen(deps.storage)?;
+
+    match &merkle_root {
+        Some(merkle_root) => {
+            decode_merkle_hash(merkle_root)?;
+            ALLOWLIST_MERKLE_ROOT.save(deps.storage, merkle_root)?;
+        }
+        None => ALLOWLIST_MERKLE_ROOT.remove(deps.storage),
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "update_allowlist_merkle_root")
+        .add_attribute("merkle_root", merkle_root.unwrap_or_default()))
+}
+
+pub fn execute_propose_new_owner(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: String,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if expires.map_or(false, |expires| expires.is_expired(&env.block)) {
+        return Err(ContractError::OwnershipProposalExpired {});
+    }
+
+    let address = deps.api.addr_validate(&address)?;
+    PENDING_OWNER.save(deps.storage, &(address.clone(), expires))?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose_new_owner")
+        .add_attribute("pending_owner", address))
+}
+
+pub fn execute_accept_ownership(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let (pending_owner, expires) = PENDING_OWNER
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoPendingOwner {})?;
+
+    if info.sender != pending_owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if expires.map_or(false, |expires| expires.is_expired(&env.block)) {
+        return Err(ContractError::OwnershipProposalExpired {});
+    }
+
+    OWNER.save(deps.storage, &pending_owner.to_string())?;
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("action", "accept_ownership")
+        .add_attribute("owner", pending_owner))
+}
+
+pub fn execute_revoke_ownership_proposal(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if PENDING_OWNER.may_load(deps.storage)?.is_none() {
+        return Err(ContractError::NoPendingOwner {});
+    }
+
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::new().add_attribute("action", "revoke_ownership_proposal"))
+}
+
+pub fn execute_freeze_config(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_config_not_frozen(deps.storage)?;
+
+    CONFIG_FROZEN.save(deps.storage, &true)?;
+
+    Ok(Response::new().add_attribute("action", "freeze_config"))
+}
+
+pub fn execute_renounce_ownership(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // royalties that went to the owner keep going to the last owner
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.royalty_payment_address.is_none() {
+        config.royalty_payment_address = Some(owner.clone());
+        save_config(deps.storage, &env.block, &config)?;
+    }
+    CONFIG_FROZEN.save(deps.storage, &true)?;
+    PENDING_OWNER.remove(deps.storage);
+    // no sender can match an empty owner, so every owner-gated message is
+    // disabled for good
+    OWNER.save(deps.storage, &String::new())?;
+
+    Ok(Response::new()
+        .add_attribute("action", "renounce_ownership")
+        .add_attribute("previous_owner", owner))
+}
+
+pub fn execute_pause(
+    deps: DepsMut,
+    info: MessageInfo,
+    actions: Option<Vec<PausableAction>>,
+) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
 
-    cw721_contract.increment_tokens(deps.storage)?;
+    let actions = actions.unwrap_or_else(|| PausableAction::ALL.to_vec());
+    if actions.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "at least one action to pause is required",
+        )));
+    }
+    PAUSED_ACTIONS.save(deps.storage, &actions)?;
 
+    let names: Vec<&str> = actions.iter().map(PausableAction::as_str).collect();
     Ok(Response::new()
-        .add_attribute("action", "mint")
-        .add_attribute("minter", info.sender)
-        .add_attribute("token_id", numeric_token_id(token_id)?))
+        .add_attribute("action", "pause")
+        .add_attribute("paused", names.join(",")))
 }
 
-fn check_sufficient_funds(funds: Vec<Coin>, required: Coin) -> Result<(), ContractError> {
-    if required.amount.u128() == 0 {
-        return Ok(());
-    }
-    let sent_sufficient_funds = funds.iter().any(|coin| {
-        // check if a given sent coin matches denom
-        // and has sufficient amount
-        coin.denom == required.denom && coin.amount.u128() >= required.amount.u128()
-    });
-    if sent_sufficient_funds {
-        Ok(())
-    } else {
-        Err(ContractError::Std(StdError::generic_err(
-            "insufficient funds sent",
-        )))
+pub fn execute_unpause(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
     }
+
+    PAUSED_ACTIONS.remove(deps.storage);
+
+    Ok(Response::new().add_attribute("action", "unpause"))
 }
 
-fn check_wallet_limit(
+pub fn check_not_paused(
     storage: &dyn Storage,
-    owner: cosmwasm_std::Addr,
-    limit: u32,
+    action: PausableAction,
 ) -> Result<(), ContractError> {
-    let num_wallet_tokens = tokens()
-        .idx
-        .owner
-        .prefix(owner)
-        .range(storage, None, None, Order::Ascending)
-        .count();
-
-    if num_wallet_tokens >= limit as usize {
-        Err(ContractError::WalletLimit {})
+    let paused = PAUSED_ACTIONS.may_load(storage)?.unwrap_or_default();
+    if paused.contains(&action) {
+        Err(ContractError::Paused {})
     } else {
         Ok(())
     }
 }
 
-fn check_captcha_signature(
-    storage: &dyn Storage,
-    coordinates: &Coordinates,
-    captcha_signature: &str,
-) -> Result<(), ContractError> {
-    let key = load_captcha_public_key(storage).unwrap();
+/// Rejects parameter changes once the config has been frozen.
+fn check_config_not_frozen(storage: &dyn Storage) -> Result<(), ContractError> {
+    if CONFIG_FROZEN.may_load(storage)?.unwrap_or_default() {
+        Err(ContractError::ConfigFrozen {})
+    } else {
+        Ok(())
+    }
+}
 
-    let signature_bytes = base64::decode(captcha_signature).unwrap();
+pub fn execute_add_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
 
-    let coords_json_bytes = serde_json::to_vec(coordinates).unwrap();
-    let mut hasher = Sha256::new();
-    hasher.update(&coords_json_bytes);
-    let digest = hasher.finalize();
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_config_not_frozen(deps.storage)?;
 
-    if key
-        .verify(
-            PaddingScheme::PKCS1v15Sign {
-                hash: Some(Hash::SHA2_256),
-            },
-            &digest,
-            &signature_bytes,
-        )
-        .is_ok()
-    {
-        Ok(())
-    } else {
-        Err(ContractError::Unauthorized {})
+    let addr = deps.api.addr_validate(&addr)?;
+    let mut hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    if hooks.contains(&addr) {
+        return Err(ContractError::HookAlreadyRegistered {});
+    }
+    if hooks.len() >= MAX_HOOKS {
+        return Err(ContractError::TooManyHooks { max: MAX_HOOKS });
     }
+    hooks.push(addr.clone());
+    HOOKS.save(deps.storage, &hooks)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_hook")
+        .add_attribute("hook", addr))
 }
 
-fn check_coordinates(storage: &dyn Storage, coords: &Coordinates) -> Result<(), ContractError> {
-    let config = CONFIG.load(storage)?;
-    config.check_bounds(*coords).map_err(ContractError::Std)?;
-    match tokens().idx.coordinates.item(storage, coords.to_bytes())? {
-        Some(_) => Err(ContractError::Claimed {}),
-        None => Ok(()),
+pub fn execute_remove_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    let owner = OWNER.load(deps.storage)?;
+
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
     }
+    check_config_not_frozen(deps.storage)?;
+
+    let addr = deps.api.addr_validate(&addr)?;
+    let mut hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    if !hooks.contains(&addr) {
+        return Err(ContractError::HookNotRegistered {});
+    }
+    hooks.retain(|hook| hook != &addr);
+    HOOKS.save(deps.storage, &hooks)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "remove_hook")
+        .add_attribute("hook", addr))
 }
 
-pub fn execute_move(
+pub fn execute_reserve_coordinates(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    token_id: String,
-    coordinates: Coordinates,
+    reservations: Vec<Reservation>,
 ) -> Result<Response, ContractError> {
     let owner = OWNER.load(deps.storage)?;
     let config = CONFIG.load(deps.storage)?;
-    let token = tokens().load(deps.storage, &token_id)?;
 
-    // check that the sender owns the token
-    if token.owner != info.sender {
+    if info.sender != owner {
         return Err(ContractError::Unauthorized {});
     }
+    check_config_not_frozen(deps.storage)?;
 
-    // check that a move isn't currently in progess
-    if !token.extension.has_arrived(env.block.time) {
-        return Err(ContractError::MoveInProgress {});
-    }
-
-    // check that a non-owner sent funds greater than the move fee
-    if owner != info.sender {
-        let move_fee = config.get_move_fee(token.extension.coordinates, coordinates);
-        check_sufficient_funds(info.funds, move_fee)?;
+    check_reservation_batch(reservations.len())?;
+    for reservation in reservations.iter() {
+        config.check_bounds(reservation.coordinates)?;
+        if !reservation.is_active(env.block.time) {
+            return Err(ContractError::Std(StdError::generic_err(
+                "reservations must expire after the current block",
+            )));
+        }
+        RESERVED_COORDINATES.save(
+            deps.storage,
+            &reservation.coordinates.to_bytes(),
+            reservation,
+        )?;
     }
 
-    // check that move target is unoccupied and in bounds
-    check_coordinates(deps.storage, &coordinates)?;
-
-    // update token with new coordinates, prev coordinates, and arrival time
-    let mut new_token = token.clone();
-    new_token.image = Some(base64_token_image(&coordinates));
-    new_token.extension.coordinates = coordinates;
-    new_token.extension.prev_coordinates = Some(token.extension.coordinates);
-    let travel_time_nanos = config.get_move_nanos(token.extension.coordinates, coordinates);
-    new_token.extension.arrival = env.block.time.plus_nanos(travel_time_nanos);
-    tokens().replace(deps.storage, &token_id, Some(&new_token), Some(&token))?;
-
-    Ok(Response::default()
-        .add_attribute("action", "move")
-        .add_attribute("mover", info.sender)
-        .add_attribute("token_id", numeric_token_id(token_id)?))
+    Ok(Response::new()
+        .add_attribute("action", "reserve_coordinates")
+        .add_attribute("count", reservations.len().to_string()))
 }
 
-pub fn execute_update_config(
+pub fn execute_release_coordinates(
     deps: DepsMut,
     info: MessageInfo,
-    config: Config,
+    coordinates: Vec<Coordinates>,
 ) -> Result<Response, ContractError> {
     let owner = OWNER.load(deps.storage)?;
+
     if info.sender != owner {
         return Err(ContractError::Unauthorized {});
     }
-    CONFIG.save(deps.storage, &config)?;
-    Ok(Response::new().add_attribute("action", "update_config"))
+    check_config_not_frozen(deps.storage)?;
+
+    check_reservation_batch(coordinates.len())?;
+    for coords in coordinates.iter() {
+        RESERVED_COORDINATES.remove(deps.storage, &coords.to_bytes());
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "release_coordinates")
+        .add_attribute("count", coordinates.len().to_string()))
 }
 
-pub fn execute_update_captcha_public_key(
+pub fn execute_set_wallet_limit_exemption(
     deps: DepsMut,
     info: MessageInfo,
-    public_key: String,
+    address: String,
+    exempt: bool,
 ) -> Result<Response, ContractError> {
     let owner = OWNER.load(deps.storage)?;
 
     if info.sender != owner {
         return Err(ContractError::Unauthorized {});
     }
+    check_config_not_frozen(deps.storage)?;
+
+    let address = deps.api.addr_validate(&address)?;
+    if exempt {
+        WALLET_LIMIT_EXEMPTIONS.save(deps.storage, &address, &Empty {})?;
+    } else {
+        WALLET_LIMIT_EXEMPTIONS.remove(deps.storage, &address);
+    }
 
-    save_captcha_public_key(deps.storage, &public_key)?;
+    Ok(Response::new()
+        .add_attribute("action", "set_wallet_limit_exemption")
+        .add_attribute("address", address)
+        .add_attribute("exempt", exempt.to_string()))
+}
 
-    Ok(Response::new().add_attribute("action", "update_captcha_public_key"))
+fn check_reservation_batch(len: usize) -> Result<(), ContractError> {
+    if len == 0 {
+        return Err(ContractError::Std(StdError::generic_err(
+            "at least one set of coordinates is required",
+        )));
+    }
+    if len > MAX_RESERVATION_BATCH {
+        return Err(ContractError::ReservationBatchTooLarge {
+            max: MAX_RESERVATION_BATCH,
+        });
+    }
+    Ok(())
+}
+
+/// The reply id of hook submessages
+pub const HOOK_REPLY_ID: u64 = 1;
+
+/// Returns submessages sending msg to each registered hook. Failed hooks are
+/// caught by hook_reply, so they never revert the change they report.
+fn hook_messages(storage: &dyn Storage, msg: XyzHookMsg) -> StdResult<Vec<SubMsg>> {
+    HOOKS
+        .may_load(storage)?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|hook| {
+            Ok(SubMsg::reply_on_error(
+                msg.clone().into_cosmos_msg(hook)?,
+                HOOK_REPLY_ID,
+            ))
+        })
+        .collect()
+}
+
+/// Handles a failed hook submessage. Its effects have already been rolled
+/// back, so the error is recorded and otherwise ignored.
+pub fn hook_reply(msg: Reply) -> Result<Response, ContractError> {
+    let mut response = Response::new().add_attribute("action", "hook_failed");
+    if let ContractResult::Err(err) = msg.result {
+        response = response.add_attribute("error", err);
+    }
+    Ok(response)
 }
 
 pub fn execute_withdraw(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     amount: Vec<Coin>,
+    cw20_amount: Vec<Cw20Coin>,
+    recipient: Option<String>,
+    balance: Option<WithdrawBalance>,
 ) -> Result<Response, ContractError> {
     let owner = OWNER.load(deps.storage)?;
     if info.sender != owner {
         return Err(ContractError::Unauthorized {});
     }
 
-    Ok(Response::new().add_message(BankMsg::Send {
-        amount,
-        to_address: owner,
-    }))
+    let amount = match balance {
+        None => amount,
+        Some(_) if !amount.is_empty() => {
+            return Err(ContractError::Std(StdError::generic_err(
+                "amount must be empty when withdrawing a full balance",
+            )));
+        }
+        Some(WithdrawBalance::All) => withdrawable_balances(deps.as_ref(), &env)?,
+        Some(WithdrawBalance::Denom(denom)) => {
+            let balance = deps.querier.query_balance(&env.contract.address, denom)?;
+            let balance = withdrawable_balance(deps.storage, balance)?;
+            vec![balance]
+                .into_iter()
+                .filter(|coin| !coin.amount.is_zero())
+                .collect()
+        }
+    };
+
+    // leave enough of each denom to cover the offers and swaps in escrow
+    for coin in &amount {
+        let escrow = ESCROW
+            .may_load(deps.storage, &coin.denom)?
+            .unwrap_or_default();
+        if escrow.is_zero() {
+            continue;
+        }
+        let balance = deps
+            .querier
+            .query_balance(&env.contract.address, &coin.denom)?;
+        if coin.amount + escrow > balance.amount {
+            return Err(ContractError::Std(StdError::generic_err(format!(
+                "can't withdraw {}, {}{} is held in escrow",
+                coin, escrow, coin.denom
+            ))));
+        }
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let recipients = match recipient {
+        Some(recipient) => vec![(deps.api.addr_validate(&recipient)?.to_string(), 10_000)],
+        None if config.fee_split.is_empty() => vec![(owner, 10_000)],
+        None => config.fee_split,
+    };
+
+    // divide each coin among the recipients
+    let mut shares = vec![(vec![], vec![]); recipients.len()];
+    for coin in amount {
+        let split = split_amount(coin.amount, &recipients);
+        for ((coins, _), amount) in shares.iter_mut().zip(split) {
+            add_coin(coins, Coin::new(amount.u128(), &coin.denom));
+        }
+    }
+    for coin in cw20_amount {
+        deps.api.addr_validate(&coin.address)?;
+        let split = split_amount(coin.amount, &recipients);
+        for ((_, cw20_coins), amount) in shares.iter_mut().zip(split) {
+            if !amount.is_zero() {
+                cw20_coins.push(Cw20Coin {
+                    address: coin.address.clone(),
+                    amount,
+                });
+            }
+        }
+    }
+
+    let mut response = Response::new().add_attribute("action", "withdraw");
+    for ((recipient, _), (coins, cw20_coins)) in recipients.into_iter().zip(shares) {
+        if coins.is_empty() && cw20_coins.is_empty() {
+            continue;
+        }
+        let amount_attr = coins
+            .iter()
+            .map(|coin| coin.to_string())
+            .chain(
+                cw20_coins
+                    .iter()
+                    .map(|coin| format!("{}{}", coin.amount, coin.address)),
+            )
+            .collect::<Vec<_>>()
+            .join(",");
+        response = response
+            .add_attribute("recipient", &recipient)
+            .add_attribute("amount", amount_attr);
+        if !coins.is_empty() {
+            response = response.add_message(BankMsg::Send {
+                amount: coins,
+                to_address: recipient.clone(),
+            });
+        }
+        for coin in cw20_coins {
+            let transfer = Cw20ExecuteMsg::Transfer {
+                recipient: recipient.clone(),
+                amount: coin.amount,
+            };
+            response = response.add_message(transfer.into_cosmos_msg(coin.address)?);
+        }
+    }
+    Ok(response)
+}
+
+/// Returns the contract's native balances less the funds held in escrow for
+/// offers and swaps, sorted by denom and leaving out denoms with nothing to
+/// withdraw.
+pub fn withdrawable_balances(deps: Deps, env: &Env) -> StdResult<Vec<Coin>> {
+    let mut balances = vec![];
+    for balance in deps.querier.query_all_balances(&env.contract.address)? {
+        let balance = withdrawable_balance(deps.storage, balance)?;
+        if !balance.amount.is_zero() {
+            balances.push(balance);
+        }
+    }
+    balances.sort_by(|a, b| a.denom.cmp(&b.denom));
+    Ok(balances)
+}
+
+fn withdrawable_balance(storage: &dyn Storage, balance: Coin) -> StdResult<Coin> {
+    let escrow = ESCROW
+        .may_load(storage, &balance.denom)?
+        .unwrap_or_default();
+    Ok(Coin {
+        amount: balance.amount.saturating_sub(escrow),
+        denom: balance.denom,
+    })
+}
+
+/// Divides amount among recipients by their basis-point shares, giving any
+/// rounding dust to the first recipient.
+pub fn split_amount(amount: Uint128, recipients: &[(String, u16)]) -> Vec<Uint128> {
+    let mut split: Vec<Uint128> = recipients
+        .iter()
+        .map(|(_, bps)| amount.multiply_ratio(*bps, 10_000u128))
+        .collect();
+    let paid = split
+        .iter()
+        .fold(Uint128::zero(), |sum, share| sum + *share);
+    if let Some(first) = split.first_mut() {
+        *first += amount - paid;
+    }
+    split
+}
+
+/// Handles a mint or move paid for with CW20 tokens. The calling CW20 token
+/// contract must be the one configured for the fee being paid.
+pub fn execute_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let sender = deps.api.addr_validate(&wrapper.sender)?;
+    match from_binary(&wrapper.msg)? {
+        Cw20HookMsg::Mint {
+            coordinates,
+            captcha_signature,
+            nonce,
+            expiry,
+        } => {
+            let mint = MintRequest {
+                coordinates,
+                captcha_signature,
+                nonce,
+                expiry,
+            };
+            execute_cw20_mint(deps, env, sender, info.sender, wrapper.amount, mint)
+        }
+        Cw20HookMsg::Move {
+            token_id,
+            coordinates,
+        } => {
+            let config = CONFIG.load(deps.storage)?;
+            if config
+                .cw20_move_fee
+                .as_ref()
+                .map_or(true, |fee| fee.address != info.sender.as_str())
+            {
+                return Err(ContractError::UnacceptedCw20Token {});
+            }
+            let payment = MovePayment::Cw20 {
+                token: info.sender,
+                amount: wrapper.amount,
+            };
+            move_token(deps, env, sender, payment, token_id, coordinates)
+        }
+    }
+}
+
+fn execute_cw20_mint(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    token: Addr,
+    amount: Uint128,
+    mint: MintRequest,
+) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage, PausableAction::Mint)?;
+    let owner = OWNER.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+
+    let mint_fee = match &config.cw20_mint_fee {
+        Some(fee) if fee.address == token.as_str() => {
+            let adjacent = adjacent_to_owned(deps.storage, &config, &sender, mint.coordinates)?;
+            config.scale_mint_fee(fee.amount, mint.coordinates, adjacent)?
+        }
+        _ => return Err(ContractError::UnacceptedCw20Token {}),
+    };
+
+    let response = if sender != owner {
+        check_cw20_mint(
+            deps.storage,
+            &env,
+            &sender,
+            &token,
+            amount,
+            &config,
+            mint_fee,
+        )?
+    } else {
+        // the owner is never charged, so their payment is refunded as with
+        // refund_owner_payment
+        let refund = charge_cw20_fee(&config, &sender, &token, amount, Uint128::zero())?;
+        Response::new().add_messages(refund)
+    };
+
+    let info = MessageInfo {
+        sender,
+        funds: vec![],
+    };
+    let (token_ids, hooks) = mint_tokens(
+        deps.storage,
+        deps.api,
+        &env,
+        &info,
+        &config,
+        info.sender != owner,
+        vec![(mint, None)],
+    )?;
+
+    Ok(response
+        .add_submessages(hooks)
+        .add_attribute("action", "mint")
+        .add_attribute("minter", info.sender)
+        .add_attributes(minted_token_attributes(
+            &config,
+            token_ids[0].clone(),
+            None,
+        )?))
 }
 
 pub fn cw721_base_execute(
@@ -274,7 +4072,7 @@ pub fn cw721_base_execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     let cw721_contract = Cw721Contract::<XyzExtension, Empty>::default();
-    let cw721_msg: Cw721ExecuteMsg<XyzExtension> = msg.into();
+    let cw721_msg: Cw721ExecuteMsg<XyzExtension> = msg.try_into()?;
     let cw721_msg_full_token_id = match cw721_msg {
         Cw721ExecuteMsg::Approve {
             spender,
@@ -309,6 +4107,10 @@ pub fn cw721_base_execute(
     };
 
     let mut response = (match cw721_msg_full_token_id {
+        Cw721ExecuteMsg::TransferNft {
+            recipient,
+            token_id,
+        } => execute_transfer_nft(deps, env, info, recipient, token_id),
         Cw721ExecuteMsg::SendNft {
             contract,
             token_id,
@@ -336,6 +4138,30 @@ pub fn cw721_base_execute(
     Ok(response)
 }
 
+pub fn execute_transfer_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let hooks = transfer_nft(
+        deps,
+        &env,
+        &info,
+        &recipient,
+        &token_id,
+        XyzHookEvent::Transfer,
+    )?;
+
+    Ok(Response::new()
+        .add_submessages(hooks)
+        .add_attribute("action", "transfer_nft")
+        .add_attribute("sender", info.sender)
+        .add_attribute("recipient", recipient)
+        .add_attribute("token_id", token_id))
+}
+
 pub fn execute_send_nft(
     deps: DepsMut,
     env: Env,
@@ -344,9 +4170,8 @@ pub fn execute_send_nft(
     token_id: String,
     msg: Binary,
 ) -> Result<Response, ContractError> {
-    let cw721_contract = Cw721Contract::<XyzExtension, Empty>::default();
     // Transfer token
-    cw721_contract._transfer_nft(deps, &env, &info, &contract, &token_id)?;
+    let hooks = transfer_nft(deps, &env, &info, &contract, &token_id, XyzHookEvent::Send)?;
 
     let send = Cw721ReceiveMsg {
         sender: info.sender.to_string(),
@@ -357,13 +4182,194 @@ pub fn execute_send_nft(
     // Send message
     Ok(Response::new()
         .add_message(send.into_cosmos_msg(contract.clone())?)
+        .add_submessages(hooks)
         .add_attribute("action", "send_nft")
         .add_attribute("sender", info.sender)
         .add_attribute("recipient", contract)
         .add_attribute("token_id", token_id))
 }
 
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+/// The most tokens TransferMany or SendMany can transfer at once
+pub const MAX_TRANSFER_BATCH: usize = 50;
+
+/// Transfers each of the tokens, given by their numeric ids, to recipient as
+/// with transfer_nft. Returns the full token ids along with the hook
+/// submessages reporting the transfers as event.
+fn transfer_many(
+    mut deps: DepsMut,
+    env: &Env,
+    info: &MessageInfo,
+    recipient: &str,
+    token_ids: Vec<String>,
+    event: XyzHookEvent,
+) -> Result<(Vec<String>, Vec<SubMsg>), ContractError> {
+    if token_ids.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "transfer many requires at least one token",
+        )));
+    }
+    if token_ids.len() > MAX_TRANSFER_BATCH {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "cannot transfer more than {} tokens at once",
+            MAX_TRANSFER_BATCH
+        ))));
+    }
+
+    let mut full_token_ids: Vec<String> = vec![];
+    for token_id in token_ids {
+        let token_id = full_token_id(token_id)?;
+        if full_token_ids.contains(&token_id) {
+            return Err(ContractError::Std(StdError::generic_err(format!(
+                "token {} is listed more than once",
+                numeric_token_id(token_id)?
+            ))));
+        }
+        full_token_ids.push(token_id);
+    }
+
+    let mut hooks = vec![];
+    for token_id in &full_token_ids {
+        hooks.extend(transfer_nft(
+            deps.branch(),
+            env,
+            info,
+            recipient,
+            token_id,
+            event.clone(),
+        )?);
+    }
+    Ok((full_token_ids, hooks))
+}
+
+pub fn execute_transfer_many(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    token_ids: Vec<String>,
+) -> Result<Response, ContractError> {
+    let (token_ids, hooks) = transfer_many(
+        deps,
+        &env,
+        &info,
+        &recipient,
+        token_ids,
+        XyzHookEvent::Transfer,
+    )?;
+
+    let mut response = Response::new()
+        .add_submessages(hooks)
+        .add_attribute("action", "transfer_many")
+        .add_attribute("sender", info.sender)
+        .add_attribute("recipient", recipient);
+    for token_id in token_ids {
+        response = response.add_attribute("token_id", numeric_token_id(token_id)?);
+    }
+    Ok(response)
+}
+
+pub fn execute_send_many(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    contract: String,
+    token_ids: Vec<String>,
+    msg: Binary,
+) -> Result<Response, ContractError> {
+    let (token_ids, hooks) =
+        transfer_many(deps, &env, &info, &contract, token_ids, XyzHookEvent::Send)?;
+
+    let mut response = Response::new();
+    for token_id in &token_ids {
+        let send = Cw721ReceiveMsg {
+            sender: info.sender.to_string(),
+            token_id: numeric_token_id(token_id.clone())?,
+            msg: msg.clone(),
+        };
+        response = response.add_message(send.into_cosmos_msg(contract.clone())?);
+    }
+    response = response
+        .add_submessages(hooks)
+        .add_attribute("action", "send_many")
+        .add_attribute("sender", info.sender)
+        .add_attribute("recipient", contract);
+    for token_id in token_ids {
+        response = response.add_attribute("token_id", numeric_token_id(token_id)?);
+    }
+    Ok(response)
+}
+
+/// Transfers a token using cw721-base's permission checks, keeping the
+/// per-owner token counts in sync with the ownership change. Returns the
+/// hook submessages reporting the transfer as event.
+fn transfer_nft(
+    mut deps: DepsMut,
+    env: &Env,
+    info: &MessageInfo,
+    recipient: &str,
+    token_id: &str,
+    event: XyzHookEvent,
+) -> Result<Vec<SubMsg>, ContractError> {
+    check_not_paused(deps.storage, PausableAction::Transfer)?;
+    let cw721_contract = Cw721Contract::<XyzExtension, Empty>::default();
+    let config = CONFIG.load(deps.storage)?;
+    // start any queued moves that became due so the lock sees the leg the
+    // token is actually on
+    let prev_token = advance_move_queue(deps.storage, &config, token_id, env.block.time)?;
+    if config.transfers_locked_during_move && !prev_token.extension.has_arrived(env.block.time) {
+        return Err(ContractError::TransferLockedDuringMove {
+            arrival: prev_token.extension.arrival,
+        });
+    }
+    let prev_owner = prev_token.owner;
+
+    let token = cw721_contract._transfer_nft(deps.branch(), env, info, recipient, token_id)?;
+
+    decrement_owner_token_count(deps.storage, &prev_owner)?;
+    increment_owner_token_count(deps.storage, &token.owner)?;
+    HELD_SINCE.save(deps.storage, token_id, &env.block.time)?;
+    LISTINGS.remove(deps.storage, token_id);
+    PILOTS.remove(deps.storage, token_id);
+
+    Ok(hook_messages(
+        deps.storage,
+        XyzHookMsg {
+            event,
+            token_id: numeric_token_id(token_id.to_string())?,
+            owner: token.owner.to_string(),
+            prev_coordinates: Some(token.extension.coordinates),
+            coordinates: Some(token.extension.coordinates),
+            arrival: token.extension.arrival,
+        },
+    )?)
+}
+
+/// Upgrades state written by the given contract version to the current
+/// layout. Each branch transforms state written before the version it names.
+pub fn migrate(
+    deps: DepsMut,
+    env: Env,
+    _msg: MigrateMsg,
+    from_version: (u64, u64, u64),
+) -> StdResult<Response> {
+    // 0.3.0 added per-axis bounds, captcha key rotation, holding periods,
+    // energy, mint times, owner token counts, order-preserving coordinate
+    // keys, the COORDINATE_TOKENS record, lifetime stats, and travel stats
+    if from_version < (0, 3, 0) {
+        migrate_config_bounds(deps.storage)?;
+        migrate_captcha_public_keys(deps.storage)?;
+        migrate_held_since(deps.storage, env.block.time)?;
+        migrate_token_extensions(deps.storage, &env.block)?;
+        rebuild_owner_token_counts(deps.storage)?;
+        rebuild_coordinate_keys(deps.storage)?;
+        start_lifetime_stats(deps.storage)?;
+    }
+
+    // refuse to upgrade onto a config the contract can't safely run with
+    let config = CONFIG.load(deps.storage)?;
+    config.validate()?;
+    save_config(deps.storage, &env.block, &config)?;
+
     Ok(Response::default().add_attribute("action", "migrate"))
 }
 
@@ -376,6 +4382,8 @@ mod test {
     use cw721::{Cw721ReceiveMsg, Expiration};
     use cw721_base::state::Approval;
 
+    use crate::contract_tests::mock_config;
+
     const ADDR1: &str = "addr1";
     const ADDR2: &str = "addr2";
 
@@ -391,6 +4399,17 @@ mod test {
                     coordinates: Coordinates { x: 1, y: 1, z: 1 },
                     arrival: Timestamp::from_nanos(0),
                     prev_coordinates: None,
+                    energy: 0,
+                    energy_updated_at: Timestamp::from_nanos(0),
+                    level: 0,
+                    token_uri: None,
+                    minted_at: Timestamp::from_nanos(0),
+                    minted_height: 0,
+                    rerolled: false,
+                    name: None,
+                    description: None,
+                    moves_count: 0,
+                    total_distance: 0,
                 },
             },
             XyzTokenInfo {
@@ -403,12 +4422,24 @@ mod test {
                     coordinates: Coordinates { x: 2, y: 2, z: 2 },
                     arrival: Timestamp::from_nanos(0),
                     prev_coordinates: None,
+                    energy: 0,
+                    energy_updated_at: Timestamp::from_nanos(0),
+                    level: 0,
+                    token_uri: None,
+                    minted_at: Timestamp::from_nanos(0),
+                    minted_height: 0,
+                    rerolled: false,
+                    name: None,
+                    description: None,
+                    moves_count: 0,
+                    total_distance: 0,
                 },
             },
         ]
     }
 
     fn setup_storage(deps: DepsMut) {
+        CONFIG.save(deps.storage, &mock_config()).unwrap();
         for token in token_examples().iter() {
             tokens().save(deps.storage, &token.name, token).unwrap();
         }
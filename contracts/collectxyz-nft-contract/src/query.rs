@@ -1,67 +1,428 @@
-use rsa::pkcs8::ToPublicKey;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
 
+use collectxyz::cw20::Cw20Coin;
 use collectxyz::nft::{
-    full_token_id, numeric_token_id, Config, Coordinates, Cw721AllNftInfoResponse,
-    Cw721NftInfoResponse, MoveParamsResponse, QueryMsg, XyzExtension, XyzTokenInfo,
-    XyzTokensResponse,
+    full_token_id, numeric_token_id, ActiveReservationsResponse, AllowlistEligibilityResponse,
+    AllowlistMerkleRootResponse, ApprovalResponse, ApprovalsResponse, CaptchaPublicKeysResponse,
+    CheckRoyaltiesResponse, CollisionCheckResponse, Config, ConfigInfoResponse, ConfigResponse,
+    ConfigVersion, CoordinateRole, CoordinateStatus, Coordinates, CoordinatesStatusResponse,
+    CurrentMintFeeResponse, Cw721AllNftInfoResponse, Cw721NftInfoResponse, DensityBucket,
+    DensityResponse, FeeBalancesResponse, HooksResponse, Listing, ListingsResponse,
+    MintFailureReason, MintPricesResponse, MintRequest, MintStatusResponse, MinterResponse,
+    MoveHistoryEntry, MoveHistoryResponse, MoveManyParamsResponse, MoveParamsResponse,
+    MoveQueueResponse, NearbyToken, OffersResponse, OperatorsResponse, OwnerLeaderboardEntry,
+    OwnerLeaderboardResponse, OwnerTokenSummaryResponse, OwnershipDistributionResponse,
+    OwnershipResponse, PausableAction, PausedResponse, PilotResponse, PublicMintsRemainingResponse,
+    QueryMsg, ReservedCoordinatesResponse, RoyaltiesInfoResponse, SimulateMintResponse, SortOrder,
+    StatsResponse, SupplyResponse, SwapParamsResponse, SwapProposal, TokenEnergyResponse,
+    TokenInTransit, TokenOrder, TokenStatsResponse, TokensInTransitResponse, TransitMatch,
+    VerifyCaptchaResponse, WalletLimitExemptionsResponse, WithdrawableBalanceResponse,
+    XyzExtension, XyzNftInfoBatchEntry, XyzNftInfoBatchResponse, XyzNftInfoByCoordsResponse,
+    XyzTokenEntry, XyzTokenInfo, XyzTokensNearbyResponse, XyzTokensResponse,
+};
+use cosmwasm_std::{
+    to_binary, Addr, Binary, BlockInfo, Coin, Deps, Empty, Env, Order, StdError, StdResult,
+    Timestamp, Uint128,
+};
+use cw721::{
+    ApprovedForAllResponse, Expiration, NumTokensResponse, OwnerOfResponse, TokensResponse,
 };
-use cosmwasm_std::{to_binary, Binary, BlockInfo, Deps, Empty, Env, Order, StdError, StdResult};
-use cw721::{NumTokensResponse, OwnerOfResponse, TokensResponse};
 use cw721_base::{msg::QueryMsg as Cw721QueryMsg, Cw721Contract};
-use cw_storage_plus::Bound;
+use cw_storage_plus::{Bound, U64Key};
+
+use crate::error::ContractError;
+use crate::execute::{
+    apply_queued_moves, captcha_payload, check_captcha_signature, check_mint_targets,
+    check_not_paused, check_public_mint_open, check_swap_legs, check_swap_tokens, priced_mints,
+    random_mint_captcha_payload, sender_move_fee, split_amount, verify_allowlist_proof,
+    withdrawable_balances,
+};
+use crate::state::{
+    arrival_key, coordinate_key, coordinates_end_key, coordinates_of_key, is_reserved,
+    load_active_captcha_public_keys, load_config_version, load_cooldown_until, load_held_nanos,
+    load_public_mints_in_block, load_token_at, load_tokens_at, load_tokens_minted,
+    owner_leaderboard_key, parse_u64_addr_key, parse_u64_key, tokens, ALLOWLIST_MERKLE_ROOT,
+    ALLOWLIST_MINTS, BIDDER_OFFERS, CONFIG, CONFIG_FROZEN, COORDINATE_TOKENS, CW20_FEES_COLLECTED,
+    DISTANCE_TRAVELED, FEES_COLLECTED, HOOKS, LISTINGS, MOVES_STARTED, MOVE_HISTORY, MOVE_QUEUES,
+    OFFERS, OWNER, OWNER_LEADERBOARD, OWNER_TOKEN_COUNTS, PAUSED_ACTIONS, PENDING_OWNER, PILOTS,
+    QUEUED_COORDINATES, RESERVED_COORDINATES, SWAP_PROPOSALS, WALLET_LIMIT_EXEMPTIONS,
+};
+
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    Ok(ConfigResponse {
+        config: CONFIG.load(deps.storage)?,
+        frozen: CONFIG_FROZEN.may_load(deps.storage)?.unwrap_or_default(),
+    })
+}
+
+pub fn query_config_info(deps: Deps) -> StdResult<ConfigInfoResponse> {
+    let ConfigVersion {
+        version,
+        updated_at,
+        updated_height,
+    } = load_config_version(deps.storage)?;
+    Ok(ConfigInfoResponse {
+        config: CONFIG.load(deps.storage)?,
+        version,
+        updated_at,
+        updated_height,
+    })
+}
+
+pub fn query_minter(deps: Deps) -> StdResult<MinterResponse> {
+    Ok(MinterResponse {
+        minter: OWNER.load(deps.storage)?,
+        public_minting_enabled: CONFIG.load(deps.storage)?.public_minting_enabled,
+    })
+}
+
+pub fn query_mint_status(deps: Deps, env: Env) -> StdResult<MintStatusResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let now = env.block.time;
+    let started = config.mint_start.map_or(true, |start| now >= start);
+    let ended = config.mint_end.map_or(false, |end| now >= end);
 
-use crate::state::{load_captcha_public_key, tokens, CONFIG};
+    // the window only opens or closes public mints while they're enabled
+    let next_transition = match (config.public_minting_enabled, started, ended) {
+        (false, _, _) | (_, _, true) => None,
+        (true, false, false) => config.mint_start,
+        (true, true, false) => config.mint_end,
+    };
+    Ok(MintStatusResponse {
+        open: config.public_minting_enabled && started && !ended,
+        public_minting_enabled: config.public_minting_enabled,
+        mint_start: config.mint_start,
+        mint_end: config.mint_end,
+        next_transition,
+        nanos_until_transition: next_transition.map(|time| time.nanos() - now.nanos()),
+    })
+}
 
-pub fn query_config(deps: Deps) -> StdResult<Config> {
-    CONFIG.load(deps.storage)
+pub fn query_ownership(deps: Deps) -> StdResult<OwnershipResponse> {
+    let pending = PENDING_OWNER.may_load(deps.storage)?;
+    Ok(OwnershipResponse {
+        owner: OWNER.load(deps.storage)?,
+        pending_owner: pending.as_ref().map(|(address, _)| address.to_string()),
+        pending_expires: pending.and_then(|(_, expires)| expires),
+    })
 }
 
-pub fn query_captcha_public_key(deps: Deps) -> StdResult<String> {
-    let public_key = load_captcha_public_key(deps.storage)?;
+pub fn query_hooks(deps: Deps) -> StdResult<HooksResponse> {
+    let hooks = HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    Ok(HooksResponse {
+        hooks: hooks.into_iter().map(String::from).collect(),
+    })
+}
 
-    public_key
-        .to_public_key_pem()
-        .map_err(|_| StdError::generic_err("couldn't serialize public key"))
+pub fn query_paused(deps: Deps) -> StdResult<PausedResponse> {
+    Ok(PausedResponse {
+        actions: PAUSED_ACTIONS.may_load(deps.storage)?.unwrap_or_default(),
+    })
+}
+
+pub fn query_reserved_coordinates(
+    deps: Deps,
+    start_after: Option<Coordinates>,
+    limit: Option<u32>,
+) -> StdResult<ReservedCoordinatesResponse> {
+    let limit = CONFIG.load(deps.storage)?.query_limit(limit);
+    let start = start_after.map(|coords| Bound::exclusive(coords.to_bytes()));
+
+    let coordinates: StdResult<Vec<_>> = RESERVED_COORDINATES
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|key| Coordinates::from_bytes(&key))
+        .collect();
+    Ok(ReservedCoordinatesResponse {
+        coordinates: coordinates?,
+    })
+}
+
+pub fn query_active_reservations(
+    deps: Deps,
+    env: Env,
+    start_after: Option<Coordinates>,
+    limit: Option<u32>,
+) -> StdResult<ActiveReservationsResponse> {
+    let limit = CONFIG.load(deps.storage)?.query_limit(limit);
+    let start = start_after.map(|coords| Bound::exclusive(coords.to_bytes()));
+
+    let mut reservations = vec![];
+    for item in RESERVED_COORDINATES.range(deps.storage, start, None, Order::Ascending) {
+        let (_, reservation) = item?;
+        if reservation.is_active(env.block.time) {
+            reservations.push(reservation);
+            if reservations.len() == limit {
+                break;
+            }
+        }
+    }
+    Ok(ActiveReservationsResponse { reservations })
+}
+
+pub fn query_wallet_limit_exemptions(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<WalletLimitExemptionsResponse> {
+    let limit = CONFIG.load(deps.storage)?.query_limit(limit);
+    let start = start_after.map(Bound::exclusive);
+
+    let addresses: StdResult<Vec<_>> = WALLET_LIMIT_EXEMPTIONS
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|key| Ok(String::from_utf8(key)?))
+        .collect();
+    Ok(WalletLimitExemptionsResponse {
+        addresses: addresses?,
+    })
+}
+
+pub fn query_captcha_public_key(deps: Deps, env: Env) -> StdResult<String> {
+    let keys = load_active_captcha_public_keys(deps.storage, &env.block)?;
+    keys.into_iter()
+        .last()
+        .map(|key| key.public_key)
+        .ok_or_else(|| StdError::not_found("captcha public key"))
+}
+
+pub fn query_captcha_public_keys(deps: Deps, env: Env) -> StdResult<CaptchaPublicKeysResponse> {
+    Ok(CaptchaPublicKeysResponse {
+        keys: load_active_captcha_public_keys(deps.storage, &env.block)?,
+    })
+}
+
+/// Fills in the token fields that follow the current config: its image and
+/// token URI.
+fn resolve_token(config: &Config, token: &mut XyzTokenInfo) {
+    token.image = config.token_image(&token.extension.coordinates);
+    token.extension.token_uri = config.token_uri(&token.name, token.extension.token_uri.as_ref());
 }
 
 pub fn query_xyz_nft_info(deps: Deps, token_id: String) -> StdResult<XyzTokenInfo> {
-    let token = tokens().load(deps.storage, &token_id)?;
+    let config = CONFIG.load(deps.storage)?;
+    let mut token = tokens().load(deps.storage, &token_id)?;
+    resolve_token(&config, &mut token);
     Ok(token)
 }
 
-pub fn query_xyz_nft_info_by_coords(deps: Deps, coords: Coordinates) -> StdResult<XyzTokenInfo> {
-    let token = tokens()
+/// The most tokens XyzNftInfoBatch can return at once
+pub const MAX_NFT_INFO_BATCH: usize = 100;
+
+pub fn query_xyz_nft_info_batch(
+    deps: Deps,
+    token_ids: Vec<String>,
+) -> StdResult<XyzNftInfoBatchResponse> {
+    if token_ids.len() > MAX_NFT_INFO_BATCH {
+        return Err(StdError::generic_err(format!(
+            "cannot query more than {} tokens at once",
+            MAX_NFT_INFO_BATCH
+        )));
+    }
+    let config = CONFIG.load(deps.storage)?;
+    let tokens = token_ids
+        .into_iter()
+        .map(|token_id| {
+            let info = tokens()
+                .may_load(deps.storage, &token_id)?
+                .map(|mut token| {
+                    resolve_token(&config, &mut token);
+                    token
+                });
+            Ok(XyzNftInfoBatchEntry { token_id, info })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(XyzNftInfoBatchResponse { tokens })
+}
+
+pub fn query_xyz_nft_info_by_coords(
+    deps: Deps,
+    env: Env,
+    coords: Coordinates,
+) -> StdResult<XyzNftInfoByCoordsResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    if let Some((_, mut token, role)) = load_claimant(deps, &config, &env, coords)? {
+        resolve_token(&config, &mut token);
+        return Ok(XyzNftInfoByCoordsResponse { token, role });
+    }
+
+    // otherwise, a token still moving away from the coordinates
+    let in_transit = tokens()
         .idx
-        .coordinates
-        .item(deps.storage, coords.to_bytes())?
-        .map(|(_, item)| item);
-    if let Some(token) = token {
-        Ok(token)
+        .prev_coordinates
+        .prefix(coords.to_bytes())
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, token)| token))
+        .find(|item| match item {
+            Ok(token) => !token.extension.has_arrived(env.block.time),
+            Err(_) => true,
+        })
+        .transpose()?;
+    match in_transit {
+        Some(mut token) => {
+            resolve_token(&config, &mut token);
+            Ok(XyzNftInfoByCoordsResponse {
+                token,
+                role: CoordinateRole::VacatedOrigin,
+            })
+        }
+        None => Err(StdError::not_found("xyz_token_info")),
+    }
+}
+
+pub fn query_xyz_nft_infos_by_coords(
+    deps: Deps,
+    coords: Coordinates,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<XyzTokensResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let limit = config.query_limit(limit);
+    let tokens = load_tokens_at(
+        deps.storage,
+        &COORDINATE_TOKENS,
+        &coords,
+        start_after.as_deref(),
+        limit,
+    )?
+    .into_iter()
+    .map(|token_id| {
+        let mut info = tokens().load(deps.storage, &token_id)?;
+        resolve_token(&config, &mut info);
+        Ok(XyzTokenEntry { token_id, info })
+    })
+    .collect::<StdResult<Vec<_>>>()?;
+    Ok(XyzTokensResponse { tokens })
+}
+
+/// Loads the token claiming coords, whether it's there or moving or queued
+/// to move there, along with the role coords play for it. Queued moves are
+/// only started when the token is next acted on, so it may have since
+/// departed, leaving coords as its vacated origin or freeing them entirely.
+fn load_claimant(
+    deps: Deps,
+    config: &Config,
+    env: &Env,
+    coords: Coordinates,
+) -> StdResult<Option<(String, XyzTokenInfo, CoordinateRole)>> {
+    let token_id = match load_token_at(deps.storage, &COORDINATE_TOKENS, &coords)? {
+        Some(token_id) => token_id,
+        None => match load_token_at(deps.storage, &QUEUED_COORDINATES, &coords)? {
+            Some(token_id) => token_id,
+            None => return Ok(None),
+        },
+    };
+    let mut token = tokens().load(deps.storage, &token_id)?;
+    let mut queue = MOVE_QUEUES
+        .may_load(deps.storage, &token_id)?
+        .unwrap_or_default();
+    apply_queued_moves(config, &mut token, &mut queue, env.block.time);
+    let arrived = token.extension.has_arrived(env.block.time);
+    let role = if token.extension.coordinates == coords {
+        if arrived {
+            CoordinateRole::Current
+        } else {
+            CoordinateRole::ReservedDestination
+        }
+    } else if queue
+        .iter()
+        .any(|queued_move| queued_move.coordinates == coords)
+    {
+        CoordinateRole::ReservedDestination
+    } else if token.extension.prev_coordinates == Some(coords) && !arrived {
+        CoordinateRole::VacatedOrigin
     } else {
-        Err(StdError::not_found("xyz_token_info"))
+        return Ok(None);
+    };
+    Ok(Some((token_id, token, role)))
+}
+
+/// The most coordinates CoordinatesStatus can check at once
+pub const MAX_COORDINATES_STATUS_BATCH: usize = 200;
+
+pub fn query_coordinates_status(
+    deps: Deps,
+    env: Env,
+    coordinates: Vec<Coordinates>,
+) -> StdResult<CoordinatesStatusResponse> {
+    if coordinates.len() > MAX_COORDINATES_STATUS_BATCH {
+        return Err(StdError::generic_err(format!(
+            "cannot check more than {} coordinates at once",
+            MAX_COORDINATES_STATUS_BATCH
+        )));
     }
+    let config = CONFIG.load(deps.storage)?;
+    let statuses = coordinates
+        .into_iter()
+        .map(|coords| {
+            let mut status = CoordinateStatus {
+                coordinates: coords,
+                in_bounds: config.check_bounds(coords).is_ok(),
+                occupied: false,
+                reserved_destination: false,
+                reserved: is_reserved(deps.storage, &coords, env.block.time)?,
+                token_id: None,
+            };
+            if !status.in_bounds {
+                return Ok(status);
+            }
+            match load_claimant(deps, &config, &env, coords)? {
+                Some((token_id, _, CoordinateRole::Current)) => {
+                    status.occupied = true;
+                    status.token_id = Some(token_id);
+                }
+                Some((token_id, _, CoordinateRole::ReservedDestination)) => {
+                    status.reserved_destination = true;
+                    status.token_id = Some(token_id);
+                }
+                Some((_, _, CoordinateRole::VacatedOrigin)) | None => {}
+            }
+            Ok(status)
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(CoordinatesStatusResponse { statuses })
 }
 
-const DEFAULT_LIMIT: u32 = 10;
-const MAX_LIMIT: u32 = 30;
+/// Returns the range bounds that list the keys after cursor in the given
+/// order, which are the keys before it when descending.
+fn cursor_bounds(
+    cursor: Option<Vec<u8>>,
+    order: SortOrder,
+) -> (Option<Bound>, Option<Bound>, Order) {
+    let cursor = cursor.map(Bound::exclusive);
+    match order {
+        SortOrder::Ascending => (cursor, None, Order::Ascending),
+        SortOrder::Descending => (None, cursor, Order::Descending),
+    }
+}
 
 pub fn query_xyz_tokens(
     deps: Deps,
     owner: String,
     start_after: Option<String>,
     limit: Option<u32>,
+    order: SortOrder,
 ) -> StdResult<XyzTokensResponse> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let start = start_after.map(Bound::exclusive);
+    let config = CONFIG.load(deps.storage)?;
+    let limit = config.query_limit(limit);
+    let (min, max, order) = cursor_bounds(start_after.map(String::into_bytes), order);
 
     let owner = deps.api.addr_validate(&owner)?;
     let tokens: StdResult<Vec<_>> = tokens()
         .idx
         .owner
         .prefix(owner)
-        .range(deps.storage, start, None, Order::Ascending)
+        .range(deps.storage, min, max, order)
         .take(limit)
-        .map(|item| item.map(|(_, token)| token))
+        .map(|item| {
+            let (token_id, mut info) = item?;
+            resolve_token(&config, &mut info);
+            Ok(XyzTokenEntry {
+                token_id: String::from_utf8(token_id)?,
+                info,
+            })
+        })
         .collect();
     Ok(XyzTokensResponse { tokens: tokens? })
 }
@@ -70,15 +431,64 @@ pub fn query_all_xyz_tokens(
     deps: Deps,
     start_after: Option<String>,
     limit: Option<u32>,
+    order_by: TokenOrder,
+    start_after_coords: Option<Coordinates>,
+    order: SortOrder,
 ) -> StdResult<XyzTokensResponse> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let start = start_after.map(Bound::exclusive);
+    let config = CONFIG.load(deps.storage)?;
+    let limit = config.query_limit(limit);
 
-    let tokens: StdResult<Vec<_>> = tokens()
-        .range(deps.storage, start, None, Order::Ascending)
-        .take(limit)
-        .map(|item| item.map(|(_, token)| token))
-        .collect();
+    let tokens: StdResult<Vec<_>> = match order_by {
+        TokenOrder::TokenId => {
+            if start_after_coords.is_some() {
+                return Err(StdError::generic_err(
+                    "start_after_coords requires ordering by coordinates",
+                ));
+            }
+            let (min, max, order) = cursor_bounds(start_after.map(String::into_bytes), order);
+            tokens()
+                .range(deps.storage, min, max, order)
+                .take(limit)
+                .map(|item| {
+                    let (token_id, mut info) = item?;
+                    resolve_token(&config, &mut info);
+                    Ok(XyzTokenEntry {
+                        token_id: String::from_utf8(token_id)?,
+                        info,
+                    })
+                })
+                .collect()
+        }
+        // COORDINATE_TOKENS is keyed by order-preserving coordinate bytes
+        // followed by the token id, so start_after breaks ties between tokens
+        // sharing start_after_coords
+        TokenOrder::Coordinates => {
+            let cursor = match (start_after_coords, start_after) {
+                (Some(coords), Some(token_id)) => Some(coordinate_key(&coords, &token_id)),
+                (Some(coords), None) => Some(match order {
+                    SortOrder::Ascending => coordinates_end_key(&coords),
+                    SortOrder::Descending => coords.to_bytes(),
+                }),
+                (None, None) => None,
+                (None, Some(_)) => {
+                    return Err(StdError::generic_err(
+                        "start_after requires ordering by token id",
+                    ));
+                }
+            };
+            let (min, max, order) = cursor_bounds(cursor, order);
+            COORDINATE_TOKENS
+                .range(deps.storage, min, max, order)
+                .take(limit)
+                .map(|item| {
+                    let (_, token_id) = item?;
+                    let mut info = tokens().load(deps.storage, &token_id)?;
+                    resolve_token(&config, &mut info);
+                    Ok(XyzTokenEntry { token_id, info })
+                })
+                .collect()
+        }
+    };
     Ok(XyzTokensResponse { tokens: tokens? })
 }
 
@@ -93,7 +503,83 @@ pub fn query_num_tokens_for_owner(deps: Deps, owner: String) -> StdResult<NumTok
     Ok(NumTokensResponse { count })
 }
 
+pub fn query_owner_token_summary(
+    deps: Deps,
+    env: Env,
+    owner: String,
+) -> StdResult<OwnerTokenSummaryResponse> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let mut summary = OwnerTokenSummaryResponse {
+        is_exempt: WALLET_LIMIT_EXEMPTIONS.has(deps.storage, &owner),
+        ..Default::default()
+    };
+    for item in tokens()
+        .idx
+        .owner
+        .prefix(owner)
+        .range(deps.storage, None, None, Order::Ascending)
+    {
+        let (token_id, token) = item?;
+        // a token with queued moves arrives when its last queued move does
+        let arrival = MOVE_QUEUES
+            .may_load(deps.storage, &String::from_utf8(token_id)?)?
+            .and_then(|queue| queue.last().map(|queued_move| queued_move.arrival))
+            .unwrap_or(token.extension.arrival);
+        summary.total += 1;
+        if arrival > env.block.time {
+            summary.in_transit += 1;
+        } else {
+            summary.arrived += 1;
+        }
+    }
+    Ok(summary)
+}
+
 pub fn query_move_params(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+    coordinates: Coordinates,
+) -> StdResult<MoveParamsResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let token = tokens().load(deps.storage, &token_id)?;
+
+    config.check_bounds(coordinates)?;
+
+    let held_nanos = load_held_nanos(deps.storage, &token_id, env.block.time)?;
+    let start = token.extension.coordinates;
+    Ok(MoveParamsResponse {
+        fee: config.get_discounted_move_fee(start, coordinates, held_nanos)?,
+        duration_nanos: config.get_move_nanos(start, coordinates)?,
+        cooldown_until: load_cooldown_until(
+            deps.storage,
+            &config,
+            &token_id,
+            &token,
+            env.block.time,
+        )?,
+        max_move_distance: config.max_move_distance,
+        config_version: load_config_version(deps.storage)?.version,
+    })
+}
+
+pub fn query_move_many_params(
+    deps: Deps,
+    env: Env,
+    moves: Vec<(String, Coordinates)>,
+) -> StdResult<MoveManyParamsResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut fee = Coin::new(0, &config.base_move_fee.denom);
+    let mut params = vec![];
+    for (token_id, coordinates) in moves {
+        let leg = query_move_params(deps, env.clone(), token_id, coordinates)?;
+        fee.amount = fee.amount.checked_add(leg.fee.amount)?;
+        params.push(leg);
+    }
+    Ok(MoveManyParamsResponse { fee, moves: params })
+}
+
+pub fn query_teleport_params(
     deps: Deps,
     token_id: String,
     coordinates: Coordinates,
@@ -101,20 +587,1009 @@ pub fn query_move_params(
     let config = CONFIG.load(deps.storage)?;
     let token = tokens().load(deps.storage, &token_id)?;
 
+    if !config.teleport_enabled {
+        return Err(StdError::generic_err("teleporting is disabled"));
+    }
     config.check_bounds(coordinates)?;
 
-    let fee = config.get_move_fee(token.extension.coordinates, coordinates);
-    let duration_nanos = config.get_move_nanos(token.extension.coordinates, coordinates);
     Ok(MoveParamsResponse {
+        fee: config.get_teleport_fee(token.extension.coordinates, coordinates)?,
+        duration_nanos: 0,
+        cooldown_until: None,
+        max_move_distance: config.max_move_distance,
+        config_version: load_config_version(deps.storage)?.version,
+    })
+}
+
+pub fn query_move_quote(
+    deps: Deps,
+    start: Coordinates,
+    end: Coordinates,
+) -> StdResult<MoveParamsResponse> {
+    let config = CONFIG.load(deps.storage)?;
+
+    config.check_bounds(start)?;
+    config.check_bounds(end)?;
+
+    move_params(
+        &config,
+        load_config_version(deps.storage)?.version,
+        start,
+        end,
+    )
+}
+
+/// Returns the fee and duration of a move from start to end, computed the
+/// same way as when the move is made.
+fn move_params(
+    config: &Config,
+    config_version: u64,
+    start: Coordinates,
+    end: Coordinates,
+) -> StdResult<MoveParamsResponse> {
+    Ok(MoveParamsResponse {
+        fee: config.get_move_fee(start, end)?,
+        duration_nanos: config.get_move_nanos(start, end)?,
+        cooldown_until: None,
+        max_move_distance: config.max_move_distance,
+        config_version,
+    })
+}
+
+pub fn query_token_energy(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+) -> StdResult<TokenEnergyResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let token = tokens().load(deps.storage, &token_id)?;
+
+    Ok(TokenEnergyResponse {
+        energy: token.extension.current_energy(&config, env.block.time),
+        energy_max: config.energy_max,
+    })
+}
+
+pub fn query_token_stats(deps: Deps, env: Env, token_id: String) -> StdResult<TokenStatsResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let token = load_current_token(deps, &config, &env, &token_id)?;
+    Ok(TokenStatsResponse {
+        moves_count: token.extension.moves_count,
+        total_distance: token.extension.total_distance,
+    })
+}
+
+pub fn query_move_history(
+    deps: Deps,
+    token_id: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<MoveHistoryResponse> {
+    let limit = CONFIG.load(deps.storage)?.query_limit(limit);
+    let start = start_after.map(Bound::exclusive_int);
+    let entries = MOVE_HISTORY
+        .prefix(&token_id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, record) = item?;
+            Ok(MoveHistoryEntry {
+                sequence: parse_u64_key(&key)?,
+                record,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(MoveHistoryResponse { entries })
+}
+
+pub fn query_ownership_distribution(deps: Deps) -> StdResult<OwnershipDistributionResponse> {
+    let mut distribution = OwnershipDistributionResponse::default();
+    for item in OWNER_TOKEN_COUNTS.range(deps.storage, None, None, Order::Ascending) {
+        let (_, count) = item?;
+        match count {
+            0 => continue,
+            1 => distribution.one += 1,
+            2..=5 => distribution.two_to_five += 1,
+            6..=10 => distribution.six_to_ten += 1,
+            _ => distribution.eleven_or_more += 1,
+        }
+        distribution.unique_holders += 1;
+    }
+    Ok(distribution)
+}
+
+pub fn query_swap_proposal(deps: Deps, proposal_id: u64) -> StdResult<SwapProposal> {
+    SWAP_PROPOSALS.load(deps.storage, U64Key::new(proposal_id))
+}
+
+pub fn query_swap_params(
+    deps: Deps,
+    env: Env,
+    token_id_a: String,
+    token_id_b: String,
+) -> StdResult<SwapParamsResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let a = load_current_token(deps, &config, &env, &token_id_a)?;
+    let b = load_current_token(deps, &config, &env, &token_id_b)?;
+
+    let mut fee = Coin::new(0, &config.base_move_fee.denom);
+    for (token_id, token, target) in [(&token_id_a, &a, &b), (&token_id_b, &b, &a)] {
+        let leg = sender_move_fee(
+            deps.storage,
+            &env,
+            &config,
+            &token.owner,
+            token_id,
+            token.extension.coordinates,
+            target.extension.coordinates,
+        )?;
+        fee.amount = fee.amount.checked_add(leg.amount)?;
+    }
+
+    let reason = check_not_paused(deps.storage, PausableAction::Move)
+        .and_then(|_| {
+            check_swap_tokens(
+                &config,
+                env.block.time,
+                (&token_id_a, &a),
+                (&token_id_b, &b),
+            )
+        })
+        .and_then(|_| {
+            check_swap_legs(
+                deps.storage,
+                &config,
+                env.block.time,
+                [(&token_id_a, &a), (&token_id_b, &b)],
+            )
+        })
+        .err()
+        .map(|err| err.to_string());
+    Ok(SwapParamsResponse {
+        fee,
+        allowed: reason.is_none(),
+        reason,
+    })
+}
+
+pub fn query_pilot(deps: Deps, env: Env, token_id: String) -> StdResult<PilotResponse> {
+    let grant = PILOTS
+        .may_load(deps.storage, &token_id)?
+        .filter(|grant| !grant.expires.is_expired(&env.block));
+    Ok(PilotResponse { grant })
+}
+
+pub fn query_listing(deps: Deps, token_id: String) -> StdResult<Listing> {
+    LISTINGS.load(deps.storage, &token_id)
+}
+
+pub fn query_listings(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ListingsResponse> {
+    let limit = CONFIG.load(deps.storage)?.query_limit(limit);
+    let start = start_after.map(Bound::exclusive);
+
+    let listings: StdResult<Vec<_>> = LISTINGS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, listing)| listing))
+        .collect();
+    Ok(ListingsResponse {
+        listings: listings?,
+    })
+}
+
+pub fn query_offers_by_token(
+    deps: Deps,
+    token_id: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<OffersResponse> {
+    let limit = CONFIG.load(deps.storage)?.query_limit(limit);
+    let start_addr = start_after
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let start = start_addr.map(|addr| Bound::exclusive(addr.as_ref()));
+
+    let offers: StdResult<Vec<_>> = OFFERS
+        .prefix(&token_id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, offer)| offer))
+        .collect();
+    Ok(OffersResponse { offers: offers? })
+}
+
+pub fn query_offers_by_bidder(
+    deps: Deps,
+    bidder: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<OffersResponse> {
+    let limit = CONFIG.load(deps.storage)?.query_limit(limit);
+    let start = start_after.map(Bound::exclusive);
+
+    let bidder = deps.api.addr_validate(&bidder)?;
+    let offers: StdResult<Vec<_>> = BIDDER_OFFERS
+        .prefix(&bidder)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|token_id| OFFERS.load(deps.storage, (&String::from_utf8(token_id)?, &bidder)))
+        .collect();
+    Ok(OffersResponse { offers: offers? })
+}
+
+pub fn query_owner_leaderboard(
+    deps: Deps,
+    start_after: Option<(u64, String)>,
+    limit: Option<u32>,
+) -> StdResult<OwnerLeaderboardResponse> {
+    let limit = CONFIG.load(deps.storage)?.query_limit(limit);
+    let end = start_after.map(|(count, owner)| {
+        Bound::exclusive(owner_leaderboard_key(count, &Addr::unchecked(owner)))
+    });
+
+    let entries: StdResult<Vec<_>> = OWNER_LEADERBOARD
+        .keys(deps.storage, None, end, Order::Descending)
+        .take(limit)
+        .map(|key| {
+            let (count, owner) = parse_u64_addr_key(&key)?;
+            Ok(OwnerLeaderboardEntry {
+                owner: owner.into(),
+                count,
+            })
+        })
+        .collect();
+    Ok(OwnerLeaderboardResponse { entries: entries? })
+}
+
+const MAX_COLLISION_CHECK_STEPS: usize = 10_000;
+
+pub fn query_collision_check(
+    deps: Deps,
+    env: Env,
+    token_id_a: String,
+    token_id_b: String,
+) -> StdResult<CollisionCheckResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let a = tokens().load(deps.storage, &token_id_a)?.extension;
+    let b = tokens().load(deps.storage, &token_id_b)?.extension;
+
+    // positions only change when one of the tokens takes a step, so it's
+    // enough to compare them now and after every subsequent step
+    let mut time = env.block.time;
+    for _ in 0..MAX_COLLISION_CHECK_STEPS {
+        let position = a.position_at(&config, time);
+        if position == b.position_at(&config, time) {
+            return Ok(CollisionCheckResponse {
+                collides: true,
+                coordinates: Some(position),
+                time: Some(time),
+            });
+        }
+        time = match (a.next_step_at(&config, time), b.next_step_at(&config, time)) {
+            (Some(a_next), Some(b_next)) => a_next.min(b_next),
+            (Some(next), None) | (None, Some(next)) => next,
+            (None, None) => {
+                return Ok(CollisionCheckResponse {
+                    collides: false,
+                    coordinates: None,
+                    time: None,
+                })
+            }
+        };
+    }
+    Err(StdError::generic_err(format!(
+        "cannot check collisions over more than {} steps",
+        MAX_COLLISION_CHECK_STEPS
+    )))
+}
+
+pub fn query_signable_payload(
+    deps: Deps,
+    sender: String,
+    coordinates: Option<Coordinates>,
+    nonce: u64,
+    expiry: Timestamp,
+) -> StdResult<Binary> {
+    let sender = deps.api.addr_validate(&sender)?;
+    Ok(Binary::from(match coordinates {
+        Some(coordinates) => captcha_payload(&sender, &coordinates, nonce, expiry),
+        None => random_mint_captcha_payload(&sender, nonce, expiry),
+    }))
+}
+
+pub fn query_verify_captcha(
+    deps: Deps,
+    env: Env,
+    minter: String,
+    mint: MintRequest,
+) -> StdResult<VerifyCaptchaResponse> {
+    let minter = deps.api.addr_validate(&minter)?;
+    let config = CONFIG.load(deps.storage)?;
+    let reason = check_captcha_signature(deps.storage, deps.api, &env, &config, &minter, &mint)
+        .err()
+        .map(|err| err.to_string());
+    Ok(VerifyCaptchaResponse {
+        valid: reason.is_none(),
+        reason,
+    })
+}
+
+pub fn query_move_queue(deps: Deps, env: Env, token_id: String) -> StdResult<MoveQueueResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut token = tokens().load(deps.storage, &token_id)?;
+    let mut moves = MOVE_QUEUES
+        .may_load(deps.storage, &token_id)?
+        .unwrap_or_default();
+
+    // queued moves are only started when the token is next acted on, so
+    // leave out any that have started since
+    apply_queued_moves(&config, &mut token, &mut moves, env.block.time);
+    Ok(MoveQueueResponse { moves })
+}
+
+pub fn query_tokens_in_transit(
+    deps: Deps,
+    env: Env,
+    start_after: Option<(Timestamp, String)>,
+    limit: Option<u32>,
+) -> StdResult<TokensInTransitResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let limit = config.query_limit(limit);
+    let is_after_start = |token: &TokenInTransit| match &start_after {
+        Some((arrival, token_id)) => (token.arrival, &token.token_id) > (*arrival, token_id),
+        None => true,
+    };
+
+    // tokens on their latest move, as indexed by arrival time
+    let token_map = tokens();
+    let arrival_index = &token_map.idx.arrival;
+    let start = match &start_after {
+        Some((arrival, token_id)) if *arrival > env.block.time => Bound::exclusive(
+            arrival_index.index_key((arrival_key(*arrival), token_id.as_bytes().to_vec())),
+        ),
+        _ => Bound::inclusive(
+            arrival_index.index_key((arrival_key(env.block.time.plus_nanos(1)), vec![])),
+        ),
+    };
+    let mut in_transit = arrival_index
+        .range(deps.storage, Some(start), None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (token_id, token) = item?;
+            Ok(token_in_transit(String::from_utf8(token_id)?, token))
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    // queued moves are only started when the token is next acted on, so
+    // tokens that have arrived may be partway through a queued move
+    for item in MOVE_QUEUES.range(deps.storage, None, None, Order::Ascending) {
+        let (token_id, mut queue) = item?;
+        let token_id = String::from_utf8(token_id)?;
+        let mut token = tokens().load(deps.storage, &token_id)?;
+        if !token.extension.has_arrived(env.block.time) {
+            continue;
+        }
+        apply_queued_moves(&config, &mut token, &mut queue, env.block.time);
+        let token = token_in_transit(token_id, token);
+        if token.arrival > env.block.time && is_after_start(&token) {
+            in_transit.push(token);
+        }
+    }
+
+    in_transit.sort_by(|a, b| (a.arrival, &a.token_id).cmp(&(b.arrival, &b.token_id)));
+    in_transit.truncate(limit);
+    Ok(TokensInTransitResponse { tokens: in_transit })
+}
+
+fn token_in_transit(token_id: String, token: XyzTokenInfo) -> TokenInTransit {
+    TokenInTransit {
+        token_id,
+        owner: token.owner,
+        coordinates: token.extension.coordinates,
+        prev_coordinates: token.extension.prev_coordinates,
+        arrival: token.extension.arrival,
+    }
+}
+
+pub fn query_public_mints_remaining(
+    deps: Deps,
+    env: Env,
+) -> StdResult<PublicMintsRemainingResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let block_mints = load_public_mints_in_block(deps.storage, env.block.height)?;
+    Ok(PublicMintsRemainingResponse {
+        remaining: config
+            .max_public_mints_per_block
+            .map(|max_mints| max_mints.saturating_sub(block_mints)),
+    })
+}
+
+const MAX_OCCUPANCY_BITMAP_VOLUME: u64 = 65_536;
+
+pub fn query_occupancy_bitmap(deps: Deps, min: Coordinates, max: Coordinates) -> StdResult<Binary> {
+    if min.x > max.x || min.y > max.y || min.z > max.z {
+        return Err(StdError::generic_err("min must not exceed max on any axis"));
+    }
+    // the side lengths can overflow i64 on wide boxes, so they're taken in
+    // i128 and only narrowed once the volume is known to be within the cap
+    let len_along = |min: i64, max: i64| max as i128 - min as i128 + 1;
+    let volume = [
+        len_along(min.x, max.x),
+        len_along(min.y, max.y),
+        len_along(min.z, max.z),
+    ]
+    .iter()
+    .try_fold(1i128, |total, &len| total.checked_mul(len))
+    .filter(|&volume| volume <= MAX_OCCUPANCY_BITMAP_VOLUME as i128)
+    .ok_or_else(|| {
+        StdError::generic_err(format!(
+            "cannot return occupancy for more than {} cells",
+            MAX_OCCUPANCY_BITMAP_VOLUME
+        ))
+    })? as u64;
+    let len_y = len_along(min.y, max.y) as u64;
+    let len_z = len_along(min.z, max.z) as u64;
+
+    // COORDINATE_TOKENS is ordered by coordinates, so only the keys between
+    // the box's corners are scanned; keys within that span but outside the
+    // box on the y or z axis are skipped
+    let mut bitmap = vec![0u8; ((volume + 7) / 8) as usize];
+    for key in COORDINATE_TOKENS.keys(
+        deps.storage,
+        Some(Bound::inclusive(min.to_bytes())),
+        Some(Bound::exclusive(coordinates_end_key(&max))),
+        Order::Ascending,
+    ) {
+        let c = coordinates_of_key(&key)?;
+        if !in_box(c, min, max) {
+            continue;
+        }
+        let index = (((c.x - min.x) as u64 * len_y + (c.y - min.y) as u64) * len_z
+            + (c.z - min.z) as u64) as usize;
+        bitmap[index / 8] |= 1 << (index % 8);
+    }
+    Ok(Binary::from(bitmap))
+}
+
+fn in_box(c: Coordinates, min: Coordinates, max: Coordinates) -> bool {
+    (min.x..=max.x).contains(&c.x)
+        && (min.y..=max.y).contains(&c.y)
+        && (min.z..=max.z).contains(&c.z)
+}
+
+/// Returns the coordinates a token is matched at by coordinate range
+/// queries, or None if it isn't inside the box or fails the filter.
+fn range_match(
+    token: &XyzTokenInfo,
+    time: Timestamp,
+    transit_match: TransitMatch,
+    min: Coordinates,
+    max: Coordinates,
+    filter: &dyn Fn(Coordinates) -> bool,
+) -> Option<Coordinates> {
+    let destination = token.extension.coordinates;
+    let origin = token
+        .extension
+        .prev_coordinates
+        .filter(|_| !token.extension.has_arrived(time));
+    let candidates = match (origin, transit_match) {
+        (None, _) | (Some(_), TransitMatch::Destination) => vec![destination],
+        (Some(origin), TransitMatch::Origin) => vec![origin],
+        (Some(origin), TransitMatch::Both) => vec![destination, origin],
+    };
+    candidates
+        .into_iter()
+        .find(|c| in_box(*c, min, max) && filter(*c))
+}
+
+type RangeItem = (Vec<u8>, Vec<u8>, XyzTokenInfo);
+
+/// Lists the tokens matched inside the box bounded by min and max, along
+/// with the coordinates and id each was matched at (see
+/// QueryMsg::XyzTokensInRange).
+/// Only matches that pass the filter are returned.
+#[allow(clippy::too_many_arguments)]
+fn range_matches(
+    deps: Deps,
+    time: Timestamp,
+    min: Coordinates,
+    max: Coordinates,
+    transit_match: TransitMatch,
+    start_after: Option<String>,
+    limit: usize,
+    filter: &dyn Fn(Coordinates) -> bool,
+) -> StdResult<Vec<(Coordinates, String, XyzTokenInfo)>> {
+    // matches are ordered by (matched coordinate bytes, token id bytes), and
+    // the scans resume from start_after's position in that order
+    let cursor = match start_after {
+        Some(token_id) => {
+            let token = tokens().load(deps.storage, &token_id)?;
+            let coordinates = range_match(&token, time, transit_match, min, max, filter)
+                .ok_or_else(|| StdError::generic_err("start_after token is not in range"))?;
+            Some((coordinates.to_bytes(), token_id.into_bytes()))
+        }
+        None => None,
+    };
+    let start_bytes = cursor
+        .as_ref()
+        .map(|(bytes, _)| bytes.clone())
+        .unwrap_or_else(|| min.to_bytes());
+    let max_bytes = max.to_bytes();
+
+    // both indexes are ordered by coordinates, so only the keys between the
+    // box's corners are scanned; keys within that span but outside the box
+    // on the y or z axis are skipped
+    let keep = |item: &RangeItem| {
+        let (bytes, pk, token) = item;
+        let matched = range_match(token, time, transit_match, min, max, filter);
+        matched.map(|c| c.to_bytes()).as_ref() == Some(bytes)
+            && cursor
+                .as_ref()
+                .map_or(true, |cursor| (bytes, pk) > (&cursor.0, &cursor.1))
+    };
+    let collect_matches = |items: Box<dyn Iterator<Item = StdResult<RangeItem>> + '_>| {
+        let mut matches = vec![];
+        for item in items {
+            let item = item?;
+            if item.0 > max_bytes {
+                break;
+            }
+            if keep(&item) {
+                matches.push(item);
+                if matches.len() == limit {
+                    break;
+                }
+            }
+        }
+        StdResult::Ok(matches)
+    };
+
+    let destinations = COORDINATE_TOKENS
+        .range(
+            deps.storage,
+            Some(Bound::inclusive(start_bytes.clone())),
+            None,
+            Order::Ascending,
+        )
+        .map(|item| {
+            let (mut bytes, token_id) = item?;
+            bytes.truncate(24);
+            let token = tokens().load(deps.storage, &token_id)?;
+            Ok((bytes, token_id.into_bytes(), token))
+        });
+    let mut matches = collect_matches(Box::new(destinations))?;
+
+    if transit_match != TransitMatch::Destination {
+        let token_map = tokens();
+        let origin_index = &token_map.idx.prev_coordinates;
+        let origins = origin_index
+            .range(
+                deps.storage,
+                Some(Bound::inclusive(
+                    origin_index.index_key((start_bytes, vec![])),
+                )),
+                None,
+                Order::Ascending,
+            )
+            .map(|item| {
+                item.map(|(pk, token)| {
+                    let prev = token.extension.prev_coordinates.map(|c| c.to_bytes());
+                    (prev.unwrap_or_default(), pk, token)
+                })
+            });
+        matches.extend(collect_matches(Box::new(origins))?);
+        matches.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        matches.truncate(limit);
+    }
+
+    matches
+        .into_iter()
+        .map(|(bytes, pk, token)| {
+            Ok((
+                Coordinates::from_bytes(&bytes)?,
+                String::from_utf8(pk)?,
+                token,
+            ))
+        })
+        .collect()
+}
+
+pub fn query_xyz_tokens_in_range(
+    deps: Deps,
+    env: Env,
+    min: Coordinates,
+    max: Coordinates,
+    transit_match: Option<TransitMatch>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<XyzTokensResponse> {
+    if min.x > max.x || min.y > max.y || min.z > max.z {
+        return Err(StdError::generic_err("min must not exceed max on any axis"));
+    }
+    let limit = CONFIG.load(deps.storage)?.query_limit(limit);
+
+    let matches = range_matches(
+        deps,
+        env.block.time,
+        min,
+        max,
+        transit_match.unwrap_or_default(),
+        start_after,
+        limit,
+        &|_| true,
+    )?;
+    Ok(XyzTokensResponse {
+        tokens: matches
+            .into_iter()
+            .map(|(_, token_id, info)| XyzTokenEntry { token_id, info })
+            .collect(),
+    })
+}
+
+/// The largest max_distance an XyzTokensNearby query scans, to bound the
+/// size of the box it searches
+pub const MAX_NEARBY_DISTANCE: u64 = 1_000;
+
+pub fn query_xyz_tokens_nearby(
+    deps: Deps,
+    env: Env,
+    coordinates: Coordinates,
+    max_distance: u64,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<XyzTokensNearbyResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let limit = config.query_limit(limit);
+    let max_distance = max_distance.min(MAX_NEARBY_DISTANCE);
+
+    // every metric is at least the Chebyshev distance, so every point within
+    // max_distance lies in the cube of that radius
+    let radius = max_distance as i64;
+    let min = Coordinates {
+        x: coordinates.x.saturating_sub(radius),
+        y: coordinates.y.saturating_sub(radius),
+        z: coordinates.z.saturating_sub(radius),
+    };
+    let max = Coordinates {
+        x: coordinates.x.saturating_add(radius),
+        y: coordinates.y.saturating_add(radius),
+        z: coordinates.z.saturating_add(radius),
+    };
+    let matches = range_matches(
+        deps,
+        env.block.time,
+        min,
+        max,
+        TransitMatch::Destination,
+        start_after,
+        limit,
+        &|c| config.distance(coordinates, c) <= max_distance,
+    )?;
+    Ok(XyzTokensNearbyResponse {
+        tokens: matches
+            .into_iter()
+            .map(|(c, _, token)| NearbyToken {
+                distance: config.distance(coordinates, c),
+                token,
+            })
+            .collect(),
+    })
+}
+
+/// The most buckets a Density query can span
+pub const MAX_DENSITY_BUCKETS: u64 = 1_000;
+
+pub fn query_density(
+    deps: Deps,
+    min: Coordinates,
+    max: Coordinates,
+    bucket_size: u64,
+) -> StdResult<DensityResponse> {
+    if min.x > max.x || min.y > max.y || min.z > max.z {
+        return Err(StdError::generic_err("min must not exceed max on any axis"));
+    }
+    if bucket_size == 0 {
+        return Err(StdError::generic_err("bucket_size must be positive"));
+    }
+
+    // the number of buckets along an axis, rounding up for a partial bucket
+    let size = bucket_size as i128;
+    let buckets_along = |min: i64, max: i64| (max as i128 - min as i128) / size + 1;
+    let buckets = [
+        buckets_along(min.x, max.x),
+        buckets_along(min.y, max.y),
+        buckets_along(min.z, max.z),
+    ]
+    .iter()
+    .try_fold(1i128, |total, &n| total.checked_mul(n))
+    .filter(|&total| total <= MAX_DENSITY_BUCKETS as i128);
+    if buckets.is_none() {
+        return Err(StdError::generic_err(format!(
+            "box must span at most {} buckets",
+            MAX_DENSITY_BUCKETS
+        )));
+    }
+
+    // COORDINATE_TOKENS is ordered by coordinates, so only the keys between
+    // the box's corners are scanned; keys within that span but outside the
+    // box on the y or z axis are skipped
+    let bucket_origin =
+        |min: i64, c: i64| (min as i128 + (c as i128 - min as i128) / size * size) as i64;
+    let mut counts: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for key in COORDINATE_TOKENS.keys(
+        deps.storage,
+        Some(Bound::inclusive(min.to_bytes())),
+        Some(Bound::exclusive(coordinates_end_key(&max))),
+        Order::Ascending,
+    ) {
+        let c = coordinates_of_key(&key)?;
+        if c.y < min.y || c.y > max.y || c.z < min.z || c.z > max.z {
+            continue;
+        }
+        let origin = Coordinates {
+            x: bucket_origin(min.x, c.x),
+            y: bucket_origin(min.y, c.y),
+            z: bucket_origin(min.z, c.z),
+        };
+        *counts.entry(origin.to_bytes()).or_default() += 1;
+    }
+
+    let buckets = counts
+        .into_iter()
+        .map(|(origin, count)| {
+            Ok(DensityBucket {
+                origin: Coordinates::from_bytes(&origin)?,
+                count,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(DensityResponse { buckets })
+}
+
+pub fn query_supply(deps: Deps) -> StdResult<SupplyResponse> {
+    Ok(SupplyResponse {
+        minted: load_tokens_minted(deps.storage)?,
+        live: Cw721Contract::<XyzExtension, Empty>::default().token_count(deps.storage)?,
+        token_supply: CONFIG.load(deps.storage)?.token_supply,
+    })
+}
+
+pub fn query_current_mint_fee(deps: Deps) -> StdResult<CurrentMintFeeResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let minted = load_tokens_minted(deps.storage)?;
+    Ok(CurrentMintFeeResponse {
+        fee: config.mint_fee_at(minted).clone(),
+        remaining_in_tier: config.mints_left_in_tier(minted),
+    })
+}
+
+pub fn query_mint_prices(deps: Deps) -> StdResult<MintPricesResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let minted = load_tokens_minted(deps.storage)?;
+    Ok(MintPricesResponse {
+        prices: config.mint_prices_for(minted, 1)?,
+    })
+}
+
+pub fn query_mint_price(
+    deps: Deps,
+    coordinates: Coordinates,
+    minter: Option<String>,
+) -> StdResult<MintPricesResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    config.check_bounds(coordinates)?;
+    let minted = load_tokens_minted(deps.storage)?;
+    let mints = match minter {
+        Some(minter) => {
+            let minter = deps.api.addr_validate(&minter)?;
+            priced_mints(deps.storage, &config, &minter, &[coordinates])?
+        }
+        None => vec![(coordinates, false)],
+    };
+    Ok(MintPricesResponse {
+        prices: config.mint_prices_at(minted, &mints)?,
+    })
+}
+
+pub fn query_simulate_mint(
+    deps: Deps,
+    env: Env,
+    minter: String,
+    coordinates: Coordinates,
+) -> StdResult<SimulateMintResponse> {
+    let owner = OWNER.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+    let minter = deps.api.addr_validate(&minter)?;
+    let minted = load_tokens_minted(deps.storage)?;
+
+    let public_mint = minter != owner;
+    let fee = if public_mint {
+        let mints = priced_mints(deps.storage, &config, &minter, &[coordinates])?;
+        config.mint_prices_at(minted, &mints)?.remove(0)
+    } else {
+        Coin::new(0, &config.mint_fee.denom)
+    };
+
+    let checks = check_not_paused(deps.storage, PausableAction::Mint)
+        .and_then(|_| match public_mint {
+            true => check_public_mint_open(deps.storage, &env, &config, &minter, 1),
+            false => Ok(()),
+        })
+        .and_then(|_| {
+            check_mint_targets(
+                deps.storage,
+                &config,
+                public_mint,
+                &minter,
+                &[coordinates],
+                env.block.time,
+            )
+        });
+    let failure_reason = match checks {
+        Ok(()) => None,
+        Err(err) => Some(mint_failure_reason(err)?),
+    };
+
+    Ok(SimulateMintResponse {
+        success: failure_reason.is_none(),
         fee,
-        duration_nanos,
+        remaining_supply: config.token_supply.saturating_sub(minted),
+        failure_reason,
+    })
+}
+
+/// Returns the reason for a mint check's error. The only generic error the
+/// checks raise is for coordinates out of bounds, so any other error is
+/// passed on.
+fn mint_failure_reason(err: ContractError) -> StdResult<MintFailureReason> {
+    match err {
+        ContractError::Paused {} => Ok(MintFailureReason::Paused),
+        ContractError::Unauthorized {} => Ok(MintFailureReason::PublicMintingDisabled),
+        ContractError::MintNotStarted { .. } | ContractError::MintEnded { .. } => {
+            Ok(MintFailureReason::OutsideMintWindow)
+        }
+        ContractError::BlockMintLimit {} => Ok(MintFailureReason::BlockMintLimit),
+        ContractError::MintCooldown { .. } => Ok(MintFailureReason::MintCooldown),
+        ContractError::SupplyExhausted {} => Ok(MintFailureReason::SupplyExhausted),
+        ContractError::WalletLimit {} => Ok(MintFailureReason::WalletLimit),
+        ContractError::Claimed {} => Ok(MintFailureReason::Claimed),
+        ContractError::Reserved {} => Ok(MintFailureReason::Reserved),
+        ContractError::Std(StdError::GenericErr { .. }) => Ok(MintFailureReason::OutOfBounds),
+        ContractError::Std(err) => Err(err),
+        err => Err(StdError::generic_err(err.to_string())),
+    }
+}
+
+pub fn query_allowlist_merkle_root(deps: Deps) -> StdResult<AllowlistMerkleRootResponse> {
+    Ok(AllowlistMerkleRootResponse {
+        merkle_root: ALLOWLIST_MERKLE_ROOT.may_load(deps.storage)?,
+    })
+}
+
+pub fn query_allowlist_eligibility(
+    deps: Deps,
+    address: String,
+    proof: Vec<String>,
+) -> StdResult<AllowlistEligibilityResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let address = deps.api.addr_validate(&address)?;
+
+    let minted = ALLOWLIST_MINTS
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    let remaining = config.allowlist_mint_quota.saturating_sub(minted);
+    let on_allowlist = match ALLOWLIST_MERKLE_ROOT.may_load(deps.storage)? {
+        Some(merkle_root) => verify_allowlist_proof(&merkle_root, address.as_str(), &proof)?,
+        None => false,
+    };
+    Ok(AllowlistEligibilityResponse {
+        eligible: config.allowlist_mint_enabled && on_allowlist && remaining > 0,
+        remaining,
+    })
+}
+
+pub fn query_royalty_info(
+    deps: Deps,
+    token_id: String,
+    sale_price: Uint128,
+) -> StdResult<RoyaltiesInfoResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    tokens().load(deps.storage, &token_id)?;
+
+    let address = match config.royalty_payment_address.clone() {
+        Some(address) => address,
+        None => OWNER.load(deps.storage)?,
+    };
+    Ok(RoyaltiesInfoResponse {
+        address,
+        royalty_amount: config.royalty_amount(sale_price),
+    })
+}
+
+pub fn query_check_royalties(deps: Deps) -> StdResult<CheckRoyaltiesResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(CheckRoyaltiesResponse {
+        royalty_payments: config.royalty_bps > 0,
+    })
+}
+
+pub fn query_withdrawable_balance(deps: Deps, env: Env) -> StdResult<WithdrawableBalanceResponse> {
+    Ok(WithdrawableBalanceResponse {
+        balances: withdrawable_balances(deps, &env)?,
+    })
+}
+
+pub fn query_fee_balances(deps: Deps, env: Env) -> StdResult<FeeBalancesResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let owner = OWNER.load(deps.storage)?;
+    let recipients = if config.fee_split.is_empty() {
+        vec![(owner.clone(), 10_000)]
+    } else {
+        config.fee_split
+    };
+    let balances = withdrawable_balances(deps, &env)?;
+    let owner_share = balances
+        .iter()
+        .map(|coin| {
+            let split = split_amount(coin.amount, &recipients);
+            let amount = recipients
+                .iter()
+                .zip(split)
+                .filter(|((address, _), _)| *address == owner)
+                .fold(Uint128::zero(), |sum, (_, share)| sum + share);
+            Coin::new(amount.u128(), &coin.denom)
+        })
+        .filter(|coin| !coin.amount.is_zero())
+        .collect();
+    Ok(FeeBalancesResponse {
+        balances,
+        owner_share,
+    })
+}
+
+pub fn query_stats(deps: Deps) -> StdResult<StatsResponse> {
+    let mints = load_tokens_minted(deps.storage)?;
+    let live = Cw721Contract::<XyzExtension, Empty>::default().token_count(deps.storage)?;
+    let fees_collected = FEES_COLLECTED
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (denom, amount) = item?;
+            Ok(Coin {
+                denom: String::from_utf8(denom)?,
+                amount,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    let cw20_fees_collected = CW20_FEES_COLLECTED
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (address, amount) = item?;
+            Ok(Cw20Coin {
+                address: String::from_utf8(address)?,
+                amount,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(StatsResponse {
+        mints,
+        burns: mints.saturating_sub(live),
+        moves: MOVES_STARTED.may_load(deps.storage)?.unwrap_or_default(),
+        distance_traveled: DISTANCE_TRAVELED
+            .may_load(deps.storage)?
+            .unwrap_or_default(),
+        fees_collected,
+        cw20_fees_collected,
     })
 }
 
 pub fn cw721_base_query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     let cw721_contract = Cw721Contract::<XyzExtension, Empty>::default();
 
-    let cw721_msg: Cw721QueryMsg = msg.into();
+    let cw721_msg: Cw721QueryMsg = msg.try_into()?;
     match cw721_msg {
         Cw721QueryMsg::NftInfo { token_id } => {
             to_binary(&query_nft_info(deps, env, full_token_id(token_id)?)?)
@@ -145,13 +1620,97 @@ pub fn cw721_base_query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary
             full_token_id(token_id)?,
             include_expired.unwrap_or(false),
         )?),
+        Cw721QueryMsg::ApprovedForAll {
+            owner,
+            include_expired,
+            start_after,
+            limit,
+        } => to_binary(&ApprovedForAllResponse {
+            operators: operators(
+                deps,
+                env,
+                owner,
+                include_expired.unwrap_or(false),
+                start_after,
+                limit,
+            )?,
+        }),
         _ => cw721_contract.query(deps, env, cw721_msg),
     }
 }
 
-pub fn query_nft_info(deps: Deps, _env: Env, token_id: String) -> StdResult<Cw721NftInfoResponse> {
-    let info = tokens().load(deps.storage, &token_id)?;
-    Ok(info.as_cw721_nft_info())
+pub fn query_all_operators(
+    deps: Deps,
+    env: Env,
+    owner: String,
+    include_expired: bool,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<OperatorsResponse> {
+    Ok(OperatorsResponse {
+        operators: operators(deps, env, owner, include_expired, start_after, limit)?,
+    })
+}
+
+/// Returns the operators approved for all of owner's tokens after
+/// start_after, ordered by address, for both AllOperators and
+/// ApprovedForAll.
+fn operators(
+    deps: Deps,
+    env: Env,
+    owner: String,
+    include_expired: bool,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Vec<cw721::Approval>> {
+    let limit = CONFIG.load(deps.storage)?.query_limit(limit);
+    let start_addr = start_after
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let start = start_addr.map(|addr| Bound::exclusive(addr.as_ref()));
+
+    let owner_addr = deps.api.addr_validate(&owner)?;
+    Cw721Contract::<XyzExtension, Empty>::default()
+        .operators
+        .prefix(&owner_addr)
+        .range(deps.storage, start, None, Order::Ascending)
+        .filter(|item| {
+            include_expired
+                || item
+                    .as_ref()
+                    .map_or(true, |(_, expires)| !expires.is_expired(&env.block))
+        })
+        .take(limit)
+        .map(|item| {
+            let (spender, expires) = item?;
+            Ok(cw721::Approval {
+                spender: String::from_utf8(spender)?,
+                expires,
+            })
+        })
+        .collect()
+}
+
+pub fn query_nft_info(deps: Deps, env: Env, token_id: String) -> StdResult<Cw721NftInfoResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let info = load_current_token(deps, &config, &env, &token_id)?;
+    Ok(info.as_cw721_nft_info(&config, env.block.time))
+}
+
+/// Loads a token as of the current block, including any of its queued
+/// moves that have started since it was last acted on.
+fn load_current_token(
+    deps: Deps,
+    config: &Config,
+    env: &Env,
+    token_id: &str,
+) -> StdResult<XyzTokenInfo> {
+    let mut token = tokens().load(deps.storage, token_id)?;
+    let mut queue = MOVE_QUEUES
+        .may_load(deps.storage, token_id)?
+        .unwrap_or_default();
+    apply_queued_moves(config, &mut token, &mut queue, env.block.time);
+    Ok(token)
 }
 
 pub fn query_all_nft_info(
@@ -160,13 +1719,14 @@ pub fn query_all_nft_info(
     token_id: String,
     include_expired: bool,
 ) -> StdResult<Cw721AllNftInfoResponse> {
-    let info = tokens().load(deps.storage, &token_id)?;
+    let config = CONFIG.load(deps.storage)?;
+    let info = load_current_token(deps, &config, &env, &token_id)?;
     Ok(Cw721AllNftInfoResponse {
         access: OwnerOfResponse {
             owner: info.owner.to_string(),
             approvals: humanize_approvals(&env.block, &info, include_expired),
         },
-        info: info.as_cw721_nft_info(),
+        info: info.as_cw721_nft_info(&config, env.block.time),
     })
 }
 
@@ -176,7 +1736,7 @@ pub fn query_tokens(
     start_after: Option<String>,
     limit: Option<u32>,
 ) -> StdResult<TokensResponse> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let limit = CONFIG.load(deps.storage)?.query_limit(limit);
     let start = start_after.map(Bound::exclusive);
 
     let owner_addr = deps.api.addr_validate(&owner)?;
@@ -204,7 +1764,7 @@ pub fn query_all_tokens(
     start_after: Option<String>,
     limit: Option<u32>,
 ) -> StdResult<TokensResponse> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let limit = CONFIG.load(deps.storage)?.query_limit(limit);
     let start = start_after.map(Bound::exclusive);
 
     let res: StdResult<Vec<String>> = tokens()
@@ -235,6 +1795,44 @@ pub fn owner_of(
     })
 }
 
+pub fn query_approval(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+    spender: String,
+    include_expired: bool,
+) -> StdResult<ApprovalResponse> {
+    let info = tokens().load(deps.storage, &full_token_id(token_id)?)?;
+
+    // the owner can always transfer their own token
+    if info.owner == spender {
+        return Ok(ApprovalResponse {
+            approval: cw721::Approval {
+                spender,
+                expires: Expiration::Never {},
+            },
+        });
+    }
+
+    humanize_approvals(&env.block, &info, include_expired)
+        .into_iter()
+        .find(|approval| approval.spender == spender)
+        .map(|approval| ApprovalResponse { approval })
+        .ok_or_else(|| StdError::not_found("Approval"))
+}
+
+pub fn query_approvals(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+    include_expired: bool,
+) -> StdResult<ApprovalsResponse> {
+    let info = tokens().load(deps.storage, &full_token_id(token_id)?)?;
+    Ok(ApprovalsResponse {
+        approvals: humanize_approvals(&env.block, &info, include_expired),
+    })
+}
+
 // adapted from: https://github.com/CosmWasm/cw-nfts/blob/5e1e72a3682f988d4504b94f2e203dd4a5a99ad9/contracts/cw721-base/src/query.rs#L211-L228
 fn humanize_approvals(
     block: &BlockInfo,
@@ -259,9 +1857,12 @@ fn humanize_approval(approval: &cw721_base::state::Approval) -> cw721::Approval
 mod test {
     use super::*;
 
-    use collectxyz::nft::{Cw721AllNftInfoResponse, Cw721Metadata, Cw721Trait};
+    use collectxyz::nft::{
+        base64_token_image, CoordinateBounds, Cw721AllNftInfoResponse, Cw721Metadata, Cw721Trait,
+        DistanceMetric,
+    };
     use cosmwasm_std::testing::{mock_dependencies, mock_env};
-    use cosmwasm_std::{from_binary, Addr, DepsMut, Timestamp};
+    use cosmwasm_std::{from_binary, Addr, Coin, DepsMut, Timestamp, Uint128};
 
     const ADDR1: &str = "addr1";
     const ADDR2: &str = "addr2";
@@ -278,6 +1879,17 @@ mod test {
                     coordinates: Coordinates { x: 1, y: 1, z: 1 },
                     arrival: Timestamp::from_nanos(0),
                     prev_coordinates: None,
+                    energy: 0,
+                    energy_updated_at: Timestamp::from_nanos(0),
+                    level: 0,
+                    token_uri: None,
+                    minted_at: Timestamp::from_nanos(0),
+                    minted_height: 0,
+                    rerolled: false,
+                    name: None,
+                    description: None,
+                    moves_count: 0,
+                    total_distance: 0,
                 },
             },
             XyzTokenInfo {
@@ -290,12 +1902,78 @@ mod test {
                     coordinates: Coordinates { x: 2, y: 2, z: 2 },
                     arrival: Timestamp::from_nanos(0),
                     prev_coordinates: None,
+                    energy: 0,
+                    energy_updated_at: Timestamp::from_nanos(0),
+                    level: 0,
+                    token_uri: None,
+                    minted_at: Timestamp::from_nanos(0),
+                    minted_height: 0,
+                    rerolled: false,
+                    name: None,
+                    description: None,
+                    moves_count: 0,
+                    total_distance: 0,
                 },
             },
         ]
     }
 
     fn setup_storage(deps: DepsMut) {
+        let config = Config {
+            public_minting_enabled: true,
+            bounds: CoordinateBounds::symmetric(1000),
+            token_supply: 10000,
+            wallet_limit: 5,
+            mint_fee: Coin::new(0, "uluna"),
+            base_move_nanos: 10,
+            move_nanos_per_step: 1,
+            base_move_fee: Coin::new(100, "uluna"),
+            move_fee_per_step: Uint128::new(1),
+            energy_max: u32::MAX,
+            energy_regen_nanos: 0,
+            burn_mint_fees: false,
+            default_query_limit: 10,
+            max_query_limit: 30,
+            max_merge_level: 0,
+            max_public_mints_per_block: None,
+            refund_overpayment: true,
+            move_cancel_refund_bps: 0,
+            max_queued_moves: 0,
+            allowlist_mint_enabled: false,
+            allowlist_mint_fee: None,
+            allowlist_mint_quota: 1,
+            mint_fee_tiers: vec![],
+            mint_fee_alternatives: vec![],
+            cw20_mint_fee: None,
+            cw20_move_fee: None,
+            royalty_bps: 0,
+            royalty_payment_address: None,
+            fee_split: vec![],
+            max_captcha_age_nanos: 600_000_000_000,
+            embed_token_image: true,
+            move_fee_discounts: vec![],
+            teleport_enabled: false,
+            teleport_fee_multiplier: 1,
+            expedite_bps: 0,
+            expedite_min_remaining_nanos: 0,
+            move_cooldown_nanos: 0,
+            max_move_distance: 0,
+            mint_for_wallet_limit: false,
+            reroll_enabled: false,
+            reroll_window_nanos: 0,
+            distance_metric: DistanceMetric::Manhattan,
+            transfers_locked_during_move: false,
+            mint_start: None,
+            mint_end: None,
+            per_address_mint_cooldown_nanos: 0,
+            token_profiles_enabled: true,
+            base_token_uri: None,
+            unique_coordinates: true,
+            mint_fee_distance_curve: None,
+            adjacent_mint_discount_bps: 0,
+            fee_distribution: vec![],
+        };
+        CONFIG.save(deps.storage, &config).unwrap();
         for token in token_examples().iter() {
             tokens().save(deps.storage, &token.name, token).unwrap();
         }
@@ -313,35 +1991,60 @@ mod test {
         let expected = Cw721NftInfoResponse {
             token_uri: None,
             extension: Cw721Metadata {
-                image: Some(
-                    "data:image/svg+xml;base64,PHN2ZyB4bWxucz0iaHR0cDovL3d3dy53My5vcmcvMjAwMC9zdmciIHByZXNlcnZlQXNwZWN0UmF0aW89InhNaW5ZTWluIG1lZXQiIHZpZXdCb3g9IjAgMCAyNDAgMjQwIj48ZyBjbGFzcz0iY29udGFpbmVyIj48cmVjdCBzdHlsZT0id2lkdGg6MjQwcHg7aGVpZ2h0OjI0MHB4O2ZpbGw6IzAwMDsiLz48dGV4dCB4PSIxMjAiIHk9IjEyMCIgZG9taW5hbnQtYmFzZWxpbmU9Im1pZGRsZSIgdGV4dC1hbmNob3I9Im1pZGRsZSIgc3R5bGU9ImZpbGw6I2ZmZjtmb250LWZhbWlseTpzZXJpZjtmb250LXNpemU6MTZweDt0ZXh0LWFsaWduOmNlbnRlcjsiPlsxLCAxLCAxXTwvdGV4dD48L2c+PC9zdmc+".to_string(),
-                ),
+                image: Some(base64_token_image(
+                    &token_examples()[0].extension.coordinates,
+                )),
                 image_data: None,
                 external_url: None,
-                description: Some("".to_string()
-                ),
-                name: Some(
-                    "xyz #1".to_string()
-                ),
-                attributes: Some(
-                    vec![
-                        Cw721Trait {
-                            display_type: None,
-                            trait_type: "x".to_string(),
-                            value: "1".to_string(),
-                        },
-                        Cw721Trait {
-                            display_type: None,
-                            trait_type: "y".to_string(),
-                            value: "1".to_string(),
-                        },
-                        Cw721Trait {
-                            display_type: None,
-                            trait_type: "z".to_string(),
-                            value: "1".to_string(),
-                        },
-                    ],
-                ),
+                description: Some("".to_string()),
+                name: Some("xyz #1".to_string()),
+                attributes: Some(vec![
+                    Cw721Trait {
+                        display_type: None,
+                        trait_type: "x".to_string(),
+                        value: "1".to_string(),
+                    },
+                    Cw721Trait {
+                        display_type: None,
+                        trait_type: "y".to_string(),
+                        value: "1".to_string(),
+                    },
+                    Cw721Trait {
+                        display_type: None,
+                        trait_type: "z".to_string(),
+                        value: "1".to_string(),
+                    },
+                    Cw721Trait {
+                        display_type: None,
+                        trait_type: "in_transit".to_string(),
+                        value: "false".to_string(),
+                    },
+                    Cw721Trait {
+                        display_type: Some("date".to_string()),
+                        trait_type: "arrival".to_string(),
+                        value: "0".to_string(),
+                    },
+                    Cw721Trait {
+                        display_type: Some("date".to_string()),
+                        trait_type: "minted_at".to_string(),
+                        value: "0".to_string(),
+                    },
+                    Cw721Trait {
+                        display_type: None,
+                        trait_type: "minted_height".to_string(),
+                        value: "0".to_string(),
+                    },
+                    Cw721Trait {
+                        display_type: Some("number".to_string()),
+                        trait_type: "moves_count".to_string(),
+                        value: "0".to_string(),
+                    },
+                    Cw721Trait {
+                        display_type: Some("number".to_string()),
+                        trait_type: "total_distance".to_string(),
+                        value: "0".to_string(),
+                    },
+                ]),
                 background_color: None,
                 animation_url: None,
                 youtube_url: None,
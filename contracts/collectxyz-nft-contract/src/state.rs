@@ -1,36 +1,269 @@
-use rsa::{pkcs8::FromPublicKey, RsaPublicKey};
+use std::convert::TryInto;
 
-use collectxyz::nft::{Config, XyzTokenInfo};
-use cosmwasm_std::{Addr, StdError, StdResult, Storage};
-use cw_storage_plus::{Index, IndexList, IndexedMap, Item, MultiIndex, UniqueIndex};
+use rsa::{
+    pkcs8::{FromPublicKey, ToPublicKey},
+    RsaPublicKey,
+};
 
-pub const CONFIG: Item<Config> = Item::new("config");
+use collectxyz::nft::{
+    CaptchaKeyAlgorithm, CaptchaPublicKey, Config, ConfigVersion, CoordinateBounds, Coordinates,
+    Listing, MoveRecord, Offer, PausableAction, PilotGrant, QueuedMove, Reservation, SwapProposal,
+    XyzExtension, XyzTokenInfo,
+};
+use cosmwasm_std::{
+    Addr, BlockInfo, Coin, Empty, Order, OverflowError, OverflowOperation, StdError, StdResult,
+    Storage, Timestamp, Uint128,
+};
+use cw721::Expiration;
+use cw721_base::Cw721Contract;
+use cw_storage_plus::{
+    Bound, Index, IndexList, IndexedMap, Item, Map, MultiIndex, PrimaryKey, U64Key,
+};
 
-const CAPTCHA_PUBLIC_KEY: Item<String> = Item::new("captcha_public_key");
-pub fn save_captcha_public_key(storage: &mut dyn Storage, public_key: &str) -> StdResult<()> {
-    RsaPublicKey::from_public_key_pem(public_key)
-        .map_err(|_| StdError::generic_err("invalid public key"))?;
-    CAPTCHA_PUBLIC_KEY.save(storage, &public_key.to_string())?;
-    Ok(())
+const CONFIG_KEY: &str = "config";
+pub const CONFIG: Item<Config> = Item::new(CONFIG_KEY);
+
+/// When the config last changed, and its version at that point
+const CONFIG_VERSION: Item<ConfigVersion> = Item::new("config_version");
+
+/// Saves the config as a new version changed in the given block.
+pub fn save_config(storage: &mut dyn Storage, block: &BlockInfo, config: &Config) -> StdResult<()> {
+    let version = load_config_version(storage)?.version + 1;
+    CONFIG.save(storage, config)?;
+    CONFIG_VERSION.save(
+        storage,
+        &ConfigVersion {
+            version,
+            updated_at: block.time,
+            updated_height: block.height,
+        },
+    )
+}
+
+/// Returns the config's version, or version 0 if the config predates
+/// versioning and hasn't been migrated.
+pub fn load_config_version(storage: &dyn Storage) -> StdResult<ConfigVersion> {
+    Ok(CONFIG_VERSION.may_load(storage)?.unwrap_or(ConfigVersion {
+        version: 0,
+        updated_at: Timestamp::from_nanos(0),
+        updated_height: 0,
+    }))
+}
+
+/// Rewrites a config stored before per-axis coordinate bounds existed,
+/// replacing its max_coordinate_value with the equivalent symmetric bounds.
+/// Configs that already have bounds are left unchanged.
+pub fn migrate_config_bounds(storage: &mut dyn Storage) -> StdResult<()> {
+    let raw = storage
+        .get(CONFIG_KEY.as_bytes())
+        .ok_or_else(|| StdError::not_found("config"))?;
+    let mut config: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_slice(&raw).map_err(|e| StdError::parse_err("config", e.to_string()))?;
+    let max_value = match config.remove("max_coordinate_value") {
+        Some(max_value) => max_value
+            .as_i64()
+            .filter(|max_value| *max_value >= 0)
+            .ok_or_else(|| StdError::parse_err("config", "invalid max_coordinate_value"))?,
+        None => return Ok(()),
+    };
+    config.insert(
+        "bounds".to_string(),
+        serde_json::to_value(CoordinateBounds::symmetric(max_value))
+            .map_err(|e| StdError::serialize_err("config", e.to_string()))?,
+    );
+    let config: Config = serde_json::from_value(config.into())
+        .map_err(|e| StdError::parse_err("config", e.to_string()))?;
+    CONFIG.save(storage, &config)
 }
-pub fn load_captcha_public_key(storage: &dyn Storage) -> StdResult<RsaPublicKey> {
-    let public_key = CAPTCHA_PUBLIC_KEY.load(storage)?;
-    RsaPublicKey::from_public_key_pem(&public_key)
-        .map_err(|_| StdError::generic_err("invalid public key"))
+
+/// The single captcha public key stored before key rotation was supported.
+/// Only read when migrating.
+const LEGACY_CAPTCHA_PUBLIC_KEY: Item<String> = Item::new("captcha_public_key");
+
+/// The captcha public keys signatures may be made under, oldest first
+const CAPTCHA_PUBLIC_KEYS: Item<Vec<CaptchaPublicKey>> = Item::new("captcha_public_keys");
+
+/// The most captcha public keys that may be stored at once
+pub const MAX_CAPTCHA_PUBLIC_KEYS: usize = 5;
+
+/// A parsed captcha public key, ready to verify signatures
+pub enum CaptchaVerifier {
+    Rsa(RsaPublicKey),
+    Secp256k1(Vec<u8>),
+    Ed25519(Vec<u8>),
+}
+
+/// Parses a public key under the given algorithm, returning it along with
+/// its normalized encoding so that equal keys compare equal. Keys whose
+/// length doesn't match the algorithm are rejected.
+fn parse_captcha_public_key(
+    public_key: &str,
+    algorithm: CaptchaKeyAlgorithm,
+) -> StdResult<(CaptchaVerifier, String)> {
+    let invalid = || StdError::generic_err("invalid public key");
+    match algorithm {
+        CaptchaKeyAlgorithm::Rsa => {
+            let key = RsaPublicKey::from_public_key_pem(public_key).map_err(|_| invalid())?;
+            let pem = key.to_public_key_pem().map_err(|_| invalid())?;
+            Ok((CaptchaVerifier::Rsa(key), pem))
+        }
+        CaptchaKeyAlgorithm::Secp256k1 => {
+            let bytes = base64::decode(public_key).map_err(|_| invalid())?;
+            let valid = match bytes.first() {
+                Some(0x02) | Some(0x03) => bytes.len() == 33,
+                Some(0x04) => bytes.len() == 65,
+                _ => false,
+            };
+            if !valid {
+                return Err(StdError::generic_err(
+                    "secp256k1 public keys must be 33 bytes compressed or 65 bytes uncompressed",
+                ));
+            }
+            let encoded = base64::encode(&bytes);
+            Ok((CaptchaVerifier::Secp256k1(bytes), encoded))
+        }
+        CaptchaKeyAlgorithm::Ed25519 => {
+            let bytes = base64::decode(public_key).map_err(|_| invalid())?;
+            if bytes.len() != 32 {
+                return Err(StdError::generic_err(
+                    "ed25519 public keys must be 32 bytes",
+                ));
+            }
+            let encoded = base64::encode(&bytes);
+            Ok((CaptchaVerifier::Ed25519(bytes), encoded))
+        }
+    }
+}
+
+/// Replaces every stored captcha public key with public_key.
+pub fn save_captcha_public_key(
+    storage: &mut dyn Storage,
+    public_key: &str,
+    algorithm: CaptchaKeyAlgorithm,
+) -> StdResult<()> {
+    let (_, public_key) = parse_captcha_public_key(public_key, algorithm)?;
+    CAPTCHA_PUBLIC_KEYS.save(
+        storage,
+        &vec![CaptchaPublicKey {
+            public_key,
+            algorithm,
+            expires: None,
+        }],
+    )
+}
+
+/// Adds public_key to the stored captcha public keys, or updates its expiry
+/// if it's already stored. Expired keys are pruned first.
+pub fn add_captcha_public_key(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    public_key: &str,
+    algorithm: CaptchaKeyAlgorithm,
+    expires: Option<Expiration>,
+) -> StdResult<()> {
+    let (_, public_key) = parse_captcha_public_key(public_key, algorithm)?;
+    let mut keys = load_active_captcha_public_keys(storage, block)?;
+    keys.retain(|key| key.public_key != public_key);
+    if keys.len() >= MAX_CAPTCHA_PUBLIC_KEYS {
+        return Err(StdError::generic_err(format!(
+            "can't store more than {} captcha public keys",
+            MAX_CAPTCHA_PUBLIC_KEYS
+        )));
+    }
+    keys.push(CaptchaPublicKey {
+        public_key,
+        algorithm,
+        expires,
+    });
+    CAPTCHA_PUBLIC_KEYS.save(storage, &keys)
+}
+
+/// Removes public_key from the stored captcha public keys. Expired keys are
+/// pruned first, and the last active key can't be removed.
+pub fn remove_captcha_public_key(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    public_key: &str,
+    algorithm: CaptchaKeyAlgorithm,
+) -> StdResult<()> {
+    let (_, public_key) = parse_captcha_public_key(public_key, algorithm)?;
+    let mut keys = load_active_captcha_public_keys(storage, block)?;
+    let len = keys.len();
+    keys.retain(|key| key.public_key != public_key);
+    if keys.len() == len {
+        return Err(StdError::not_found("captcha public key"));
+    }
+    if keys.is_empty() {
+        return Err(StdError::generic_err(
+            "can't remove the last active captcha public key",
+        ));
+    }
+    CAPTCHA_PUBLIC_KEYS.save(storage, &keys)
+}
+
+/// Returns the stored captcha public keys that haven't expired, oldest
+/// first.
+pub fn load_active_captcha_public_keys(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+) -> StdResult<Vec<CaptchaPublicKey>> {
+    let mut keys = CAPTCHA_PUBLIC_KEYS.load(storage)?;
+    keys.retain(|key| {
+        !key.expires
+            .map_or(false, |expires| expires.is_expired(block))
+    });
+    Ok(keys)
+}
+
+/// Returns the parsed captcha public keys that haven't expired, oldest
+/// first.
+pub fn load_captcha_public_keys(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+) -> StdResult<Vec<CaptchaVerifier>> {
+    load_active_captcha_public_keys(storage, block)?
+        .iter()
+        .map(|key| parse_captcha_public_key(&key.public_key, key.algorithm).map(|(key, _)| key))
+        .collect()
+}
+
+/// Moves a captcha public key stored before key rotation was supported into
+/// the set of captcha public keys. Contracts already storing a set are left
+/// unchanged.
+pub fn migrate_captcha_public_keys(storage: &mut dyn Storage) -> StdResult<()> {
+    if let Some(public_key) = LEGACY_CAPTCHA_PUBLIC_KEY.may_load(storage)? {
+        if CAPTCHA_PUBLIC_KEYS.may_load(storage)?.is_none() {
+            save_captcha_public_key(storage, &public_key, CaptchaKeyAlgorithm::Rsa)?;
+        }
+        LEGACY_CAPTCHA_PUBLIC_KEY.remove(storage);
+    }
+    Ok(())
 }
 
 pub struct TokenIndexes<'a> {
     pub owner: MultiIndex<'a, (Addr, Vec<u8>), XyzTokenInfo>,
-    pub coordinates: UniqueIndex<'a, Vec<u8>, XyzTokenInfo>,
+    /// Indexes tokens by prev_coordinates, the origin of their latest move.
+    /// Tokens that have never moved are indexed under an empty key, which
+    /// sorts before every encoded coordinate.
+    pub prev_coordinates: MultiIndex<'a, (Vec<u8>, Vec<u8>), XyzTokenInfo>,
+    /// Indexes tokens by the big-endian nanos of their latest arrival time,
+    /// which excludes any queued moves not yet started.
+    pub arrival: MultiIndex<'a, (Vec<u8>, Vec<u8>), XyzTokenInfo>,
 }
 
 impl<'a> IndexList<XyzTokenInfo> for TokenIndexes<'a> {
     fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<XyzTokenInfo>> + '_> {
-        let v: Vec<&dyn Index<XyzTokenInfo>> = vec![&self.owner, &self.coordinates];
+        let v: Vec<&dyn Index<XyzTokenInfo>> =
+            vec![&self.owner, &self.prev_coordinates, &self.arrival];
         Box::new(v.into_iter())
     }
 }
 
+/// Encodes an arrival time as a tokens arrival index key
+pub fn arrival_key(arrival: Timestamp) -> Vec<u8> {
+    arrival.nanos().to_be_bytes().to_vec()
+}
+
 pub fn tokens<'a>() -> IndexedMap<'a, &'a str, XyzTokenInfo, TokenIndexes<'a>> {
     let indexes = TokenIndexes {
         owner: MultiIndex::new(
@@ -38,12 +271,651 @@ pub fn tokens<'a>() -> IndexedMap<'a, &'a str, XyzTokenInfo, TokenIndexes<'a>> {
             "tokens",
             "tokens__owner",
         ),
-        coordinates: UniqueIndex::new(
-            |d: &XyzTokenInfo| d.extension.coordinates.to_bytes(),
-            "tokens__coordinates",
+        prev_coordinates: MultiIndex::new(
+            |d: &XyzTokenInfo, k: Vec<u8>| {
+                let prev = d.extension.prev_coordinates.map(|c| c.to_bytes());
+                (prev.unwrap_or_default(), k)
+            },
+            "tokens",
+            "tokens__prev_coordinates",
+        ),
+        arrival: MultiIndex::new(
+            |d: &XyzTokenInfo, k: Vec<u8>| (arrival_key(d.extension.arrival), k),
+            "tokens",
+            "tokens__arrival",
         ),
     };
     IndexedMap::new("tokens", indexes)
 }
 
 pub const OWNER: Item<String> = Item::new("owner");
+
+/// The address proposed as the next owner and the proposal's expiry, if
+/// any. The proposal takes effect once the proposed owner accepts it.
+pub const PENDING_OWNER: Item<(Addr, Option<Expiration>)> = Item::new("pending_owner");
+
+/// If true, the config and other contract parameters can no longer be
+/// changed. Once set, it's never cleared.
+pub const CONFIG_FROZEN: Item<bool> = Item::new("config_frozen");
+
+/// The actions paused by the owner. Unpausing removes the item.
+pub const PAUSED_ACTIONS: Item<Vec<PausableAction>> = Item::new("paused_actions");
+
+/// The contracts sent an XyzHookMsg whenever a token changes position or
+/// owner
+pub const HOOKS: Item<Vec<Addr>> = Item::new("hooks");
+
+/// The most hooks that may be registered at once
+pub const MAX_HOOKS: usize = 10;
+
+/// The coordinates only the owner may mint, keyed by coordinate bytes.
+/// Expired reservations are kept until they're released, consumed or
+/// replaced, but count as absent.
+pub const RESERVED_COORDINATES: Map<&[u8], Reservation> = Map::new("reserved_coordinates");
+
+/// Returns whether coords hold a reservation that hasn't expired by time.
+pub fn is_reserved(
+    storage: &dyn Storage,
+    coords: &Coordinates,
+    time: Timestamp,
+) -> StdResult<bool> {
+    Ok(RESERVED_COORDINATES
+        .may_load(storage, &coords.to_bytes())?
+        .map_or(false, |reservation| reservation.is_active(time)))
+}
+
+/// The most coordinates that may be reserved or released in one message
+pub const MAX_RESERVATION_BATCH: usize = 100;
+
+/// The addresses exempt from the wallet limit
+pub const WALLET_LIMIT_EXEMPTIONS: Map<&Addr, Empty> = Map::new("wallet_limit_exemptions");
+
+/// The captcha signature nonces each address has used, so that signatures
+/// can't be replayed
+pub const CAPTCHA_NONCES: Map<(&Addr, U64Key), Empty> = Map::new("captcha_nonces");
+
+/// The hex-encoded root of the allowlist merkle tree
+pub const ALLOWLIST_MERKLE_ROOT: Item<String> = Item::new("allowlist_merkle_root");
+
+/// The number of tokens each address has minted through the allowlist, used
+/// to enforce Config::allowlist_mint_quota
+pub const ALLOWLIST_MINTS: Map<&Addr, u32> = Map::new("allowlist_mints");
+
+/// The block height and number of public mints made in that block, used to
+/// enforce Config::max_public_mints_per_block
+pub const PUBLIC_MINTS_IN_BLOCK: Item<(u64, u32)> = Item::new("public_mints_in_block");
+
+pub fn load_public_mints_in_block(storage: &dyn Storage, height: u64) -> StdResult<u32> {
+    Ok(match PUBLIC_MINTS_IN_BLOCK.may_load(storage)? {
+        Some((mint_height, count)) if mint_height == height => count,
+        _ => 0,
+    })
+}
+
+/// When each address last minted publicly, used to enforce
+/// Config::per_address_mint_cooldown_nanos. Entries whose cooldown has
+/// passed are pruned a few at a time as later mints are recorded.
+pub const LAST_PUBLIC_MINTS: Map<&Addr, Timestamp> = Map::new("last_public_mints");
+
+/// LAST_PUBLIC_MINTS keyed by mint time, oldest first, for pruning
+const LAST_PUBLIC_MINTS_BY_TIME: Map<(U64Key, &Addr), Empty> =
+    Map::new("last_public_mints_by_time");
+
+/// The most expired LAST_PUBLIC_MINTS entries pruned per recorded mint.
+/// Pruning more than the one entry each mint adds keeps the records bounded
+/// by the number of addresses still cooling down.
+const MAX_LAST_PUBLIC_MINTS_PRUNED: usize = 2;
+
+/// Records that minter publicly minted at time, pruning expired records.
+pub fn record_public_mint(
+    storage: &mut dyn Storage,
+    config: &Config,
+    minter: &Addr,
+    time: Timestamp,
+) -> StdResult<()> {
+    let cutoff = time
+        .nanos()
+        .saturating_sub(config.per_address_mint_cooldown_nanos);
+    // the empty address sorts before any address that minted at the cutoff
+    let cutoff = (U64Key::new(cutoff), &Addr::unchecked("")).joined_key();
+    let expired: Vec<Vec<u8>> = LAST_PUBLIC_MINTS_BY_TIME
+        .keys(
+            storage,
+            None,
+            Some(Bound::exclusive(cutoff)),
+            Order::Ascending,
+        )
+        .take(MAX_LAST_PUBLIC_MINTS_PRUNED)
+        .collect();
+    for key in expired {
+        let (mint_time, address) = parse_u64_addr_key(&key)?;
+        LAST_PUBLIC_MINTS_BY_TIME.remove(storage, (U64Key::new(mint_time), &address));
+        LAST_PUBLIC_MINTS.remove(storage, &address);
+    }
+
+    if config.per_address_mint_cooldown_nanos > 0 {
+        if let Some(last_mint) = LAST_PUBLIC_MINTS.may_load(storage, minter)? {
+            LAST_PUBLIC_MINTS_BY_TIME.remove(storage, (U64Key::new(last_mint.nanos()), minter));
+        }
+        LAST_PUBLIC_MINTS.save(storage, minter, &time)?;
+        LAST_PUBLIC_MINTS_BY_TIME.save(storage, (U64Key::new(time.nanos()), minter), &Empty {})?;
+    }
+    Ok(())
+}
+
+/// The fee paid for each token's most recent move, used to cap the refund
+/// when a move is cancelled. Moves that charged no fee aren't recorded.
+pub const MOVE_FEES_PAID: Map<&str, Coin> = Map::new("move_fees_paid");
+
+/// Who paid each fee in MOVE_FEES_PAID, and so is refunded if the move is
+/// cancelled. Fees recorded before payers were tracked have no entry, and
+/// are refunded to the token's owner.
+pub const MOVE_FEE_PAYERS: Map<&str, Addr> = Map::new("move_fee_payers");
+
+/// The moves queued behind each token's in-progress move, in order
+pub const MOVE_QUEUES: Map<&str, Vec<QueuedMove>> = Map::new("move_queues");
+
+/// The tokens at each occupied coordinates, keyed by coordinate_key and
+/// mapped to the token id. It's the one record of which coordinates are
+/// taken: while Config::unique_coordinates is set, entries are only added
+/// through a check-and-set that fails if the coordinates are already held,
+/// so no two tokens can be recorded at the same coordinates. Otherwise,
+/// tokens can stack.
+pub const COORDINATE_TOKENS: Map<&[u8], String> = Map::new("coordinate_tokens");
+
+/// The destinations of all queued moves, keyed by coordinate_key and mapped
+/// to the token queued to move there. Queued destinations count as claimed
+/// while Config::unique_coordinates is set, so a queued move can always
+/// start once its token arrives.
+pub const QUEUED_COORDINATES: Map<&[u8], String> = Map::new("queued_coordinates");
+
+/// Returns the COORDINATE_TOKENS or QUEUED_COORDINATES key recording the
+/// token at coords: the coordinate bytes followed by the token id, so keys
+/// are ordered by coordinates and then token id.
+pub fn coordinate_key(coords: &Coordinates, token_id: &str) -> Vec<u8> {
+    [coords.to_bytes(), token_id.as_bytes().to_vec()].concat()
+}
+
+/// Returns the coordinates recorded by a coordinate_key.
+pub fn coordinates_of_key(key: &[u8]) -> StdResult<Coordinates> {
+    Coordinates::from_bytes(key.get(..24).unwrap_or(key))
+}
+
+/// Returns a key sorting after every coordinate_key at coords, since token
+/// ids are UTF-8 and so never contain the byte 0xff.
+pub fn coordinates_end_key(coords: &Coordinates) -> Vec<u8> {
+    [coords.to_bytes(), vec![0xff]].concat()
+}
+
+/// Returns the ids of the tokens recorded at coords in one of the
+/// coordinate-keyed maps, in token id order, starting after start_after.
+pub fn load_tokens_at(
+    storage: &dyn Storage,
+    map: &Map<&[u8], String>,
+    coords: &Coordinates,
+    start_after: Option<&str>,
+    limit: usize,
+) -> StdResult<Vec<String>> {
+    let start = match start_after {
+        Some(token_id) => Bound::exclusive(coordinate_key(coords, token_id)),
+        None => Bound::inclusive(coords.to_bytes()),
+    };
+    map.range(
+        storage,
+        Some(start),
+        Some(Bound::exclusive(coordinates_end_key(coords))),
+        Order::Ascending,
+    )
+    .take(limit)
+    .map(|item| item.map(|(_, token_id)| token_id))
+    .collect()
+}
+
+/// Returns the first token recorded at coords in one of the
+/// coordinate-keyed maps, which is the only one while
+/// Config::unique_coordinates is set.
+pub fn load_token_at(
+    storage: &dyn Storage,
+    map: &Map<&[u8], String>,
+    coords: &Coordinates,
+) -> StdResult<Option<String>> {
+    Ok(load_tokens_at(storage, map, coords, None, 1)?.pop())
+}
+
+/// Returns the first coordinates claimed by more than one token, as an
+/// occupant or a queued destination, if any. Keys at the same coordinates
+/// are adjacent in each map, so each map is scanned once.
+pub fn find_stacked_coordinates(storage: &dyn Storage) -> StdResult<Option<Coordinates>> {
+    let mut prev = None;
+    for key in COORDINATE_TOKENS.keys(storage, None, None, Order::Ascending) {
+        let coords = coordinates_of_key(&key)?;
+        if prev == Some(coords) {
+            return Ok(Some(coords));
+        }
+        prev = Some(coords);
+    }
+    let mut prev = None;
+    for key in QUEUED_COORDINATES.keys(storage, None, None, Order::Ascending) {
+        let coords = coordinates_of_key(&key)?;
+        if prev == Some(coords) || load_token_at(storage, &COORDINATE_TOKENS, &coords)?.is_some() {
+            return Ok(Some(coords));
+        }
+        prev = Some(coords);
+    }
+    Ok(None)
+}
+
+/// The number of tokens ever minted, including burned tokens. New token ids
+/// are allocated from this, so ids are never reused. The cw721 token count
+/// only tracks live tokens.
+pub const TOKENS_MINTED: Item<u64> = Item::new("tokens_minted");
+
+pub fn load_tokens_minted(storage: &dyn Storage) -> StdResult<u64> {
+    match TOKENS_MINTED.may_load(storage)? {
+        Some(minted) => Ok(minted),
+        // before burns existed, every minted token was live
+        None => Cw721Contract::<XyzExtension, Empty>::default().token_count(storage),
+    }
+}
+
+/// The lifetime number of moves started, including queued moves, teleports
+/// and both moves of each swap
+pub const MOVES_STARTED: Item<u64> = Item::new("moves_started");
+
+/// The lifetime total distance covered by the moves in MOVES_STARTED
+pub const DISTANCE_TRAVELED: Item<Uint128> = Item::new("distance_traveled");
+
+/// The lifetime total of native fees collected, by denom, counting the
+/// full fee of moves that are later cancelled
+pub const FEES_COLLECTED: Map<&str, Uint128> = Map::new("fees_collected");
+
+/// The lifetime total of CW20 fees collected, by token contract
+pub const CW20_FEES_COLLECTED: Map<&Addr, Uint128> = Map::new("cw20_fees_collected");
+
+/// Starts the lifetime move counts from zero where they aren't recorded yet.
+/// Earlier moves and fees can't be recovered, but mint and burn counts come
+/// from TOKENS_MINTED and the live token count, so they cover every token.
+pub fn start_lifetime_stats(storage: &mut dyn Storage) -> StdResult<()> {
+    if MOVES_STARTED.may_load(storage)?.is_none() {
+        MOVES_STARTED.save(storage, &0)?;
+    }
+    if DISTANCE_TRAVELED.may_load(storage)?.is_none() {
+        DISTANCE_TRAVELED.save(storage, &Uint128::zero())?;
+    }
+    Ok(())
+}
+
+pub fn record_move(storage: &mut dyn Storage, distance: u64) -> StdResult<()> {
+    let moves = MOVES_STARTED.may_load(storage)?.unwrap_or_default();
+    let moves = moves
+        .checked_add(1)
+        .ok_or_else(|| OverflowError::new(OverflowOperation::Add, moves, 1))?;
+    MOVES_STARTED.save(storage, &moves)?;
+    let traveled = DISTANCE_TRAVELED.may_load(storage)?.unwrap_or_default();
+    DISTANCE_TRAVELED.save(storage, &traveled.checked_add(distance.into())?)
+}
+
+pub fn record_fee(storage: &mut dyn Storage, fee: &Coin) -> StdResult<()> {
+    if fee.amount.is_zero() {
+        return Ok(());
+    }
+    FEES_COLLECTED.update(storage, &fee.denom, |collected| -> StdResult<_> {
+        Ok(collected.unwrap_or_default().checked_add(fee.amount)?)
+    })?;
+    Ok(())
+}
+
+pub fn record_cw20_fee(storage: &mut dyn Storage, token: &Addr, amount: Uint128) -> StdResult<()> {
+    if amount.is_zero() {
+        return Ok(());
+    }
+    CW20_FEES_COLLECTED.update(storage, token, |collected| -> StdResult<_> {
+        Ok(collected.unwrap_or_default().checked_add(amount)?)
+    })?;
+    Ok(())
+}
+
+/// Each token's move history, keyed by token id and the entry's sequence
+/// number. Entries are kept when the token is burned, since token ids are
+/// never reused.
+pub const MOVE_HISTORY: Map<(&str, U64Key), MoveRecord> = Map::new("move_history");
+
+/// Appends record to the token's move history, after its latest entry.
+pub fn append_move_history(
+    storage: &mut dyn Storage,
+    token_id: &str,
+    record: &MoveRecord,
+) -> StdResult<()> {
+    let latest = MOVE_HISTORY
+        .prefix(token_id)
+        .keys(storage, None, None, Order::Descending)
+        .next();
+    let sequence = match latest {
+        Some(key) => parse_u64_key(&key)? + 1,
+        None => 0,
+    };
+    MOVE_HISTORY.save(storage, (token_id, U64Key::new(sequence)), record)
+}
+
+/// Parses a U64Key suffix read back from a prefixed range.
+pub fn parse_u64_key(key: &[u8]) -> StdResult<u64> {
+    let bytes: [u8; 8] = key
+        .try_into()
+        .map_err(|_| StdError::generic_err("invalid key"))?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// The number of tokens currently held by each wallet. Wallets are removed
+/// once they no longer hold any tokens, so the map only contains holders.
+pub const OWNER_TOKEN_COUNTS: Map<&Addr, u64> = Map::new("owner_token_counts");
+
+/// Every holder keyed by their token count and address, so holders can be
+/// listed by how many tokens they hold. Kept in step with OWNER_TOKEN_COUNTS.
+pub const OWNER_LEADERBOARD: Map<(U64Key, &Addr), Empty> = Map::new("owner_leaderboard");
+
+/// Returns the OWNER_LEADERBOARD key for owner holding count tokens.
+pub fn owner_leaderboard_key(count: u64, owner: &Addr) -> Vec<u8> {
+    (U64Key::new(count), owner).joined_key()
+}
+
+/// Parses a (U64Key, &Addr) map key, such as an OWNER_LEADERBOARD key, back
+/// into its number and address.
+pub fn parse_u64_addr_key(key: &[u8]) -> StdResult<(u64, Addr)> {
+    let invalid = || StdError::generic_err("invalid key");
+    // the count is length-prefixed by the tuple key encoding
+    if key.len() < 10 || key[..2] != [0, 8] {
+        return Err(invalid());
+    }
+    let mut count = [0u8; 8];
+    count.copy_from_slice(&key[2..10]);
+    let count = u64::from_be_bytes(count);
+    let owner = String::from_utf8(key[10..].to_vec()).map_err(StdError::invalid_utf8)?;
+    Ok((count, Addr::unchecked(owner)))
+}
+
+/// Sets owner's token count, moving their OWNER_LEADERBOARD entry to match.
+fn save_owner_token_count(storage: &mut dyn Storage, owner: &Addr, count: u64) -> StdResult<()> {
+    if let Some(old) = OWNER_TOKEN_COUNTS.may_load(storage, owner)? {
+        OWNER_LEADERBOARD.remove(storage, (U64Key::new(old), owner));
+    }
+    if count == 0 {
+        OWNER_TOKEN_COUNTS.remove(storage, owner);
+    } else {
+        OWNER_TOKEN_COUNTS.save(storage, owner, &count)?;
+        OWNER_LEADERBOARD.save(storage, (U64Key::new(count), owner), &Empty {})?;
+    }
+    Ok(())
+}
+
+pub fn increment_owner_token_count(storage: &mut dyn Storage, owner: &Addr) -> StdResult<u64> {
+    let count = OWNER_TOKEN_COUNTS
+        .may_load(storage, owner)?
+        .unwrap_or_default()
+        + 1;
+    save_owner_token_count(storage, owner, count)?;
+    Ok(count)
+}
+
+pub fn decrement_owner_token_count(storage: &mut dyn Storage, owner: &Addr) -> StdResult<u64> {
+    let count = OWNER_TOKEN_COUNTS
+        .may_load(storage, owner)?
+        .unwrap_or_default()
+        .saturating_sub(1);
+    save_owner_token_count(storage, owner, count)?;
+    Ok(count)
+}
+
+/// Open swap proposals, keyed by id
+pub const SWAP_PROPOSALS: Map<U64Key, SwapProposal> = Map::new("swap_proposals");
+
+/// The id of the next swap proposal
+pub const NEXT_SWAP_PROPOSAL_ID: Item<u64> = Item::new("next_swap_proposal_id");
+
+/// Each token's pilot grant, cleared when the token changes hands
+pub const PILOTS: Map<&str, PilotGrant> = Map::new("pilots");
+
+/// The tokens listed for sale, keyed by token id. A token's listing is
+/// removed when it's bought, transferred or burned.
+pub const LISTINGS: Map<&str, Listing> = Map::new("listings");
+
+/// Escrowed offers to buy tokens, keyed by (token id, bidder)
+pub const OFFERS: Map<(&str, &Addr), Offer> = Map::new("offers");
+
+/// Index of OFFERS keyed by (bidder, token id)
+pub const BIDDER_OFFERS: Map<(&Addr, &str), Empty> = Map::new("bidder_offers");
+
+/// The total held in escrow for offers and swap proposals, by denom, which
+/// Withdraw won't touch
+pub const ESCROW: Map<&str, Uint128> = Map::new("escrow");
+
+pub fn add_escrow(storage: &mut dyn Storage, coin: &Coin) -> StdResult<()> {
+    ESCROW.update(storage, &coin.denom, |escrow| -> StdResult<_> {
+        Ok(escrow.unwrap_or_default().checked_add(coin.amount)?)
+    })?;
+    Ok(())
+}
+
+pub fn release_escrow(storage: &mut dyn Storage, coin: &Coin) -> StdResult<()> {
+    let escrow = ESCROW
+        .may_load(storage, &coin.denom)?
+        .unwrap_or_default()
+        .checked_sub(coin.amount)?;
+    if escrow.is_zero() {
+        ESCROW.remove(storage, &coin.denom);
+    } else {
+        ESCROW.save(storage, &coin.denom, &escrow)?;
+    }
+    Ok(())
+}
+
+/// Saves the offer, replacing the bidder's earlier offer on the token, if
+/// any, which is returned so its escrow can be refunded.
+pub fn save_offer(storage: &mut dyn Storage, offer: &Offer) -> StdResult<Option<Offer>> {
+    let old = remove_offer(storage, &offer.token_id, &offer.bidder)?;
+    OFFERS.save(storage, (&offer.token_id, &offer.bidder), offer)?;
+    BIDDER_OFFERS.save(storage, (&offer.bidder, &offer.token_id), &Empty {})?;
+    add_escrow(storage, &offer.price)?;
+    Ok(old)
+}
+
+/// Removes the bidder's offer on the token, if any, returning it so its
+/// escrow can be paid out.
+pub fn remove_offer(
+    storage: &mut dyn Storage,
+    token_id: &str,
+    bidder: &Addr,
+) -> StdResult<Option<Offer>> {
+    let offer = match OFFERS.may_load(storage, (token_id, bidder))? {
+        Some(offer) => offer,
+        None => return Ok(None),
+    };
+    OFFERS.remove(storage, (token_id, bidder));
+    BIDDER_OFFERS.remove(storage, (bidder, token_id));
+    release_escrow(storage, &offer.price)?;
+    Ok(Some(offer))
+}
+
+/// When each token's current owner received it, by mint or transfer
+pub const HELD_SINCE: Map<&str, Timestamp> = Map::new("held_since");
+
+/// Returns how long the token's current owner has held it, or zero if it's
+/// unrecorded.
+pub fn load_held_nanos(storage: &dyn Storage, token_id: &str, time: Timestamp) -> StdResult<u64> {
+    Ok(HELD_SINCE
+        .may_load(storage, token_id)?
+        .map_or(0, |since| time.nanos().saturating_sub(since.nanos())))
+}
+
+/// When each token was last sent a Move, for a move started or queued.
+/// Only tokens with a recorded move are subject to
+/// Config::move_cooldown_nanos, so that newly minted tokens can move at once.
+pub const MOVE_STARTED_AT: Map<&str, Timestamp> = Map::new("move_started_at");
+
+/// Returns the time the token can next start a move, if it's still cooling
+/// down from its last arrival at the given time.
+pub fn load_cooldown_until(
+    storage: &dyn Storage,
+    config: &Config,
+    token_id: &str,
+    token: &XyzTokenInfo,
+    time: Timestamp,
+) -> StdResult<Option<Timestamp>> {
+    if config.move_cooldown_nanos == 0 || !MOVE_STARTED_AT.has(storage, token_id) {
+        return Ok(None);
+    }
+    let until = token
+        .extension
+        .arrival
+        .plus_nanos(config.move_cooldown_nanos);
+    Ok(Some(until).filter(|until| *until > time))
+}
+
+/// Records time as the start of the holding period of every token whose
+/// holding period is unrecorded, as for tokens minted before holding
+/// periods were tracked.
+pub fn migrate_held_since(storage: &mut dyn Storage, time: Timestamp) -> StdResult<()> {
+    let token_ids: StdResult<Vec<Vec<u8>>> = tokens()
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(token_id, _)| token_id))
+        .collect();
+    for token_id in token_ids? {
+        let token_id = String::from_utf8(token_id)?;
+        if !HELD_SINCE.has(storage, &token_id) {
+            HELD_SINCE.save(storage, &token_id, &time)?;
+        }
+    }
+    Ok(())
+}
+
+/// Fills in the extension fields of every token saved before they were
+/// tracked. Such tokens get a full charge of config.energy_max as of the
+/// given block, so that their energy regenerates from then, and since their
+/// real mint block is unknown, the given block as minted_at and
+/// minted_height, and their re-roll as used. Their travel stats start from
+/// zero. Fields a token already stores are left unchanged.
+pub fn migrate_token_extensions(storage: &mut dyn Storage, block: &BlockInfo) -> StdResult<()> {
+    let config = CONFIG.load(storage)?;
+    let all_tokens: StdResult<Vec<(Vec<u8>, XyzTokenInfo)>> = tokens()
+        .range(storage, None, None, Order::Ascending)
+        .collect();
+    for (token_id, token) in all_tokens? {
+        let token_id = String::from_utf8(token_id)?;
+        let stored = stored_extension(storage, &token_id)?;
+        let mut new_token = token.clone();
+        if stored.get("energy").is_none() {
+            new_token.extension.energy = config.energy_max;
+            new_token.extension.energy_updated_at = block.time;
+        }
+        if stored.get("minted_at").is_none() {
+            new_token.extension.minted_at = block.time;
+            new_token.extension.minted_height = block.height;
+            new_token.extension.rerolled = true;
+        }
+        // write out zero travel stats for tokens stored before they existed
+        let untracked_travel = stored.get("moves_count").is_none();
+        if new_token != token || untracked_travel {
+            tokens().replace(storage, &token_id, Some(&new_token), Some(&token))?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a token's stored extension as raw JSON, since deserializing the
+/// token fills in any missing fields with defaults.
+fn stored_extension(storage: &dyn Storage, token_id: &str) -> StdResult<serde_json::Value> {
+    let primary: Map<&str, XyzTokenInfo> = Map::new("tokens");
+    let raw = storage
+        .get(&primary.key(token_id))
+        .ok_or_else(|| StdError::not_found("token"))?;
+    let stored: serde_json::Value =
+        serde_json::from_slice(&raw).map_err(|e| StdError::parse_err("token", e.to_string()))?;
+    Ok(stored["extension"].clone())
+}
+
+/// Recomputes OWNER_TOKEN_COUNTS and OWNER_LEADERBOARD from scratch by
+/// scanning every token.
+pub fn rebuild_owner_token_counts(storage: &mut dyn Storage) -> StdResult<()> {
+    let stale_owners: Vec<Vec<u8>> = OWNER_TOKEN_COUNTS
+        .keys(storage, None, None, Order::Ascending)
+        .collect();
+    for owner in stale_owners {
+        OWNER_TOKEN_COUNTS.remove(storage, &Addr::unchecked(String::from_utf8_lossy(&owner)));
+    }
+    let stale_entries: Vec<Vec<u8>> = OWNER_LEADERBOARD
+        .keys(storage, None, None, Order::Ascending)
+        .collect();
+    for key in stale_entries {
+        let (count, owner) = parse_u64_addr_key(&key)?;
+        OWNER_LEADERBOARD.remove(storage, (U64Key::new(count), &owner));
+    }
+
+    let owners: StdResult<Vec<Addr>> = tokens()
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, token)| token.owner))
+        .collect();
+    for owner in owners? {
+        increment_owner_token_count(storage, &owner)?;
+    }
+    Ok(())
+}
+
+/// Rebuilds the coordinate-keyed storage (COORDINATE_TOKENS, the tokens
+/// prev_coordinates index and QUEUED_COORDINATES) from scratch, so that every
+/// key uses the current Coordinates::to_bytes encoding. The tokens arrival
+/// index is rebuilt along with them, since it's missing for tokens saved
+/// before it was added, and the retired tokens coordinates index is cleared.
+pub fn rebuild_coordinate_keys(storage: &mut dyn Storage) -> StdResult<()> {
+    // read the indexes as raw bytes, since their keys may use an older
+    // encoding, or be missing for indexes added since the tokens were saved
+    for namespace in [
+        "tokens__coordinates",
+        "tokens__prev_coordinates",
+        "tokens__arrival",
+    ] {
+        let index: Map<&[u8], Empty> = Map::new(namespace);
+        let stale_keys: Vec<Vec<u8>> = index.keys(storage, None, None, Order::Ascending).collect();
+        for key in stale_keys {
+            index.remove(storage, &key);
+        }
+    }
+    let all_tokens: StdResult<Vec<(Vec<u8>, XyzTokenInfo)>> = tokens()
+        .range(storage, None, None, Order::Ascending)
+        .collect();
+    let stale_keys: Vec<Vec<u8>> = COORDINATE_TOKENS
+        .keys(storage, None, None, Order::Ascending)
+        .collect();
+    for key in stale_keys {
+        COORDINATE_TOKENS.remove(storage, &key);
+    }
+    for (token_id, token) in all_tokens? {
+        let token_id = String::from_utf8_lossy(&token_id).to_string();
+        // no old data, so only the new index entries are written
+        tokens().replace(storage, &token_id, Some(&token), None)?;
+        COORDINATE_TOKENS.save(
+            storage,
+            &coordinate_key(&token.extension.coordinates, &token_id),
+            &token_id,
+        )?;
+    }
+
+    let stale_keys: Vec<Vec<u8>> = QUEUED_COORDINATES
+        .keys(storage, None, None, Order::Ascending)
+        .collect();
+    for key in stale_keys {
+        QUEUED_COORDINATES.remove(storage, &key);
+    }
+    let queues: StdResult<Vec<(Vec<u8>, Vec<QueuedMove>)>> = MOVE_QUEUES
+        .range(storage, None, None, Order::Ascending)
+        .collect();
+    for (token_id, queue) in queues? {
+        let token_id = String::from_utf8_lossy(&token_id).to_string();
+        for queued_move in queue {
+            QUEUED_COORDINATES.save(
+                storage,
+                &coordinate_key(&queued_move.coordinates, &token_id),
+                &token_id,
+            )?;
+        }
+    }
+    Ok(())
+}
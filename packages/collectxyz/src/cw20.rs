@@ -0,0 +1,48 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{to_binary, Binary, CosmosMsg, StdResult, Uint128, WasmMsg};
+
+// ----------------- begin CW20 ^0.9 shim ----------------- //
+
+// adapted from: https://github.com/CosmWasm/cw-plus/blob/v0.9.1/packages/cw20/src/receiver.rs
+/// Cw20ReceiveMsg should be de/serialized under `Receive()` variant in a ExecuteMsg
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct Cw20ReceiveMsg {
+    pub sender: String,
+    pub amount: Uint128,
+    pub msg: Binary,
+}
+
+// adapted from: https://github.com/CosmWasm/cw-plus/blob/v0.9.1/packages/cw20/src/coin.rs
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Cw20Coin {
+    pub address: String,
+    pub amount: Uint128,
+}
+
+// adapted from: https://github.com/CosmWasm/cw-plus/blob/v0.9.1/packages/cw20/src/msg.rs
+/// The subset of the CW20 ExecuteMsg this contract sends to token contracts
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw20ExecuteMsg {
+    /// Transfer is a base message to move tokens to another account without triggering actions
+    Transfer { recipient: String, amount: Uint128 },
+    /// Burn is a base message to destroy tokens forever
+    Burn { amount: Uint128 },
+}
+
+// ----------------- end CW20 ^0.9 shim ----------------- //
+
+impl Cw20ExecuteMsg {
+    /// Wraps the message for execution on the given token contract.
+    pub fn into_cosmos_msg(self, token_address: impl Into<String>) -> StdResult<CosmosMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: token_address.into(),
+            msg: to_binary(&self)?,
+            funds: vec![],
+        }
+        .into())
+    }
+}
@@ -0,0 +1,98 @@
+use crate::nft::Coordinates;
+
+/// The number of background stars in every token image
+const STAR_COUNT: usize = 8;
+
+/// The most bytes a token image's SVG can take up, before base64 encoding
+pub const MAX_SVG_LEN: usize = 2048;
+
+/// A splitmix64 generator, used to scatter a token's stars from a seed
+/// derived from its coordinates alone.
+struct StarRng(u64);
+
+impl StarRng {
+    fn new(coords: &Coordinates) -> Self {
+        StarRng(
+            (coords.x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+                ^ (coords.y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+                ^ (coords.z as u64).wrapping_mul(0x1656_67B1_9E37_79F9),
+        )
+    }
+
+    fn next_below(&mut self, bound: u64) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        (z ^ (z >> 31)) % bound
+    }
+}
+
+/// Renders a token's image as an SVG: a starfield scattered by the token's
+/// coordinates, with a marker placed by x and y and colored by z, over the
+/// coordinates as text. The same coordinates always render the same image,
+/// and every image fits in MAX_SVG_LEN bytes.
+pub fn token_svg(coords: &Coordinates) -> String {
+    let mut rng = StarRng::new(coords);
+    let stars: String = (0..STAR_COUNT)
+        .map(|_| {
+            format!(
+                r#"<circle cx="{}" cy="{}" r="{}" style="fill:#fff;opacity:0.{};"/>"#,
+                rng.next_below(240),
+                rng.next_below(240),
+                1 + rng.next_below(2),
+                3 + rng.next_below(7),
+            )
+        })
+        .collect();
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" preserveAspectRatio="xMinYMin meet" viewBox="0 0 240 240"><g class="container"><rect style="width:240px;height:240px;fill:#000;"/>{}<circle cx="{}" cy="{}" r="6" style="fill:hsl({},100%,60%);"/><text x="120" y="228" dominant-baseline="middle" text-anchor="middle" style="fill:#fff;font-family:serif;font-size:10px;text-align:center;">[{}, {}, {}]</text></g></svg>"#,
+        stars,
+        20 + coords.x.rem_euclid(201),
+        20 + coords.y.rem_euclid(181),
+        coords.z.rem_euclid(360),
+        coords.x,
+        coords.y,
+        coords.z
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_svg_snapshots() {
+        let snapshots = [
+            (
+                Coordinates { x: 0, y: 0, z: 0 },
+                r#"<svg xmlns="http://www.w3.org/2000/svg" preserveAspectRatio="xMinYMin meet" viewBox="0 0 240 240"><g class="container"><rect style="width:240px;height:240px;fill:#000;"/><circle cx="175" cy="180" r="2" style="fill:#fff;opacity:0.7;"/><circle cx="187" cy="90" r="2" style="fill:#fff;opacity:0.5;"/><circle cx="179" cy="230" r="2" style="fill:#fff;opacity:0.7;"/><circle cx="123" cy="111" r="2" style="fill:#fff;opacity:0.9;"/><circle cx="165" cy="182" r="1" style="fill:#fff;opacity:0.9;"/><circle cx="39" cy="141" r="1" style="fill:#fff;opacity:0.3;"/><circle cx="201" cy="59" r="1" style="fill:#fff;opacity:0.5;"/><circle cx="118" cy="178" r="2" style="fill:#fff;opacity:0.9;"/><circle cx="20" cy="20" r="6" style="fill:hsl(0,100%,60%);"/><text x="120" y="228" dominant-baseline="middle" text-anchor="middle" style="fill:#fff;font-family:serif;font-size:10px;text-align:center;">[0, 0, 0]</text></g></svg>"#,
+            ),
+            (
+                Coordinates { x: 1, y: 2, z: 3 },
+                r#"<svg xmlns="http://www.w3.org/2000/svg" preserveAspectRatio="xMinYMin meet" viewBox="0 0 240 240"><g class="container"><rect style="width:240px;height:240px;fill:#000;"/><circle cx="65" cy="191" r="2" style="fill:#fff;opacity:0.4;"/><circle cx="159" cy="84" r="2" style="fill:#fff;opacity:0.9;"/><circle cx="111" cy="56" r="1" style="fill:#fff;opacity:0.6;"/><circle cx="19" cy="98" r="1" style="fill:#fff;opacity:0.8;"/><circle cx="144" cy="188" r="1" style="fill:#fff;opacity:0.8;"/><circle cx="159" cy="122" r="1" style="fill:#fff;opacity:0.6;"/><circle cx="90" cy="13" r="2" style="fill:#fff;opacity:0.3;"/><circle cx="180" cy="30" r="2" style="fill:#fff;opacity:0.6;"/><circle cx="21" cy="22" r="6" style="fill:hsl(3,100%,60%);"/><text x="120" y="228" dominant-baseline="middle" text-anchor="middle" style="fill:#fff;font-family:serif;font-size:10px;text-align:center;">[1, 2, 3]</text></g></svg>"#,
+            ),
+            (
+                Coordinates {
+                    x: -1000,
+                    y: 500,
+                    z: -1,
+                },
+                r#"<svg xmlns="http://www.w3.org/2000/svg" preserveAspectRatio="xMinYMin meet" viewBox="0 0 240 240"><g class="container"><rect style="width:240px;height:240px;fill:#000;"/><circle cx="207" cy="116" r="1" style="fill:#fff;opacity:0.6;"/><circle cx="91" cy="6" r="2" style="fill:#fff;opacity:0.5;"/><circle cx="60" cy="7" r="1" style="fill:#fff;opacity:0.4;"/><circle cx="17" cy="2" r="1" style="fill:#fff;opacity:0.9;"/><circle cx="224" cy="137" r="1" style="fill:#fff;opacity:0.4;"/><circle cx="204" cy="188" r="2" style="fill:#fff;opacity:0.3;"/><circle cx="172" cy="154" r="2" style="fill:#fff;opacity:0.4;"/><circle cx="167" cy="165" r="1" style="fill:#fff;opacity:0.5;"/><circle cx="25" cy="158" r="6" style="fill:hsl(359,100%,60%);"/><text x="120" y="228" dominant-baseline="middle" text-anchor="middle" style="fill:#fff;font-family:serif;font-size:10px;text-align:center;">[-1000, 500, -1]</text></g></svg>"#,
+            ),
+            (
+                Coordinates {
+                    x: i64::MIN,
+                    y: i64::MAX,
+                    z: i64::MIN,
+                },
+                r#"<svg xmlns="http://www.w3.org/2000/svg" preserveAspectRatio="xMinYMin meet" viewBox="0 0 240 240"><g class="container"><rect style="width:240px;height:240px;fill:#000;"/><circle cx="230" cy="42" r="2" style="fill:#fff;opacity:0.8;"/><circle cx="11" cy="121" r="1" style="fill:#fff;opacity:0.9;"/><circle cx="49" cy="86" r="1" style="fill:#fff;opacity:0.3;"/><circle cx="212" cy="159" r="1" style="fill:#fff;opacity:0.4;"/><circle cx="62" cy="99" r="1" style="fill:#fff;opacity:0.4;"/><circle cx="160" cy="60" r="1" style="fill:#fff;opacity:0.4;"/><circle cx="170" cy="156" r="2" style="fill:#fff;opacity:0.3;"/><circle cx="234" cy="201" r="1" style="fill:#fff;opacity:0.7;"/><circle cx="45" cy="41" r="6" style="fill:hsl(352,100%,60%);"/><text x="120" y="228" dominant-baseline="middle" text-anchor="middle" style="fill:#fff;font-family:serif;font-size:10px;text-align:center;">[-9223372036854775808, 9223372036854775807, -9223372036854775808]</text></g></svg>"#,
+            ),
+        ];
+        for (coords, expected) in snapshots {
+            let svg = token_svg(&coords);
+            assert_eq!(svg, expected);
+            assert!(svg.len() <= MAX_SVG_LEN);
+        }
+    }
+}
@@ -1 +1,3 @@
+pub mod cw20;
+pub mod image;
 pub mod nft;
@@ -1,12 +1,18 @@
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
-use std::convert::TryInto;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::convert::TryFrom;
 
-use cosmwasm_std::{Addr, Binary, Coin, StdError, StdResult, Timestamp, Uint128};
+use cosmwasm_std::{
+    to_binary, Addr, Binary, Coin, CosmosMsg, Order, StdError, StdResult, Timestamp, Uint128,
+    Uint256, WasmMsg,
+};
 use cw721::{Expiration, OwnerOfResponse};
 use cw721_base::msg::{ExecuteMsg as CW721ExecuteMsg, QueryMsg as CW721QueryMsg};
 use cw721_base::state::Approval;
 
+use crate::cw20::{Cw20Coin, Cw20ReceiveMsg};
+use crate::image::token_svg;
+
 // ----------------- begin CW721 ^0.9.2 shim ----------------- //
 
 // adapted from: https://github.com/CosmWasm/cw-nfts/blob/5e1e72a3682f988d4504b94f2e203dd4a5a99ad9/contracts/cw721-metadata-onchain/src/lib.rs#L7-L26
@@ -57,10 +63,10 @@ pub struct Config {
     /// If true, then anyone can mint an xyz token. If false, then only the
     /// contract owner is authorized to mint.
     pub public_minting_enabled: bool,
-    /// The maximum value of a coordinate in any dimension. The minimum
-    /// will be set to the negation of this value.
-    pub max_coordinate_value: i64,
-    /// The maximum allowed number of xyz tokens
+    /// The inclusive range of coordinate values allowed along each axis
+    pub bounds: CoordinateBounds,
+    /// The maximum number of xyz tokens that can be minted, including any
+    /// that have since been burned
     pub token_supply: u64,
     /// The maximum number of tokens a particular wallet can hold
     pub wallet_limit: u32,
@@ -74,37 +80,1061 @@ pub struct Config {
     pub move_nanos_per_step: u64,
     /// The base fee to initiate a move. To get overall move fee:
     ///   base_move_fee.amount + move_fee_per_step * distance
+    /// Moves already in progress or queued when the denom changes are
+    /// expedited and refunded in the denom they were paid in.
     pub base_move_fee: Coin,
     /// The increase in move fee price per marginal step taken, where
     /// a step is a one-dimensional coordinate increment or decrement.
     /// Assumed to be in the denom associated with base_move_fee.
     pub move_fee_per_step: Uint128,
+    /// The maximum amount of energy an xyz can store. Each step of a
+    /// move consumes one unit of energy.
+    #[serde(default = "unlimited_energy")]
+    pub energy_max: u32,
+    /// The number of nanoseconds it takes an xyz to regenerate a single
+    /// unit of energy. If zero, energy regenerates instantly.
+    #[serde(default)]
+    pub energy_regen_nanos: u64,
+    /// If true, mint fees are burned as they are collected instead of
+    /// accumulating in the contract balance for withdrawal. Can't be set
+    /// along with fee_distribution.
+    #[serde(default)]
+    pub burn_mint_fees: bool,
+    /// The number of items returned by paginated queries when no limit
+    /// is given
+    #[serde(default = "default_query_limit")]
+    pub default_query_limit: u32,
+    /// The maximum number of items returned by paginated queries; larger
+    /// requested limits are clamped to this value
+    #[serde(default = "max_query_limit")]
+    pub max_query_limit: u32,
+    /// The highest level an xyz can reach by merging. If zero, merging
+    /// is disabled.
+    #[serde(default)]
+    pub max_merge_level: u32,
+    /// The maximum number of public mints allowed in a single block. Mints
+    /// by the contract owner don't count toward the limit. If None, public
+    /// mints per block are unlimited.
+    #[serde(default)]
+    pub max_public_mints_per_block: Option<u32>,
+    /// If true, any funds sent in excess of a mint or move fee are refunded
+    /// to the sender, including all native funds the owner sends with its
+    /// free mints and moves. Otherwise they're kept in the contract balance.
+    #[serde(default = "default_refund_overpayment")]
+    pub refund_overpayment: bool,
+    /// The fraction of a move's fee refunded when the move is cancelled,
+    /// in basis points. Values above 10000 refund the full fee.
+    #[serde(default)]
+    pub move_cancel_refund_bps: u16,
+    /// The maximum number of moves that can be queued behind a token's
+    /// in-progress move. If zero, moves can't be queued.
+    #[serde(default)]
+    pub max_queued_moves: u32,
+    /// If true, addresses on the allowlist can mint by providing a merkle
+    /// proof, even while public minting is disabled.
+    #[serde(default)]
+    pub allowlist_mint_enabled: bool,
+    /// The price of an allowlist mint. If None, allowlist mints cost the
+    /// same as public mints.
+    #[serde(default)]
+    pub allowlist_mint_fee: Option<Coin>,
+    /// The number of tokens each allowlisted address can mint through the
+    /// allowlist
+    #[serde(default = "default_allowlist_mint_quota")]
+    pub allowlist_mint_quota: u32,
+    /// Mint prices that replace mint_fee as more tokens are minted. Each
+    /// entry is a number of tokens minted and the price from then on, so a
+    /// token is priced by the last tier starting at or below the number of
+    /// tokens minted before it. Tiers must be sorted by strictly increasing
+    /// start and share mint_fee's denom.
+    #[serde(default)]
+    pub mint_fee_tiers: Vec<(u64, Coin)>,
+    /// Prices accepted in place of mint_fee for public mints, any one of
+    /// which pays for the mint. Each must be in a different denom from
+    /// mint_fee and from each other. Alternative prices are per token and
+    /// don't follow mint_fee_tiers.
+    #[serde(default)]
+    pub mint_fee_alternatives: Vec<Coin>,
+    /// The price of a public mint paid in a CW20 token through Receive,
+    /// naming the accepted token contract. If None, mints can't be paid in
+    /// CW20 tokens. The CW20 price is per token and doesn't follow
+    /// mint_fee_tiers.
+    #[serde(default)]
+    pub cw20_mint_fee: Option<Cw20Coin>,
+    /// The move fee paid in a CW20 token through Receive. If None, moves
+    /// can't be paid in CW20 tokens.
+    #[serde(default)]
+    pub cw20_move_fee: Option<Cw20MoveFee>,
+    /// The share of each sale owed as a royalty, in basis points, as reported
+    /// by the cw2981 royalty queries. Must not exceed 10000.
+    #[serde(default)]
+    pub royalty_bps: u16,
+    /// The address royalties are paid to. If None, royalties are paid to the
+    /// contract owner.
+    #[serde(default)]
+    pub royalty_payment_address: Option<String>,
+    /// Recipients that withdrawn funds are divided among, with each
+    /// recipient's share in basis points. If empty, withdrawals go to the
+    /// owner. Shares must total 10000.
+    #[serde(default)]
+    pub fee_split: Vec<(String, u16)>,
+    /// The longest a captcha signature can remain valid, in nanoseconds.
+    /// Signatures whose expiry is further than this past the current block
+    /// time are rejected.
+    #[serde(default = "default_max_captcha_age_nanos")]
+    pub max_captcha_age_nanos: u64,
+    /// If true, token metadata embeds each token's SVG image as a data URI.
+    /// Disabling it shrinks token responses, leaving the image empty.
+    #[serde(default = "default_embed_token_image")]
+    pub embed_token_image: bool,
+    /// Move fee discounts for long-held tokens. Each entry is a number of
+    /// nanoseconds the mover has held the token and the discount on the
+    /// move fee from then on, in basis points. A move gets the largest
+    /// discount whose holding period has been reached.
+    #[serde(default)]
+    pub move_fee_discounts: Vec<(u64, u16)>,
+    /// If true, tokens can teleport, arriving at their destination
+    /// instantly for a premium on the move fee.
+    #[serde(default)]
+    pub teleport_enabled: bool,
+    /// The multiple of the regular move fee charged for a teleport. Must be
+    /// at least 1 while teleporting is enabled.
+    #[serde(default = "default_teleport_fee_multiplier")]
+    pub teleport_fee_multiplier: u32,
+    /// The share of a move's remaining travel time cut by expediting it with
+    /// a payment equal to the fee paid for the move, in basis points.
+    /// Smaller or larger payments cut proportionally less or more time. If
+    /// zero, moves can't be expedited.
+    #[serde(default)]
+    pub expedite_bps: u32,
+    /// The least travel time an expedited move can be left with
+    #[serde(default)]
+    pub expedite_min_remaining_nanos: u64,
+    /// How long a token must wait after arriving from a move before it can
+    /// start another. A move queued behind one in progress still starts on
+    /// arrival, but waits out the cooldown at its departure point before
+    /// stepping, so it arrives that much later. If zero, tokens can move
+    /// again as soon as they arrive.
+    #[serde(default)]
+    pub move_cooldown_nanos: u64,
+    /// The farthest a single move or teleport can travel, by the distance
+    /// the move fee is charged on. Any nonzero limit still leaves every
+    /// coordinate reachable one step at a time. If zero, moves can be any
+    /// distance.
+    #[serde(default)]
+    pub max_move_distance: u64,
+    /// If true, ExecuteMsg::MintFor can't take the recipient past
+    /// wallet_limit, unless the recipient is exempt from it.
+    #[serde(default)]
+    pub mint_for_wallet_limit: bool,
+    /// If true, a token's owner can re-roll its coordinates once, for free,
+    /// within reroll_window_nanos of its mint.
+    #[serde(default)]
+    pub reroll_enabled: bool,
+    /// How long after its mint a token's coordinates can be re-rolled
+    #[serde(default)]
+    pub reroll_window_nanos: u64,
+    /// How the distance of a move is measured, for its fee, travel time and
+    /// energy, and for the distance limits and queries built on them.
+    /// Changing it doesn't affect moves already in progress.
+    #[serde(default)]
+    pub distance_metric: DistanceMetric,
+    /// Whether tokens with a move in progress can't be transferred or sent
+    /// until they arrive. Approvals can still be granted meanwhile.
+    #[serde(default)]
+    pub transfers_locked_during_move: bool,
+    /// When public minting opens. If None, it's open from the start.
+    #[serde(default)]
+    pub mint_start: Option<Timestamp>,
+    /// When public minting closes. If None, it never closes. Must be after
+    /// mint_start if both are set.
+    #[serde(default)]
+    pub mint_end: Option<Timestamp>,
+    /// How long an address must wait after a public mint before it can
+    /// publicly mint again. Mints by the contract owner don't count. If
+    /// zero, addresses can mint as often as the other limits allow.
+    #[serde(default)]
+    pub per_address_mint_cooldown_nanos: u64,
+    /// If true, token owners can set a custom name and description on their
+    /// tokens. While false, profiles can't be set and metadata shows each
+    /// token's generated name and default description.
+    #[serde(default = "default_token_profiles_enabled")]
+    pub token_profiles_enabled: bool,
+    /// The prefix of the token URI reported for tokens minted without one,
+    /// followed by the token's number. If None, such tokens have no token
+    /// URI and their metadata is only available on-chain.
+    #[serde(default)]
+    pub base_token_uri: Option<String>,
+    /// If true, no two tokens can occupy or be moving to the same
+    /// coordinates. While false, mints and moves skip the Claimed check and
+    /// tokens can stack. It can't be set while any coordinates are stacked.
+    #[serde(default = "default_unique_coordinates")]
+    pub unique_coordinates: bool,
+    /// Scales the price of each public mint by the distance of the minted
+    /// coordinates from an anchor point, in every accepted denom and CW20
+    /// token. A fixed allowlist_mint_fee isn't scaled. If None, mint prices
+    /// don't depend on the coordinates.
+    #[serde(default)]
+    pub mint_fee_distance_curve: Option<MintFeeDistanceCurve>,
+    /// The discount on a paid mint, in basis points, when the minted
+    /// coordinates share a face with coordinates occupied by a token the
+    /// minter already owns, so any of the six points at distance 1. Applied
+    /// after mint_fee_distance_curve. Must not exceed 10000.
+    #[serde(default)]
+    pub adjacent_mint_discount_bps: u16,
+    /// Shares of every mint and move fee paid out as the fee is collected,
+    /// each a destination and its share in basis points. Shares are rounded
+    /// down, and the rest is retained by the contract for Withdraw, so
+    /// shares must total at most 10000. Must be empty while burn_mint_fees
+    /// is set, since mint fees are then burned in full.
+    #[serde(default)]
+    pub fee_distribution: Vec<(FeeDestination, u16)>,
+}
+
+/// A partial update to the contract config. Each field left unset keeps its
+/// current value. Optional config fields take a nested option, so that
+/// `null` clears the field while leaving it out keeps it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct ConfigUpdate {
+    pub public_minting_enabled: Option<bool>,
+    pub bounds: Option<CoordinateBounds>,
+    pub token_supply: Option<u64>,
+    pub wallet_limit: Option<u32>,
+    pub mint_fee: Option<Coin>,
+    pub base_move_nanos: Option<u64>,
+    pub move_nanos_per_step: Option<u64>,
+    pub base_move_fee: Option<Coin>,
+    pub move_fee_per_step: Option<Uint128>,
+    pub energy_max: Option<u32>,
+    pub energy_regen_nanos: Option<u64>,
+    pub burn_mint_fees: Option<bool>,
+    pub default_query_limit: Option<u32>,
+    pub max_query_limit: Option<u32>,
+    pub max_merge_level: Option<u32>,
+    #[serde(
+        default,
+        deserialize_with = "present_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_public_mints_per_block: Option<Option<u32>>,
+    pub refund_overpayment: Option<bool>,
+    pub move_cancel_refund_bps: Option<u16>,
+    pub max_queued_moves: Option<u32>,
+    pub allowlist_mint_enabled: Option<bool>,
+    #[serde(
+        default,
+        deserialize_with = "present_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub allowlist_mint_fee: Option<Option<Coin>>,
+    pub allowlist_mint_quota: Option<u32>,
+    pub mint_fee_tiers: Option<Vec<(u64, Coin)>>,
+    pub mint_fee_alternatives: Option<Vec<Coin>>,
+    #[serde(
+        default,
+        deserialize_with = "present_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cw20_mint_fee: Option<Option<Cw20Coin>>,
+    #[serde(
+        default,
+        deserialize_with = "present_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cw20_move_fee: Option<Option<Cw20MoveFee>>,
+    pub royalty_bps: Option<u16>,
+    #[serde(
+        default,
+        deserialize_with = "present_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub royalty_payment_address: Option<Option<String>>,
+    pub fee_split: Option<Vec<(String, u16)>>,
+    pub max_captcha_age_nanos: Option<u64>,
+    pub embed_token_image: Option<bool>,
+    pub move_fee_discounts: Option<Vec<(u64, u16)>>,
+    pub teleport_enabled: Option<bool>,
+    pub teleport_fee_multiplier: Option<u32>,
+    pub expedite_bps: Option<u32>,
+    pub expedite_min_remaining_nanos: Option<u64>,
+    pub move_cooldown_nanos: Option<u64>,
+    pub max_move_distance: Option<u64>,
+    pub mint_for_wallet_limit: Option<bool>,
+    pub reroll_enabled: Option<bool>,
+    pub reroll_window_nanos: Option<u64>,
+    pub distance_metric: Option<DistanceMetric>,
+    pub transfers_locked_during_move: Option<bool>,
+    #[serde(
+        default,
+        deserialize_with = "present_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub mint_start: Option<Option<Timestamp>>,
+    #[serde(
+        default,
+        deserialize_with = "present_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub mint_end: Option<Option<Timestamp>>,
+    pub per_address_mint_cooldown_nanos: Option<u64>,
+    pub token_profiles_enabled: Option<bool>,
+    #[serde(
+        default,
+        deserialize_with = "present_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub base_token_uri: Option<Option<String>>,
+    pub unique_coordinates: Option<bool>,
+    #[serde(
+        default,
+        deserialize_with = "present_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub mint_fee_distance_curve: Option<Option<MintFeeDistanceCurve>>,
+    pub adjacent_mint_discount_bps: Option<u16>,
+    pub fee_distribution: Option<Vec<(FeeDestination, u16)>>,
+}
+
+/// Deserializes a field that's present, even if null, as Some, so that a
+/// null optional field can be told apart from an absent one.
+fn present_option<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+fn unlimited_energy() -> u32 {
+    u32::MAX
+}
+
+fn default_query_limit() -> u32 {
+    10
+}
+
+fn max_query_limit() -> u32 {
+    30
+}
+
+fn default_refund_overpayment() -> bool {
+    true
+}
+
+fn default_allowlist_mint_quota() -> u32 {
+    1
+}
+
+fn default_max_captcha_age_nanos() -> u64 {
+    // 10 minutes
+    600_000_000_000
+}
+
+fn default_embed_token_image() -> bool {
+    true
+}
+
+fn default_teleport_fee_multiplier() -> u32 {
+    1
+}
+
+/// The longest base_token_uri a config can have
+pub const MAX_BASE_TOKEN_URI_LENGTH: usize = 512;
+
+fn default_token_profiles_enabled() -> bool {
+    true
+}
+
+fn default_unique_coordinates() -> bool {
+    true
 }
 
 impl Config {
-    pub fn get_move_fee(&self, start: Coordinates, end: Coordinates) -> Coin {
-        let distance = start.distance(end) as u128;
-        let move_fee_amount =
-            self.base_move_fee.amount.u128() + self.move_fee_per_step.u128() * distance;
-        Coin::new(move_fee_amount, &self.base_move_fee.denom)
+    /// Merges the fields set in update into the config, returning the names
+    /// of the fields whose values changed.
+    pub fn apply_update(&mut self, update: ConfigUpdate) -> Vec<&'static str> {
+        let mut changed = vec![];
+        macro_rules! merge {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = update.$field {
+                        if self.$field != value {
+                            self.$field = value;
+                            changed.push(stringify!($field));
+                        }
+                    }
+                )*
+            };
+        }
+        merge!(
+            public_minting_enabled,
+            bounds,
+            token_supply,
+            wallet_limit,
+            mint_fee,
+            base_move_nanos,
+            move_nanos_per_step,
+            base_move_fee,
+            move_fee_per_step,
+            energy_max,
+            energy_regen_nanos,
+            burn_mint_fees,
+            default_query_limit,
+            max_query_limit,
+            max_merge_level,
+            max_public_mints_per_block,
+            refund_overpayment,
+            move_cancel_refund_bps,
+            max_queued_moves,
+            allowlist_mint_enabled,
+            allowlist_mint_fee,
+            allowlist_mint_quota,
+            mint_fee_tiers,
+            mint_fee_alternatives,
+            cw20_mint_fee,
+            cw20_move_fee,
+            royalty_bps,
+            royalty_payment_address,
+            fee_split,
+            max_captcha_age_nanos,
+            embed_token_image,
+            move_fee_discounts,
+            teleport_enabled,
+            teleport_fee_multiplier,
+            expedite_bps,
+            expedite_min_remaining_nanos,
+            move_cooldown_nanos,
+            max_move_distance,
+            mint_for_wallet_limit,
+            reroll_enabled,
+            reroll_window_nanos,
+            distance_metric,
+            transfers_locked_during_move,
+            mint_start,
+            mint_end,
+            per_address_mint_cooldown_nanos,
+            token_profiles_enabled,
+            base_token_uri,
+            unique_coordinates,
+            mint_fee_distance_curve,
+            adjacent_mint_discount_bps,
+            fee_distribution,
+        );
+        changed
+    }
+
+    /// Returns the image to embed in the metadata of a token at coords, if
+    /// embedded images are enabled.
+    pub fn token_image(&self, coords: &Coordinates) -> Option<String> {
+        if self.embed_token_image {
+            Some(base64_token_image(coords))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the token URI of the given token: the URI it was minted with,
+    /// if any, or else base_token_uri followed by the token's number.
+    pub fn token_uri(&self, token_id: &str, token_uri: Option<&String>) -> Option<String> {
+        match (token_uri, &self.base_token_uri) {
+            (Some(token_uri), _) => Some(token_uri.clone()),
+            (None, Some(base)) => Some(format!("{}{}", base, token_id.trim_start_matches("xyz #"))),
+            (None, None) => None,
+        }
+    }
+
+    /// Returns the number of items a paginated query should return for the
+    /// requested limit.
+    pub fn query_limit(&self, limit: Option<u32>) -> usize {
+        limit
+            .unwrap_or(self.default_query_limit)
+            .min(self.max_query_limit) as usize
+    }
+
+    /// Returns the distance between start and end under distance_metric
+    pub fn distance(&self, start: Coordinates, end: Coordinates) -> u64 {
+        self.distance_metric.distance(start, end)
+    }
+
+    pub fn get_move_fee(&self, start: Coordinates, end: Coordinates) -> StdResult<Coin> {
+        let distance = Uint128::from(self.distance(start, end));
+        let move_fee_amount = self
+            .move_fee_per_step
+            .checked_mul(distance)?
+            .checked_add(self.base_move_fee.amount)?;
+        Ok(Coin {
+            amount: move_fee_amount,
+            denom: self.base_move_fee.denom.clone(),
+        })
+    }
+
+    /// Returns the move fee discount, in basis points, for a token held for
+    /// held_nanos.
+    pub fn move_fee_discount_bps(&self, held_nanos: u64) -> u16 {
+        self.move_fee_discounts
+            .iter()
+            .filter(|(min_held_nanos, _)| *min_held_nanos <= held_nanos)
+            .map(|(_, discount_bps)| *discount_bps)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the move fee between start and end for a token held for
+    /// held_nanos, after any discount for the holding period.
+    pub fn get_discounted_move_fee(
+        &self,
+        start: Coordinates,
+        end: Coordinates,
+        held_nanos: u64,
+    ) -> StdResult<Coin> {
+        let fee = self.get_move_fee(start, end)?;
+        Ok(Coin {
+            amount: apply_discount(fee.amount, self.move_fee_discount_bps(held_nanos)),
+            denom: fee.denom,
+        })
+    }
+
+    /// Returns the fee to teleport from start to end: the regular move fee
+    /// times teleport_fee_multiplier. Holding period discounts don't apply.
+    pub fn get_teleport_fee(&self, start: Coordinates, end: Coordinates) -> StdResult<Coin> {
+        let fee = self.get_move_fee(start, end)?;
+        Ok(Coin {
+            amount: fee
+                .amount
+                .checked_mul(Uint128::from(self.teleport_fee_multiplier))?,
+            denom: fee.denom,
+        })
+    }
+
+    /// Returns the travel time cut from a move with remaining_nanos left by
+    /// paying payment to expedite it, and the amount of the payment charged,
+    /// where move_fee is the fee paid for the move. Time is cut at
+    /// expedite_bps of remaining_nanos per move_fee paid, down to
+    /// expedite_min_remaining_nanos, and only the part of the payment needed
+    /// to reach that minimum is charged.
+    pub fn get_expedite(
+        &self,
+        remaining_nanos: u64,
+        move_fee: Uint128,
+        payment: Uint128,
+    ) -> StdResult<(u64, Uint128)> {
+        let max_cut_nanos = remaining_nanos.saturating_sub(self.expedite_min_remaining_nanos);
+        if max_cut_nanos == 0 || move_fee.is_zero() || self.expedite_bps == 0 {
+            return Ok((0, Uint128::zero()));
+        }
+
+        // the payment needed to cut max_cut_nanos, rounded up
+        let numerator =
+            Uint256::from(max_cut_nanos) * Uint256::from(move_fee) * Uint256::from(10_000u32);
+        let denominator = Uint256::from(remaining_nanos) * Uint256::from(self.expedite_bps);
+        let needed = (numerator + denominator - Uint256::from(1u8)) / denominator;
+        if Uint256::from(payment) >= needed {
+            return Ok((max_cut_nanos, Uint128::try_from(needed)?));
+        }
+
+        let cut_nanos = Uint128::from(remaining_nanos as u128 * self.expedite_bps as u128)
+            .multiply_ratio(payment, move_fee.checked_mul(Uint128::from(10_000u32))?);
+        Ok((cut_nanos.u128() as u64, payment))
+    }
+
+    pub fn get_move_nanos(&self, start: Coordinates, end: Coordinates) -> StdResult<u64> {
+        // a u64 product always fits in u128, so only the sum and the
+        // narrowing back to u64 can overflow
+        let distance = self.distance(start, end) as u128;
+        (self.move_nanos_per_step as u128 * distance)
+            .checked_add(self.base_move_nanos as u128)
+            .and_then(|nanos| u64::try_from(nanos).ok())
+            .ok_or_else(|| StdError::generic_err("move duration overflows"))
+    }
+
+    /// Returns the arrival time of a move from start to end departing at
+    /// departure_time.
+    pub fn get_move_arrival(
+        &self,
+        start: Coordinates,
+        end: Coordinates,
+        departure_time: Timestamp,
+    ) -> StdResult<Timestamp> {
+        departure_time
+            .nanos()
+            .checked_add(self.get_move_nanos(start, end)?)
+            .map(Timestamp::from_nanos)
+            .ok_or_else(|| StdError::generic_err("move arrival overflows"))
     }
 
-    pub fn get_move_nanos(&self, start: Coordinates, end: Coordinates) -> u64 {
-        let distance = start.distance(end) as u64;
-        self.base_move_nanos + self.move_nanos_per_step * distance
+    pub fn get_move_energy(&self, start: Coordinates, end: Coordinates) -> u64 {
+        self.distance(start, end)
     }
 
     pub fn check_bounds(&self, coords: Coordinates) -> StdResult<()> {
-        let min_coordinate_value = -self.max_coordinate_value;
-        if vec![coords.x, coords.y, coords.z]
+        for (axis, value, min, max) in self.bounds.axes(coords) {
+            if value < min || value > max {
+                return Err(StdError::generic_err(format!(
+                    "{} coordinate must be between {} and {}",
+                    axis, min, max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the price of minting a token after the given number of
+    /// tokens have been minted.
+    pub fn mint_fee_at(&self, minted: u64) -> &Coin {
+        self.mint_fee_tiers
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= minted)
+            .map_or(&self.mint_fee, |(_, fee)| fee)
+    }
+
+    /// Returns the total price of minting count tokens after the given
+    /// number of tokens have been minted.
+    pub fn mint_fee_for(&self, minted: u64, count: u32) -> StdResult<Coin> {
+        let mut amount = Uint128::zero();
+        for i in 0..count as u64 {
+            amount = amount.checked_add(self.mint_fee_at(minted.saturating_add(i)).amount)?;
+        }
+        Ok(Coin {
+            denom: self.mint_fee.denom.clone(),
+            amount,
+        })
+    }
+
+    /// Returns every accepted total price of minting count tokens after the
+    /// given number of tokens have been minted, starting with the price in
+    /// mint_fee's denom.
+    pub fn mint_prices_for(&self, minted: u64, count: u32) -> StdResult<Vec<Coin>> {
+        let mut prices = vec![self.mint_fee_for(minted, count)?];
+        for fee in &self.mint_fee_alternatives {
+            prices.push(Coin {
+                denom: fee.denom.clone(),
+                amount: fee.amount.checked_mul(count.into())?,
+            });
+        }
+        Ok(prices)
+    }
+
+    /// Returns the share of the mint price charged for a token minted at
+    /// coordinates, in basis points, under mint_fee_distance_curve.
+    pub fn mint_fee_bps_at(&self, coordinates: Coordinates) -> u32 {
+        self.mint_fee_distance_curve
+            .as_ref()
+            .map_or(10_000, |curve| curve.fee_bps_at(coordinates))
+    }
+
+    /// Returns every accepted total price of minting a token at each of the
+    /// given coordinates after the given number of tokens have been minted,
+    /// as with mint_prices_for, with each token's price scaled by
+    /// mint_fee_distance_curve. Each of mints is the coordinates and whether
+    /// they're adjacent to a token the minter owns.
+    pub fn mint_prices_at(
+        &self,
+        minted: u64,
+        mints: &[(Coordinates, bool)],
+    ) -> StdResult<Vec<Coin>> {
+        // start from zero amounts in each accepted denom
+        let mut prices = self.mint_prices_for(minted, 0)?;
+        for (i, (coords, adjacent)) in mints.iter().enumerate() {
+            let token_prices = self.mint_prices_for(minted.saturating_add(i as u64), 1)?;
+            for (price, token_price) in prices.iter_mut().zip(token_prices) {
+                let amount = self.scale_mint_fee(token_price.amount, *coords, *adjacent)?;
+                price.amount = price.amount.checked_add(amount)?;
+            }
+        }
+        Ok(prices)
+    }
+
+    /// Returns a mint price for a token at coordinates scaled by
+    /// mint_fee_distance_curve, rounded down, less adjacent_mint_discount_bps
+    /// if the coordinates are adjacent to a token the minter owns.
+    pub fn scale_mint_fee(
+        &self,
+        amount: Uint128,
+        coordinates: Coordinates,
+        adjacent: bool,
+    ) -> StdResult<Uint128> {
+        let bps = self.mint_fee_bps_at(coordinates);
+        let amount = amount
+            .checked_mul(bps.into())?
+            .multiply_ratio(1u128, 10_000u128);
+        Ok(match adjacent {
+            true => apply_discount(amount, self.adjacent_mint_discount_bps),
+            false => amount,
+        })
+    }
+
+    /// Returns the number of tokens that can be minted after the given
+    /// number of tokens before the mint price changes or supply runs out.
+    pub fn mints_left_in_tier(&self, minted: u64) -> u64 {
+        let next_tier = self
+            .mint_fee_tiers
+            .iter()
+            .find(|(start, _)| *start > minted)
+            .map_or(self.token_supply, |(start, _)| *start);
+        next_tier.min(self.token_supply).saturating_sub(minted)
+    }
+
+    /// Returns each fee_distribution destination's share of a collected fee
+    /// of amount, rounded down, leaving out zero shares.
+    pub fn fee_distribution_shares(&self, amount: Uint128) -> Vec<(&FeeDestination, Uint128)> {
+        self.fee_distribution
+            .iter()
+            .map(|(destination, bps)| (destination, amount.multiply_ratio(*bps, 10_000u128)))
+            .filter(|(_, share)| !share.is_zero())
+            .collect()
+    }
+
+    /// Returns the part of a collected fee of amount that the contract
+    /// retains after paying out fee_distribution's shares.
+    pub fn retained_fee(&self, amount: Uint128) -> Uint128 {
+        self.fee_distribution_shares(amount)
+            .iter()
+            .fold(amount, |retained, (_, share)| retained - *share)
+    }
+
+    /// Returns the royalty owed on a sale at the given price, rounded down.
+    pub fn royalty_amount(&self, sale_price: Uint128) -> Uint128 {
+        sale_price.multiply_ratio(self.royalty_bps, 10_000u128)
+    }
+
+    /// Checks that the config is internally consistent.
+    pub fn validate(&self) -> StdResult<()> {
+        self.bounds.validate()?;
+        if self.token_supply == 0 {
+            return Err(StdError::generic_err("token_supply must be positive"));
+        }
+        if self.max_query_limit == 0 {
+            return Err(StdError::generic_err("max_query_limit must be positive"));
+        }
+        if self.default_query_limit > self.max_query_limit {
+            return Err(StdError::generic_err(
+                "default_query_limit must not exceed max_query_limit",
+            ));
+        }
+        let fees = [
+            ("mint_fee", Some(&self.mint_fee)),
+            ("base_move_fee", Some(&self.base_move_fee)),
+            ("allowlist_mint_fee", self.allowlist_mint_fee.as_ref()),
+        ];
+        for (name, fee) in fees.iter() {
+            if fee.map_or(false, |fee| fee.denom.is_empty()) {
+                return Err(StdError::generic_err(format!(
+                    "{} denom must not be empty",
+                    name
+                )));
+            }
+        }
+        if self
+            .mint_fee_alternatives
             .iter()
-            .any(|c| c < &min_coordinate_value || c > &self.max_coordinate_value)
+            .any(|fee| fee.denom.is_empty())
         {
-            let error = StdError::generic_err(format!(
-                "coordinate values must be between {} and {}",
-                min_coordinate_value, self.max_coordinate_value
+            return Err(StdError::generic_err(
+                "mint fee alternative denoms must not be empty",
+            ));
+        }
+        // the longest possible move, between opposite corners of the bounds
+        let (min, max) = self.bounds.corners();
+        if self.get_move_fee(min, max).is_err() {
+            return Err(StdError::generic_err(
+                "move fee must not overflow for a move across the bounds",
+            ));
+        }
+        if self.get_move_nanos(min, max).is_err() {
+            return Err(StdError::generic_err(
+                "move duration must not overflow for a move across the bounds",
+            ));
+        }
+        if self.royalty_bps > 10_000 {
+            return Err(StdError::generic_err("royalty_bps must not exceed 10000"));
+        }
+        let fee_split_bps: u64 = self.fee_split.iter().map(|(_, bps)| *bps as u64).sum();
+        if !self.fee_split.is_empty() && fee_split_bps != 10_000 {
+            return Err(StdError::generic_err(
+                "fee split shares must total 10000 bps",
+            ));
+        }
+        let distributed_bps: u64 = self
+            .fee_distribution
+            .iter()
+            .map(|(_, bps)| *bps as u64)
+            .sum();
+        if distributed_bps > 10_000 {
+            return Err(StdError::generic_err(
+                "fee distribution shares must total at most 10000 bps",
+            ));
+        }
+        if self.fee_distribution.iter().any(
+            |(destination, _)| matches!(destination, FeeDestination::Address(address) if address.is_empty()),
+        ) {
+            return Err(StdError::generic_err(
+                "fee distribution addresses must not be empty",
+            ));
+        }
+        if self.burn_mint_fees && !self.fee_distribution.is_empty() {
+            return Err(StdError::generic_err(
+                "fee_distribution must be empty while burn_mint_fees is set",
+            ));
+        }
+        if self
+            .mint_fee_tiers
+            .windows(2)
+            .any(|pair| pair[0].0 >= pair[1].0)
+        {
+            return Err(StdError::generic_err(
+                "mint fee tiers must be sorted by increasing start",
+            ));
+        }
+        if self
+            .mint_fee_tiers
+            .iter()
+            .any(|(_, fee)| fee.denom != self.mint_fee.denom)
+        {
+            return Err(StdError::generic_err(
+                "mint fee tiers must use the mint fee denom",
+            ));
+        }
+        if self
+            .move_fee_discounts
+            .iter()
+            .any(|(_, discount_bps)| *discount_bps > 10_000)
+        {
+            return Err(StdError::generic_err(
+                "move fee discounts must not exceed 10000 bps",
+            ));
+        }
+        if self.adjacent_mint_discount_bps > 10_000 {
+            return Err(StdError::generic_err(
+                "adjacent_mint_discount_bps must not exceed 10000",
+            ));
+        }
+        if let (Some(start), Some(end)) = (self.mint_start, self.mint_end) {
+            if end <= start {
+                return Err(StdError::generic_err("mint_end must be after mint_start"));
+            }
+        }
+        if let Some(base_token_uri) = &self.base_token_uri {
+            if base_token_uri.is_empty() {
+                return Err(StdError::generic_err("base_token_uri must not be empty"));
+            }
+            if base_token_uri.len() > MAX_BASE_TOKEN_URI_LENGTH {
+                return Err(StdError::generic_err(format!(
+                    "base_token_uri must be at most {} bytes",
+                    MAX_BASE_TOKEN_URI_LENGTH
+                )));
+            }
+        }
+        if self.teleport_enabled && self.teleport_fee_multiplier == 0 {
+            return Err(StdError::generic_err(
+                "teleport_fee_multiplier must be at least 1",
+            ));
+        }
+        if let Some(curve) = &self.mint_fee_distance_curve {
+            curve.validate(&self.bounds)?;
+        }
+        let mut denoms = vec![&self.mint_fee.denom];
+        for fee in &self.mint_fee_alternatives {
+            if denoms.contains(&&fee.denom) {
+                return Err(StdError::generic_err(
+                    "mint fee alternatives must each use a different denom",
+                ));
+            }
+            denoms.push(&fee.denom);
+        }
+        Ok(())
+    }
+}
+
+/// Returns amount less discount_bps basis points of it, rounding the
+/// discount down.
+pub fn apply_discount(amount: Uint128, discount_bps: u16) -> Uint128 {
+    amount - amount.multiply_ratio(discount_bps, 10_000u128)
+}
+
+/// Scales mint prices by the Manhattan distance of the minted coordinates
+/// from an anchor point, so that central or outlying coordinates can be
+/// priced higher.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MintFeeDistanceCurve {
+    /// The coordinates distances are measured from
+    pub anchor: Coordinates,
+    /// Each band is the largest distance from the anchor it covers and the
+    /// share of the mint price charged within it, in basis points, so 10000
+    /// leaves the price unchanged. A token is priced by the first band
+    /// covering its distance. Bands must be sorted by strictly increasing
+    /// distance, and the last must cover every in-bounds coordinate.
+    pub bands: Vec<(u64, u32)>,
+}
+
+impl MintFeeDistanceCurve {
+    /// Returns the share of the mint price charged at coordinates, in basis
+    /// points.
+    pub fn fee_bps_at(&self, coordinates: Coordinates) -> u32 {
+        let distance = self.anchor.distance(coordinates);
+        self.bands
+            .iter()
+            .find(|(max_distance, _)| distance <= *max_distance)
+            .map_or(10_000, |(_, fee_bps)| *fee_bps)
+    }
+
+    /// Checks that the bands are sorted and cover every coordinate within
+    /// bounds.
+    pub fn validate(&self, bounds: &CoordinateBounds) -> StdResult<()> {
+        if self.bands.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(StdError::generic_err(
+                "mint fee distance bands must be sorted by increasing distance",
+            ));
+        }
+        // the farthest in-bounds coordinates from the anchor, at the far
+        // end of each axis
+        let (min, max) = bounds.corners();
+        let farthest = |anchor: i64, min: i64, max: i64| {
+            let gap = |end: i64| (anchor as i128 - end as i128).abs();
+            if gap(min) >= gap(max) {
+                min
+            } else {
+                max
+            }
+        };
+        let farthest = Coordinates {
+            x: farthest(self.anchor.x, min.x, max.x),
+            y: farthest(self.anchor.y, min.y, max.y),
+            z: farthest(self.anchor.z, min.z, max.z),
+        };
+        let covered = self.bands.last().map(|(max_distance, _)| *max_distance);
+        if covered.map_or(true, |covered| covered < self.anchor.distance(farthest)) {
+            return Err(StdError::generic_err(
+                "mint fee distance bands must cover every in-bounds distance",
             ));
-            return Err(error);
+        }
+        Ok(())
+    }
+}
+
+/// The native balances withdrawn in full by ExecuteMsg::Withdraw
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WithdrawBalance {
+    /// Every denom the contract holds
+    All,
+    /// Only the named denom
+    Denom(String),
+}
+
+/// Where a share of each collected fee is paid out to
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeDestination {
+    /// Sent to the address
+    Address(String),
+    /// Burned
+    Burn,
+}
+
+/// A move fee paid in a CW20 token. To get overall move fee:
+///   base_fee + fee_per_step * distance
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw20MoveFee {
+    /// The address of the accepted CW20 token contract
+    pub address: String,
+    pub base_fee: Uint128,
+    pub fee_per_step: Uint128,
+}
+
+impl Cw20MoveFee {
+    /// Returns the fee for a move of the given distance
+    pub fn get_fee(&self, distance: u64) -> StdResult<Uint128> {
+        let distance = Uint128::from(distance);
+        Ok(self
+            .fee_per_step
+            .checked_mul(distance)?
+            .checked_add(self.base_fee)?)
+    }
+}
+
+/// The inclusive minimum and maximum coordinate values along each axis
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub struct CoordinateBounds {
+    pub x_min: i64,
+    pub x_max: i64,
+    pub y_min: i64,
+    pub y_max: i64,
+    pub z_min: i64,
+    pub z_max: i64,
+}
+
+impl CoordinateBounds {
+    /// Returns bounds of -max_value to max_value along every axis.
+    pub fn symmetric(max_value: i64) -> Self {
+        CoordinateBounds {
+            x_min: -max_value,
+            x_max: max_value,
+            y_min: -max_value,
+            y_max: max_value,
+            z_min: -max_value,
+            z_max: max_value,
+        }
+    }
+
+    /// Returns each axis' name, the coordinates' value along it, and the
+    /// axis' bounds.
+    fn axes(&self, coords: Coordinates) -> [(&'static str, i64, i64, i64); 3] {
+        [
+            ("x", coords.x, self.x_min, self.x_max),
+            ("y", coords.y, self.y_min, self.y_max),
+            ("z", coords.z, self.z_min, self.z_max),
+        ]
+    }
+
+    /// Returns true if every set of coordinates within other is also within
+    /// these bounds.
+    pub fn contains(&self, other: &CoordinateBounds) -> bool {
+        self.x_min <= other.x_min
+            && self.x_max >= other.x_max
+            && self.y_min <= other.y_min
+            && self.y_max >= other.y_max
+            && self.z_min <= other.z_min
+            && self.z_max >= other.z_max
+    }
+
+    /// Returns the minimum and maximum corners of the bounds.
+    pub fn corners(&self) -> (Coordinates, Coordinates) {
+        (
+            Coordinates {
+                x: self.x_min,
+                y: self.y_min,
+                z: self.z_min,
+            },
+            Coordinates {
+                x: self.x_max,
+                y: self.y_max,
+                z: self.z_max,
+            },
+        )
+    }
+
+    pub fn validate(&self) -> StdResult<()> {
+        let axes = [
+            ("x", self.x_min, self.x_max),
+            ("y", self.y_min, self.y_max),
+            ("z", self.z_min, self.z_max),
+        ];
+        for (axis, min, max) in axes {
+            if min > max {
+                return Err(StdError::generic_err(format!(
+                    "{}_min must not exceed {}_max",
+                    axis, axis
+                )));
+            }
+            // keeps every bound safe to negate, as in reflecting coordinates
+            if min == i64::MIN {
+                return Err(StdError::generic_err(format!(
+                    "{}_min must be greater than {}",
+                    axis,
+                    i64::MIN
+                )));
+            }
         }
         Ok(())
     }
@@ -118,28 +1148,182 @@ pub struct Coordinates {
 }
 
 impl Coordinates {
+    /// Encodes the coordinates as 24 bytes whose byte-wise ordering matches
+    /// the (x, y, z) ordering of the coordinates, so coordinate-keyed
+    /// storage can be range scanned. Each component is written big-endian
+    /// with its sign bit flipped, so negative values sort before positive.
     pub fn to_bytes(&self) -> Vec<u8> {
-        vec![
-            self.x.to_be_bytes(),
-            self.y.to_be_bytes(),
-            self.z.to_be_bytes(),
-        ]
-        .concat()
+        let encode = |v: i64| ((v as u64) ^ (1 << 63)).to_be_bytes();
+        [encode(self.x), encode(self.y), encode(self.z)].concat()
+    }
+
+    /// Decodes coordinates encoded by to_bytes.
+    pub fn from_bytes(bytes: &[u8]) -> StdResult<Self> {
+        if bytes.len() != 24 {
+            return Err(StdError::generic_err("invalid coordinate bytes"));
+        }
+        let decode = |chunk: &[u8]| {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(chunk);
+            (u64::from_be_bytes(buf) ^ (1 << 63)) as i64
+        };
+        Ok(Coordinates {
+            x: decode(&bytes[0..8]),
+            y: decode(&bytes[8..16]),
+            z: decode(&bytes[16..24]),
+        })
     }
 
+    /// Returns the Manhattan distance between two points, saturating at
+    /// u64::MAX for points at opposite extremes of the i64 range.
     pub fn distance(&self, other: Self) -> u64 {
-        let distance =
-            (self.x - other.x).abs() + (self.y - other.y).abs() + (self.z - other.z).abs();
-        // the distance will always be positive, since it's a sum of absolute values
-        distance.try_into().unwrap()
+        DistanceMetric::Manhattan.distance(*self, other)
+    }
+
+    /// Returns the point offset from self by each of offset's components, or
+    /// None if any component overflows.
+    pub fn checked_translate(&self, offset: Self) -> Option<Self> {
+        Some(Coordinates {
+            x: self.x.checked_add(offset.x)?,
+            y: self.y.checked_add(offset.y)?,
+            z: self.z.checked_add(offset.z)?,
+        })
+    }
+
+    /// Returns the points sharing a face with self, one step away along a
+    /// single axis, leaving out any that overflow.
+    pub fn face_neighbors(&self) -> Vec<Self> {
+        let steps = [
+            (1, 0, 0),
+            (-1, 0, 0),
+            (0, 1, 0),
+            (0, -1, 0),
+            (0, 0, 1),
+            (0, 0, -1),
+        ];
+        steps
+            .iter()
+            .filter_map(|&(x, y, z)| self.checked_translate(Coordinates { x, y, z }))
+            .collect()
+    }
+
+    /// Returns the point reached after taking the given number of steps from
+    /// self toward other, travelling along the x axis first, then y, then z.
+    pub fn step_toward(&self, other: Self, steps: u64) -> Self {
+        let mut remaining = steps as i128;
+        let mut step_axis = |from: i64, to: i64| {
+            let delta = to as i128 - from as i128;
+            let taken = delta.abs().min(remaining);
+            remaining -= taken;
+            (from as i128 + taken * delta.signum()) as i64
+        };
+        Coordinates {
+            x: step_axis(self.x, other.x),
+            y: step_axis(self.y, other.y),
+            z: step_axis(self.z, other.z),
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Copy)]
+/// A way of measuring the distance between two points
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceMetric {
+    /// The sum of the distances along each axis
+    #[default]
+    Manhattan,
+    /// The largest distance along any one axis, so that diagonal moves
+    /// cost no more than moves along a single axis
+    Chebyshev,
+    /// The straight-line distance, rounded up to a whole number of steps
+    EuclideanRounded,
+}
+
+impl DistanceMetric {
+    /// Returns the distance between two points, saturating at u64::MAX.
+    pub fn distance(&self, a: Coordinates, b: Coordinates) -> u64 {
+        let delta = |from: i64, to: i64| (from as i128 - to as i128).unsigned_abs();
+        let deltas = [delta(a.x, b.x), delta(a.y, b.y), delta(a.z, b.z)];
+        let distance: u128 = match self {
+            DistanceMetric::Manhattan => deltas.iter().sum(),
+            DistanceMetric::Chebyshev => deltas.iter().copied().max().unwrap_or(0),
+            DistanceMetric::EuclideanRounded => {
+                let squared = deltas.iter().fold(0u128, |sum, delta| {
+                    sum.saturating_add(delta.saturating_mul(*delta))
+                });
+                let root = isqrt(squared);
+                if root * root < squared {
+                    root + 1
+                } else {
+                    root
+                }
+            }
+        };
+        u64::try_from(distance).unwrap_or(u64::MAX)
+    }
+}
+
+/// Returns the square root of n, rounded down.
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    // Newton's method descends from any estimate at or above the root, and
+    // a power of two covering half of n's bits is one
+    let mut root = 1u128 << ((128 - n.leading_zeros() + 1) / 2);
+    loop {
+        let next = (root + n / root) / 2;
+        if next >= root {
+            return root;
+        }
+        root = next;
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct XyzExtension {
     pub coordinates: Coordinates,
     pub prev_coordinates: Option<Coordinates>,
     pub arrival: Timestamp,
+    /// The xyz's stored energy as of energy_updated_at, before regeneration.
+    #[serde(default = "unlimited_energy")]
+    pub energy: u32,
+    #[serde(default)]
+    pub energy_updated_at: Timestamp,
+    /// The number of xyz that have been merged into this one.
+    #[serde(default)]
+    pub level: u32,
+    /// A URI pointing to off-chain metadata for the xyz.
+    #[serde(default)]
+    pub token_uri: Option<String>,
+    /// The block time at which the xyz was minted. Tokens minted before
+    /// this was recorded carry the time they were migrated instead.
+    #[serde(default)]
+    pub minted_at: Timestamp,
+    /// The block height at which the xyz was minted, or for tokens minted
+    /// before this was recorded, the height they were migrated at.
+    #[serde(default)]
+    pub minted_height: u64,
+    /// True once the xyz's coordinates have been re-rolled. Tokens minted
+    /// before re-rolls existed are marked as re-rolled, since their mint
+    /// time is unknown.
+    #[serde(default)]
+    pub rerolled: bool,
+    /// The name the token's owner gave it, shown in its metadata in place
+    /// of the generated name
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The description the token's owner gave it, shown in its metadata in
+    /// place of the default description
+    #[serde(default)]
+    pub description: Option<String>,
+    /// The number of moves the xyz has started, including teleports and
+    /// moves later cancelled. A queued move counts once it sets off.
+    #[serde(default)]
+    pub moves_count: u64,
+    /// The total distance covered by the moves in moves_count
+    #[serde(default)]
+    pub total_distance: u64,
 }
 
 impl XyzExtension {
@@ -147,7 +1331,107 @@ impl XyzExtension {
         self.arrival <= current_time
     }
 
-    pub fn as_traits(&self) -> Vec<Cw721Trait> {
+    /// Counts a move from the xyz's current coordinates to destination in
+    /// its travel stats.
+    pub fn record_move(&mut self, config: &Config, destination: Coordinates) {
+        let distance = config.distance(self.coordinates, destination);
+        self.moves_count = self.moves_count.saturating_add(1);
+        self.total_distance = self.total_distance.saturating_add(distance);
+    }
+
+    /// Returns the xyz's energy at the given time, including any energy
+    /// regenerated since it was last spent, capped at config.energy_max.
+    pub fn current_energy(&self, config: &Config, current_time: Timestamp) -> u32 {
+        if config.energy_regen_nanos == 0 {
+            return config.energy_max;
+        }
+        let elapsed_nanos = current_time
+            .nanos()
+            .saturating_sub(self.energy_updated_at.nanos());
+        let regenerated = elapsed_nanos / config.energy_regen_nanos;
+        let energy = (self.energy as u64).saturating_add(regenerated);
+        energy.min(config.energy_max as u64) as u32
+    }
+
+    /// Returns how long an in-progress move spends stepping toward its
+    /// destination: move_nanos_per_step for each unit of its distance under
+    /// config.distance_metric.
+    fn stepping_nanos(&self, config: &Config, prev_coordinates: Coordinates) -> u64 {
+        let distance = config.distance(prev_coordinates, self.coordinates);
+        config.move_nanos_per_step.saturating_mul(distance)
+    }
+
+    /// Returns the time at which an in-progress move finishes waiting out
+    /// base_move_nanos at its starting point and begins stepping toward its
+    /// destination, taking the steps of its Manhattan path at an even pace
+    /// over stepping_nanos until it arrives. Under the Manhattan metric,
+    /// that's one step every move_nanos_per_step. The departure isn't
+    /// stored, so this is derived from arrival using the given config; moves
+    /// started under a different config are approximated.
+    fn stepping_from(&self, config: &Config, prev_coordinates: Coordinates) -> u64 {
+        self.arrival
+            .nanos()
+            .saturating_sub(self.stepping_nanos(config, prev_coordinates))
+    }
+
+    fn steps_taken(&self, config: &Config, prev_coordinates: Coordinates, time: Timestamp) -> u64 {
+        let steps = prev_coordinates.distance(self.coordinates);
+        let stepping_from = self.stepping_from(config, prev_coordinates);
+        if time.nanos() < stepping_from {
+            return 0;
+        }
+        // with no stepping time, every step is taken at once
+        let elapsed = (time.nanos() - stepping_from) as u128;
+        match (elapsed * steps as u128)
+            .checked_div(self.stepping_nanos(config, prev_coordinates) as u128)
+        {
+            Some(steps_taken) => steps_taken.min(steps as u128) as u64,
+            None => steps,
+        }
+    }
+
+    /// Returns the xyz's position at the given time. In-progress moves are
+    /// interpolated along a Manhattan path, stepping along the x axis first,
+    /// then y, then z (see stepping_from for the timing of each step).
+    /// Arrived xyz are stationary at their coordinates.
+    pub fn position_at(&self, config: &Config, time: Timestamp) -> Coordinates {
+        match self.prev_coordinates {
+            Some(prev) if !self.has_arrived(time) => {
+                prev.step_toward(self.coordinates, self.steps_taken(config, prev, time))
+            }
+            _ => self.coordinates,
+        }
+    }
+
+    /// Returns the first time after the given time at which the xyz's
+    /// position_at changes, or None if it won't move again.
+    pub fn next_step_at(&self, config: &Config, time: Timestamp) -> Option<Timestamp> {
+        match self.prev_coordinates {
+            Some(prev) if !self.has_arrived(time) => {
+                let stepping_from = self.stepping_from(config, prev);
+                let steps = prev.distance(self.coordinates) as u128;
+                let steps_taken = self.steps_taken(config, prev, time) as u128;
+                // the first time at which steps_taken reaches the next step
+                let stepping_nanos = self.stepping_nanos(config, prev) as u128;
+                let next_step_nanos = match ((steps_taken + 1) * stepping_nanos).checked_div(steps)
+                {
+                    Some(nanos) if nanos * steps < (steps_taken + 1) * stepping_nanos => nanos + 1,
+                    Some(nanos) => nanos,
+                    None => 0,
+                };
+                let next_step_at = (stepping_from as u128).saturating_add(next_step_nanos);
+                Some(Timestamp::from_nanos(
+                    next_step_at.min(self.arrival.nanos() as u128) as u64,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the xyz's metadata attributes as of the given time: its
+    /// coordinates, whether it's in transit, its arrival and mint times in
+    /// seconds, displayed as dates, its mint height, and its travel stats.
+    pub fn as_traits(&self, current_time: Timestamp) -> Vec<Cw721Trait> {
         vec![
             Cw721Trait {
                 display_type: None,
@@ -164,29 +1448,64 @@ impl XyzExtension {
                 trait_type: "z".to_string(),
                 value: self.coordinates.z.to_string(),
             },
-        ]
-    }
-}
-
-#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
-pub struct XyzTokenInfo {
-    pub owner: Addr,
-    pub approvals: Vec<Approval>,
-    pub name: String,
-    pub description: String,
-    pub image: Option<String>,
-    pub extension: XyzExtension,
+            Cw721Trait {
+                display_type: None,
+                trait_type: "in_transit".to_string(),
+                value: (!self.has_arrived(current_time)).to_string(),
+            },
+            Cw721Trait {
+                display_type: Some("date".to_string()),
+                trait_type: "arrival".to_string(),
+                value: self.arrival.seconds().to_string(),
+            },
+            Cw721Trait {
+                display_type: Some("date".to_string()),
+                trait_type: "minted_at".to_string(),
+                value: self.minted_at.seconds().to_string(),
+            },
+            Cw721Trait {
+                display_type: None,
+                trait_type: "minted_height".to_string(),
+                value: self.minted_height.to_string(),
+            },
+            Cw721Trait {
+                display_type: Some("number".to_string()),
+                trait_type: "moves_count".to_string(),
+                value: self.moves_count.to_string(),
+            },
+            Cw721Trait {
+                display_type: Some("number".to_string()),
+                trait_type: "total_distance".to_string(),
+                value: self.total_distance.to_string(),
+            },
+        ]
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct XyzTokenInfo {
+    pub owner: Addr,
+    pub approvals: Vec<Approval>,
+    pub name: String,
+    pub description: String,
+    pub image: Option<String>,
+    pub extension: XyzExtension,
 }
 
 impl XyzTokenInfo {
-    pub fn as_cw721_nft_info(&self) -> Cw721NftInfoResponse {
+    /// Returns the token's ERC721-style metadata as of the given time.
+    pub fn as_cw721_nft_info(
+        &self,
+        config: &Config,
+        current_time: Timestamp,
+    ) -> Cw721NftInfoResponse {
         Cw721NftInfoResponse {
-            token_uri: None,
+            token_uri: config.token_uri(&self.name, self.extension.token_uri.as_ref()),
             extension: Cw721Metadata {
-                name: Some(self.name.clone()),
-                image: Some(self.base64_token_image()),
-                description: Some(self.description.clone()),
-                attributes: Some(self.extension.as_traits()),
+                name: Some(self.display_name(config).to_string()),
+                image: config.token_image(&self.extension.coordinates),
+                description: Some(self.display_description(config).to_string()),
+                attributes: Some(self.extension.as_traits(current_time)),
                 image_data: None,
                 external_url: None,
                 animation_url: None,
@@ -199,15 +1518,32 @@ impl XyzTokenInfo {
     pub fn base64_token_image(&self) -> String {
         base64_token_image(&self.extension.coordinates)
     }
+
+    /// Returns the name the owner set, or the generated name if they haven't
+    /// set one or token profiles are disabled.
+    pub fn display_name(&self, config: &Config) -> &str {
+        match &self.extension.name {
+            Some(name) if config.token_profiles_enabled => name,
+            _ => &self.name,
+        }
+    }
+
+    /// Returns the description the owner set, or the default description if
+    /// they haven't set one or token profiles are disabled.
+    pub fn display_description(&self, config: &Config) -> &str {
+        match &self.extension.description {
+            Some(description) if config.token_profiles_enabled => description,
+            _ => &self.description,
+        }
+    }
 }
 
+/// Returns a token's image as a base64-encoded SVG data URI.
 pub fn base64_token_image(coords: &Coordinates) -> String {
-    let svg = format!(
-        r#"<svg xmlns="http://www.w3.org/2000/svg" preserveAspectRatio="xMinYMin meet" viewBox="0 0 240 240"><g class="container"><rect style="width:240px;height:240px;fill:#000;"/><text x="120" y="120" dominant-baseline="middle" text-anchor="middle" style="fill:#fff;font-family:serif;font-size:16px;text-align:center;">[{}, {}, {}]</text></g></svg>"#,
-        coords.x, coords.y, coords.z
-    );
-    let base64_uri = format!("data:image/svg+xml;base64,{}", base64::encode(svg));
-    base64_uri
+    format!(
+        "data:image/svg+xml;base64,{}",
+        base64::encode(token_svg(coords))
+    )
 }
 
 pub fn full_token_id(numeric_token_id: String) -> StdResult<String> {
@@ -232,37 +1568,415 @@ pub fn numeric_token_id(full_token_id: String) -> StdResult<String> {
 #[serde(rename_all = "snake_case")]
 pub struct InstantiateMsg {
     pub captcha_public_key: String,
+    /// The signature scheme of captcha_public_key
+    #[serde(default)]
+    pub captcha_key_algorithm: CaptchaKeyAlgorithm,
     pub config: Config,
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MintRequest {
+    pub coordinates: Coordinates,
+    pub captcha_signature: String,
+    pub nonce: u64,
+    pub expiry: Timestamp,
+}
+
+/// The message the captcha signer signs to authorize sender minting an xyz
+/// at coordinates. Each nonce can be used once per sender, and the signature
+/// is rejected once the block time passes expiry.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CaptchaPayload {
+    pub sender: String,
+    /// None for MintRandom, where the contract picks the coordinates
+    pub coordinates: Option<Coordinates>,
+    pub nonce: u64,
+    pub expiry: Timestamp,
+}
+
 /// This overrides the ExecuteMsg enum defined in cw721-base
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
     /// Mint a new NFT for the message sender with the given set of coordinates and signature
     /// from the recaptcha verifier lambda function.
+    /// Only the contract owner may set a token_uri.
+    /// The signature must be over the CaptchaPayload for the sender,
+    /// coordinates, nonce, and expiry, and can't be reused.
+    /// If proof is given, the mint is an allowlist mint, and proof must be
+    /// a merkle proof (hex-encoded sibling hashes, leaf to root) that the
+    /// sender's address is on the allowlist.
     Mint {
         coordinates: Coordinates,
         captcha_signature: String,
+        nonce: u64,
+        expiry: Timestamp,
+        token_uri: Option<String>,
+        proof: Option<Vec<String>>,
     },
-    /// Move an existing NFT to the given set of coordinates.
+    /// Mint a new NFT for the message sender at free coordinates picked by
+    /// the contract, charged and limited as with Mint. The signature must be
+    /// over the CaptchaPayload for the sender, nonce, and expiry with no
+    /// coordinates. The first point tried is derived from the block, the
+    /// sender, and the number of tokens minted; if it's taken, the following
+    /// points are tried in order, and the mint fails if none of the first
+    /// 64 are free. The response's "x", "y", and "z" attributes give the
+    /// coordinates minted.
+    MintRandom {
+        captcha_signature: String,
+        nonce: u64,
+        expiry: Timestamp,
+    },
+    /// Mint several new NFTs for the message sender in one transaction. The
+    /// mint fee is charged once per entry, and if any entry fails, none of
+    /// the entries are minted.
+    BatchMint {
+        mints: Vec<MintRequest>,
+    },
+    /// Mint a new NFT at the given coordinates for the recipient, without a
+    /// captcha signature or fee, even while public minting is disabled.
+    /// Reserved coordinates can be minted, and the recipient's wallet limit
+    /// only applies if Config::mint_for_wallet_limit is set. Only the
+    /// contract owner can mint for others.
+    MintFor {
+        recipient: String,
+        coordinates: Coordinates,
+    },
+    /// Move an existing NFT to the given set of coordinates. If the NFT is
+    /// already moving, the move is queued to start once the NFT arrives,
+    /// up to Config::max_queued_moves, and its fee is charged upfront.
+    /// While Config::move_cooldown_nanos is set, the NFT must wait out the
+    /// cooldown after arriving before it can move again, and a queued move
+    /// waits it out before setting off. Moves can't
+    /// travel farther than Config::max_move_distance. The NFT's owner, an
+    /// unexpired approved spender, an unexpired operator for its owner, or
+    /// its unexpired pilot can move it; the fee is paid by the sender.
+    /// The response attributes are listed in move_attributes.
     Move {
         token_id: String,
         coordinates: Coordinates,
     },
+    /// Move a newly minted NFT to the given set of coordinates instantly and
+    /// for free, as allowed by Config::reroll_enabled. Each NFT can be
+    /// re-rolled once, within Config::reroll_window_nanos of its mint, and
+    /// only if it's never been transferred or moved. Reserved coordinates
+    /// can't be re-rolled onto. The response attributes are listed in
+    /// move_attributes, with the action "reroll_coordinates".
+    RerollCoordinates {
+        token_id: String,
+        coordinates: Coordinates,
+    },
+    /// Move an NFT to the given set of coordinates instantly, for the move
+    /// fee times Config::teleport_fee_multiplier. The NFT still spends
+    /// energy, waits out the cooldown and is held to
+    /// Config::max_move_distance as for a move. Rejected while the NFT is
+    /// moving.
+    /// The response attributes are listed in move_attributes.
+    Teleport {
+        token_id: String,
+        coordinates: Coordinates,
+    },
+    /// Move several NFTs that aren't moving to distinct coordinates in one
+    /// transaction, with the same checks as Move for each. The attached funds
+    /// must cover the sum of the moves' fees, and if any move fails, none of
+    /// them are made. The response's move_attributes::TOKEN_ID and
+    /// move_attributes::ARRIVAL attributes are repeated for each move, in
+    /// order, and its fee attributes give the total fee.
+    MoveMany {
+        moves: Vec<(String, Coordinates)>,
+    },
+    /// Move several NFTs that aren't moving by the same offset, keeping their
+    /// arrangement, as with MoveMany. A destination may be the current
+    /// coordinates of another NFT in the formation, since the tokens are
+    /// moved leading edge first so each vacates its coordinates before the
+    /// token behind it arrives; any other occupied or queued destination
+    /// fails with Claimed. Every move departs at the same time, and the
+    /// response attributes follow MoveMany's, in the order the tokens moved.
+    MoveFormation {
+        token_ids: Vec<String>,
+        offset: Coordinates,
+    },
+    /// Propose swapping the coordinates of my_token_id, which the sender must
+    /// own, with their_token_id's. The proposer's leg of the swap is paid
+    /// for now, as with Move, and its fee held in escrow until the proposal
+    /// is accepted or cancelled. The proposal can't be accepted once expires
+    /// passes, or once either token changes owner or moves.
+    ProposeSwap {
+        my_token_id: String,
+        their_token_id: String,
+        expires: Option<Expiration>,
+    },
+    /// Accept a swap proposal, paying for this leg of the swap as with Move.
+    /// Only the owner of the proposal's their_token_id can accept. Both
+    /// tokens start moving toward each other's coordinates at once, so
+    /// neither point is ever free for another token to claim. Accepting an
+    /// expired or invalidated proposal refunds its escrow to the proposer
+    /// instead.
+    AcceptSwap {
+        proposal_id: u64,
+    },
+    /// Cancel a swap proposal, refunding its escrow to the proposer. The
+    /// proposer can cancel at any time, and anyone can cancel a proposal
+    /// that's expired or invalidated.
+    CancelSwap {
+        proposal_id: u64,
+    },
+    /// Pay to cut the remaining travel time of an in-progress move, as set
+    /// by Config::expedite_bps. The payment must be in the denom of the fee
+    /// paid for the move, and any part of it not needed to reach
+    /// Config::expedite_min_remaining_nanos is treated as overpayment. The
+    /// payment isn't refunded if the move is later cancelled. Moves made by
+    /// the contract owner or paid in CW20 tokens can't be expedited.
+    ExpediteMove {
+        token_id: String,
+    },
+    /// Cancel an in-progress move, returning the NFT to the coordinates it
+    /// departed from and refunding Config::move_cancel_refund_bps of the fee
+    /// paid for the move. Any queued moves are cleared and their fees are
+    /// refunded in full. Each refund goes to whoever paid the fee.
+    CancelMove {
+        token_id: String,
+    },
+    /// Set the name and description shown in the token's metadata. Only the
+    /// token's owner can set them, and they're kept when the token is
+    /// transferred. Fields left unset keep their current value, and empty
+    /// strings restore the generated name or default description. Control
+    /// characters are removed.
+    SetTokenProfile {
+        token_id: String,
+        name: Option<String>,
+        description: Option<String>,
+    },
+    /// List the token for sale at price. Only the token's owner can list it,
+    /// and listing again replaces the price. The token stays with its owner
+    /// until it's bought, and the listing is removed if the token is
+    /// transferred or burned.
+    ListForSale {
+        token_id: String,
+        price: Coin,
+    },
+    /// Remove the token's listing. Only the token's owner can cancel it.
+    CancelListing {
+        token_id: String,
+    },
+    /// Buy a listed token, paying its price. The price less the royalty
+    /// owed under Config::royalty_bps goes to the seller, the token moves to
+    /// the buyer with its approvals cleared, and any overpayment is refunded
+    /// if Config::refund_overpayment allows.
+    Buy {
+        token_id: String,
+    },
+    /// Offer to buy the token for the funds sent, which must be in a single
+    /// denom. The funds are held in escrow until the offer is accepted or
+    /// withdrawn, and the offer can't be accepted once expires passes. A
+    /// bidder has at most one offer per token, so a new offer replaces the
+    /// old one and refunds its escrow.
+    MakeOffer {
+        token_id: String,
+        expires: Option<Expiration>,
+    },
+    /// Withdraw bidder's offer on the token, refunding its escrow to the
+    /// bidder. bidder defaults to the sender, and anyone can withdraw an
+    /// expired offer on the bidder's behalf.
+    WithdrawOffer {
+        token_id: String,
+        bidder: Option<String>,
+    },
+    /// Accept bidder's offer on the token. Only the token's owner can accept.
+    /// The escrow less the royalty owed under Config::royalty_bps goes to
+    /// the owner, and the token moves to the bidder with its approvals
+    /// cleared. Accepting an expired offer refunds it to the bidder instead.
+    AcceptOffer {
+        token_id: String,
+        bidder: String,
+    },
+    /// Let pilot move the token until expires, as with Move, without being
+    /// able to transfer, approve or burn it. A token has at most one pilot,
+    /// so granting again replaces the pilot, and the grant is cleared when
+    /// the token changes hands. Only the token's owner can grant a pilot.
+    GrantPilot {
+        token_id: String,
+        pilot: String,
+        expires: Expiration,
+    },
+    /// Revoke the token's pilot grant. Only the token's owner can revoke it.
+    RevokePilot {
+        token_id: String,
+    },
+    /// Finalize a move the token has arrived from, clearing its
+    /// prev_coordinates, starting any queued moves that have come due, and
+    /// notifying hooks of the arrival. Anyone can finalize a move once the
+    /// token arrives, so keepers can report arrivals as they happen.
+    FinalizeMove {
+        token_id: String,
+    },
+    /// Burn token_id_burn, freeing its coordinates, and increment the level
+    /// of token_id_keep. The sender must own both tokens, which must be
+    /// adjacent and not in transit.
+    Merge {
+        token_id_keep: String,
+        token_id_burn: String,
+    },
+    /// Burn an NFT, freeing its coordinates. The sender must own or be
+    /// approved to send the token, and the token must not be in transit.
+    Burn {
+        token_id: String,
+    },
+    /// Set or clear the metadata URI of an existing NFT. Only the contract
+    /// owner may set token URIs.
+    SetTokenUri {
+        token_id: String,
+        token_uri: Option<String>,
+    },
 
-    /// Update token minting and supply configuration.
+    /// Replace the whole contract config. The update is rejected if it would
+    /// strand existing tokens: lowering token_supply below the number
+    /// minted, narrowing bounds past a token's position or move, or lowering
+    /// wallet_limit below a limited wallet's holdings. Moves in flight when
+    /// the move fee changes keep the fee they paid, and are refunded in its
+    /// denomination if cancelled. Meant for migrations; use
+    /// UpdateConfigPartial to tweak live parameters.
     UpdateConfig {
         config: Config,
     },
-    /// Update public key used for captcha verification.
+    /// Update only the config fields set in config, keeping the rest, with
+    /// the same validation as UpdateConfig. The response lists the changed
+    /// fields in a "changed" attribute, and each changed field's new value
+    /// as JSON in an attribute named after the field.
+    UpdateConfigPartial {
+        config: ConfigUpdate,
+    },
+    /// Update public key used for captcha verification, replacing every
+    /// active captcha public key with this one.
     UpdateCaptchaPublicKey {
         public_key: String,
+        #[serde(default)]
+        algorithm: CaptchaKeyAlgorithm,
+    },
+    /// Add a public key used for captcha verification, alongside the keys
+    /// already active. Signatures under the key are accepted until expires,
+    /// if given. Adding a key that's already active updates its expiry.
+    AddCaptchaPublicKey {
+        public_key: String,
+        #[serde(default)]
+        algorithm: CaptchaKeyAlgorithm,
+        expires: Option<Expiration>,
+    },
+    /// Remove a captcha public key, so that signatures under it are no
+    /// longer accepted. The last active key can't be removed.
+    RemoveCaptchaPublicKey {
+        public_key: String,
+        #[serde(default)]
+        algorithm: CaptchaKeyAlgorithm,
+    },
+    /// Set the hex-encoded root of the allowlist merkle tree, or clear it
+    /// with None. Each leaf is the sha256 hash of an allowlisted address,
+    /// and each parent is the sha256 hash of its two children concatenated
+    /// in ascending order.
+    UpdateAllowlistMerkleRoot {
+        merkle_root: Option<String>,
+    },
+    /// Propose address as the next contract owner. The current owner keeps
+    /// the role until address accepts it with AcceptOwnership, which must
+    /// happen before expires, if given. Replaces any earlier proposal.
+    ProposeNewOwner {
+        address: String,
+        expires: Option<Expiration>,
+    },
+    /// Accept a pending ownership proposal, becoming the contract owner.
+    /// Only the proposed owner may accept.
+    AcceptOwnership {},
+    /// Withdraw the pending ownership proposal. Only the current owner may
+    /// revoke it.
+    RevokeOwnershipProposal {},
+    /// Permanently disable every message that changes contract parameters:
+    /// UpdateConfig, UpdateConfigPartial, the captcha public key messages,
+    /// UpdateAllowlistMerkleRoot, SetTokenUri, the hook messages, the
+    /// coordinate reservation messages, and SetWalletLimitExemption.
+    /// Withdraw, owner mints, and ownership transfers keep working. There's
+    /// no way to unfreeze. Only the owner may freeze the config.
+    FreezeConfig {},
+    /// Permanently give up contract ownership, disabling every owner-gated
+    /// message, including Withdraw, and freezing the config. Royalties that
+    /// went to the owner keep going to the renouncing owner. Only the owner
+    /// may renounce ownership.
+    RenounceOwnership {},
+    /// Pause the given actions, or every pausable action if none are given,
+    /// replacing any earlier pause. Paused actions fail for everyone,
+    /// including the owner, until unpaused. Only the owner may pause.
+    Pause {
+        actions: Option<Vec<PausableAction>>,
+    },
+    /// Resume every paused action. Only the owner may unpause.
+    Unpause {},
+    /// Register a contract to be sent an XyzHookMsg whenever a token is
+    /// minted, moved, transferred, sent, or burned. Only the owner may add
+    /// hooks.
+    AddHook {
+        addr: String,
+    },
+    /// Stop sending XyzHookMsgs to a registered contract. Only the owner may
+    /// remove hooks.
+    RemoveHook {
+        addr: String,
     },
-    /// Withdraw from current contract balance to owner address.
+    /// Set aside the given coordinates so that only the owner can mint them
+    /// or move tokens onto them, each until its expiry if it has one, or
+    /// permanently if not. Expired reservations are treated as absent.
+    /// Reserving coordinates again replaces their expiry, and minting
+    /// reserved coordinates as the owner consumes the reservation.
+    /// Only the owner may reserve coordinates.
+    ReserveCoordinates {
+        reservations: Vec<Reservation>,
+    },
+    /// Release reservations on the given coordinates, opening them up to
+    /// public minting again. Only the owner may release coordinates.
+    ReleaseCoordinates {
+        coordinates: Vec<Coordinates>,
+    },
+    /// Exempt an address from Config::wallet_limit, or revoke its exemption.
+    /// An address left over the limit by a revoked exemption keeps its
+    /// tokens, but can't mint more until it's back under the limit. Only
+    /// the owner may set exemptions.
+    SetWalletLimitExemption {
+        address: String,
+        exempt: bool,
+    },
+    /// Withdraw from current contract balance, including any balances held
+    /// in the given CW20 tokens. Funds go to recipient if given, and
+    /// otherwise are divided according to Config::fee_split, or go to the
+    /// owner address if no split is configured. If balance is given, the
+    /// native balance it names is withdrawn in full in place of amount,
+    /// which must be empty, leaving the funds held in escrow.
     Withdraw {
         amount: Vec<Coin>,
+        #[serde(default)]
+        cw20_amount: Vec<Cw20Coin>,
+        recipient: Option<String>,
+        #[serde(default)]
+        balance: Option<WithdrawBalance>,
+    },
+    /// Transfer several tokens to recipient in one transaction, with the
+    /// same checks as TransferNft for each. If any transfer fails, none of
+    /// them are made. At most 50 tokens can be transferred at once, and the
+    /// response's token_id attribute is repeated for each token, in order.
+    TransferMany {
+        recipient: String,
+        token_ids: Vec<String>,
+    },
+    /// Send several tokens to contract in one transaction, as with
+    /// TransferMany. The contract is sent one Cw721ReceiveMsg per token, in
+    /// order, each carrying msg, so receivers written for SendNft handle the
+    /// batch unchanged.
+    SendMany {
+        contract: String,
+        token_ids: Vec<String>,
+        msg: Binary,
     },
+    /// Pay for a mint or move with CW20 tokens. Called by the CW20 token
+    /// contract when tokens are sent to this contract with a Cw20HookMsg.
+    Receive(Cw20ReceiveMsg),
 
     /// BELOW ARE COPIED FROM CW721-BASE
     TransferNft {
@@ -292,9 +2006,161 @@ pub enum ExecuteMsg {
     },
 }
 
-impl From<ExecuteMsg> for CW721ExecuteMsg<XyzExtension> {
-    fn from(msg: ExecuteMsg) -> CW721ExecuteMsg<XyzExtension> {
-        match msg {
+/// Messages sent along with CW20 tokens through ExecuteMsg::Receive. The
+/// tokens sent pay the fee in the matching Config::cw20_mint_fee or
+/// Config::cw20_move_fee, and the CW20 sender acts as the message sender.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw20HookMsg {
+    /// Mint a new NFT for the CW20 sender, as with ExecuteMsg::Mint.
+    Mint {
+        coordinates: Coordinates,
+        captcha_signature: String,
+        nonce: u64,
+        expiry: Timestamp,
+    },
+    /// Move an NFT owned by the CW20 sender, as with ExecuteMsg::Move.
+    Move {
+        token_id: String,
+        coordinates: Coordinates,
+    },
+}
+
+/// An action that the owner can pause
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PausableAction {
+    /// Mint, BatchMint, and mints paid in CW20 tokens
+    Mint,
+    /// Move, MoveMany, and moves paid in CW20 tokens. Moves can still be
+    /// cancelled.
+    Move,
+    /// TransferNft, SendNft, TransferMany, SendMany, and sales through Buy
+    /// and AcceptOffer
+    Transfer,
+}
+
+impl PausableAction {
+    pub const ALL: [PausableAction; 3] = [
+        PausableAction::Mint,
+        PausableAction::Move,
+        PausableAction::Transfer,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PausableAction::Mint => "mint",
+            PausableAction::Move => "move",
+            PausableAction::Transfer => "transfer",
+        }
+    }
+}
+
+/// What happened to a token, as reported to hooks
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum XyzHookEvent {
+    Mint,
+    /// A move started, or was queued
+    Move,
+    CancelMove,
+    /// A move was finalized after the token arrived
+    MoveCompleted,
+    Transfer,
+    Send,
+    Burn,
+}
+
+/// Sent to each registered hook contract when a token changes position or
+/// owner. Queued moves are reported when they're queued, not when they
+/// start.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct XyzHookMsg {
+    pub event: XyzHookEvent,
+    /// The numeric id of the token, e.g. "1" for "xyz #1"
+    pub token_id: String,
+    /// The token's owner after the change
+    pub owner: String,
+    /// The token's coordinates before the change, or None if it was minted
+    pub prev_coordinates: Option<Coordinates>,
+    /// The token's coordinates after the change, or None if it was burned
+    pub coordinates: Option<Coordinates>,
+    /// When the token arrives at coordinates
+    pub arrival: Timestamp,
+}
+
+impl XyzHookMsg {
+    /// Wraps the message for execution on the given hook contract.
+    pub fn into_cosmos_msg(self, contract_addr: impl Into<String>) -> StdResult<CosmosMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg: to_binary(&XyzHookExecuteMsg::XyzHook(self))?,
+            funds: vec![],
+        }
+        .into())
+    }
+}
+
+/// The message hook contracts must accept
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum XyzHookExecuteMsg {
+    XyzHook(XyzHookMsg),
+}
+
+/// The attribute keys set on the response to a successful move, whether
+/// through ExecuteMsg::Move, Cw20HookMsg::Move, ExecuteMsg::Teleport or
+/// ExecuteMsg::RerollCoordinates. These keys are stable, so indexers can
+/// rely on them across contract versions.
+pub mod move_attributes {
+    /// "move", or "queue_move" if the move was queued behind another move,
+    /// "teleport" for teleports, and "reroll_coordinates" for re-rolls
+    pub const ACTION: &str = "action";
+    /// The address that made the move
+    pub const MOVER: &str = "mover";
+    /// The numeric id of the moved token, e.g. "1" for "xyz #1"
+    pub const TOKEN_ID: &str = "token_id";
+    /// The coordinates the move departs from. For a queued move, these are
+    /// the previous move's destination.
+    pub const FROM_X: &str = "from_x";
+    pub const FROM_Y: &str = "from_y";
+    pub const FROM_Z: &str = "from_z";
+    /// The coordinates the move arrives at
+    pub const TO_X: &str = "to_x";
+    pub const TO_Y: &str = "to_y";
+    pub const TO_Z: &str = "to_z";
+    /// The distance between the departure and destination, under the
+    /// configured distance metric
+    pub const DISTANCE: &str = "distance";
+    /// The fee charged for the move, excluding any refunded overpayment.
+    /// Zero for moves made by the contract owner.
+    pub const FEE_AMOUNT: &str = "fee_amount";
+    /// The denom of the fee charged, or the token contract address for
+    /// moves paid in CW20 tokens
+    pub const FEE_DENOM: &str = "fee_denom";
+    /// When the token arrives at its destination, in nanoseconds since the
+    /// epoch
+    pub const ARRIVAL: &str = "arrival";
+    /// The overpayment returned to the mover as a comma-separated list of
+    /// coins, e.g. "94uluna". Only set when native funds are refunded.
+    pub const REFUND: &str = "refund";
+}
+
+/// Returns the name of a message's variant, e.g. "Mint" for ExecuteMsg::Mint.
+fn variant_name<T: std::fmt::Debug>(msg: &T) -> String {
+    let debug = format!("{:?}", msg);
+    debug
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+impl TryFrom<ExecuteMsg> for CW721ExecuteMsg<XyzExtension> {
+    type Error = StdError;
+
+    fn try_from(msg: ExecuteMsg) -> StdResult<CW721ExecuteMsg<XyzExtension>> {
+        Ok(match msg {
             ExecuteMsg::TransferNft {
                 recipient,
                 token_id,
@@ -327,7 +2193,56 @@ impl From<ExecuteMsg> for CW721ExecuteMsg<XyzExtension> {
                 CW721ExecuteMsg::ApproveAll { operator, expires }
             }
             ExecuteMsg::RevokeAll { operator } => CW721ExecuteMsg::RevokeAll { operator },
-            _ => panic!("cannot covert {:?} to CW721ExecuteMsg", msg),
+            _ => {
+                return Err(StdError::generic_err(format!(
+                    "cannot convert {} to CW721ExecuteMsg",
+                    variant_name(&msg)
+                )))
+            }
+        })
+    }
+}
+
+/// Which position an in-progress move is matched by in coordinate range
+/// queries. Xyz that aren't moving are always matched by their coordinates.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitMatch {
+    /// Match moving xyz by the coordinates they're moving to
+    #[default]
+    Destination,
+    /// Match moving xyz by the coordinates they're moving from
+    Origin,
+    /// Match moving xyz by either end of their move
+    Both,
+}
+
+/// The order AllXyzTokens lists tokens in
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenOrder {
+    /// By token id, ordered as strings
+    #[default]
+    TokenId,
+    /// By coordinates, ordered by x, then y, then z, with negative values
+    /// before positive. Moving xyz are ordered by their destination.
+    Coordinates,
+}
+
+/// The direction list queries iterate in
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl From<SortOrder> for Order {
+    fn from(order: SortOrder) -> Order {
+        match order {
+            SortOrder::Ascending => Order::Ascending,
+            SortOrder::Descending => Order::Descending,
         }
     }
 }
@@ -336,40 +2251,130 @@ impl From<ExecuteMsg> for CW721ExecuteMsg<XyzExtension> {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    /// Returns the current contract config
-    /// Return type: Config
+    /// Returns the current contract config, and whether it's frozen
+    /// Return type: ConfigResponse
     Config {},
-    /// Returns the currently configured captcha public key
+    /// Returns the current contract config along with its version and when
+    /// it last changed, so past fees can be tied to the config they were
+    /// computed under.
+    /// Return type: ConfigInfoResponse
+    ConfigInfo {},
+    /// Returns the most recently added active captcha public key
+    /// Return type: String
     CaptchaPublicKey {},
+    /// Returns every active captcha public key and its expiry, oldest
+    /// first.
+    /// Return type: CaptchaPublicKeysResponse
+    CaptchaPublicKeys {},
+    /// Returns the contract owner and any pending ownership proposal.
+    /// Return type: OwnershipResponse
+    Ownership {},
+    /// Returns who can mint, as with the cw721 Minter query: the contract
+    /// owner, and anyone else while public minting is enabled.
+    /// Return type: MinterResponse
+    Minter {},
+    /// Returns whether public minting is open at the query block time and
+    /// when that next changes on its own as the mint window opens or closes.
+    /// Return type: MintStatusResponse
+    MintStatus {},
+    /// Returns the contracts registered to receive XyzHookMsgs.
+    /// Return type: HooksResponse
+    Hooks {},
+    /// Returns the actions currently paused.
+    /// Return type: PausedResponse
+    Paused {},
+    /// Returns the coordinates reserved for the owner to mint, in
+    /// coordinate order, including reservations that have expired.
+    /// Return type: ReservedCoordinatesResponse
+    ReservedCoordinates {
+        start_after: Option<Coordinates>,
+        limit: Option<u32>,
+    },
+    /// Returns the reservations that haven't expired, with their expiries,
+    /// in coordinate order.
+    /// Return type: ActiveReservationsResponse
+    ActiveReservations {
+        start_after: Option<Coordinates>,
+        limit: Option<u32>,
+    },
+    /// Returns the addresses exempt from Config::wallet_limit.
+    /// Return type: WalletLimitExemptionsResponse
+    WalletLimitExemptions {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
 
-    /// Returns all tokens owned by the given address, [] if unset.
+    /// Returns all tokens owned by the given address, [] if unset, ordered
+    /// by token id. In descending order, start_after lists the tokens
+    /// before it.
     /// Return type: XyzTokensResponse.
     XyzTokens {
         owner: String,
         start_after: Option<String>,
         limit: Option<u32>,
+        order: Option<SortOrder>,
     },
-    /// Lists all token_ids controlled by the contract.
+    /// Lists all token_ids controlled by the contract, in the given order.
+    /// Tokens ordered by id are paginated with start_after, and tokens
+    /// ordered by coordinates with start_after_coords, along with the
+    /// start_after token id when tokens can share coordinates. In descending
+    /// order, the cursor lists the tokens before it.
     /// Return type: XyzTokensResponse.
     AllXyzTokens {
         start_after: Option<String>,
         limit: Option<u32>,
+        order_by: Option<TokenOrder>,
+        start_after_coords: Option<Coordinates>,
+        order: Option<SortOrder>,
     },
-    /// Returns metadata about one particular token, based on *ERC721 Metadata JSON Schema*
-    /// but directly from the contract: XyzTokenInfo.
+    /// Returns the raw contract data about one particular token, with its
+    /// image and token URI resolved from the current config. For
+    /// ERC721-style metadata, use NftInfo.
+    /// Return type: XyzTokenInfo.
     XyzNftInfo {
         token_id: String,
     },
-    /// Returns metadata about the token associated with the given coordinates, if any.
-    /// Return type: XyzTokenInfo.
+    /// Returns the raw contract data about each of up to 100 tokens, as with
+    /// XyzNftInfo, in the order given. Unknown tokens have no info.
+    /// Return type: XyzNftInfoBatchResponse
+    XyzNftInfoBatch {
+        token_ids: Vec<String>,
+    },
+    /// Returns metadata about the token associated with the given coordinates,
+    /// if any, and the role the coordinates play for it, as described by
+    /// CoordinateRole.
+    /// Return type: XyzNftInfoByCoordsResponse.
     XyzNftInfoByCoords {
         coordinates: Coordinates,
     },
+    /// Lists the tokens occupying or moving to the given coordinates, in
+    /// token id order. Unless Config::unique_coordinates is unset, there's at
+    /// most one.
+    /// Return type: XyzTokensResponse.
+    XyzNftInfosByCoords {
+        coordinates: Coordinates,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Returns whether each of up to 200 coordinates is in bounds, and
+    /// whether it's occupied or reserved as a move destination, in the order
+    /// given. Out-of-bounds coordinates are flagged rather than failing the
+    /// query.
+    /// Return type: CoordinatesStatusResponse
+    CoordinatesStatus {
+        coordinates: Vec<Coordinates>,
+    },
     /// Returns the number of tokens owned by the given address
     /// Return type: NumTokensResponse
     NumTokensForOwner {
         owner: String,
     },
+    /// Returns the number of tokens owned by the given address, split into
+    /// those in transit and those that have arrived as of the current block.
+    /// Return type: OwnerTokenSummaryResponse
+    OwnerTokenSummary {
+        owner: String,
+    },
 
     /// Calculates the price to move the given token to the given coordinate.
     /// Return type: MoveParamsResponse
@@ -377,6 +2382,296 @@ pub enum QueryMsg {
         token_id: String,
         coordinates: Coordinates,
     },
+    /// Calculates the total price to move each given token to its given
+    /// coordinates, as with ExecuteMsg::MoveMany, along with each move's
+    /// parameters as with MoveParams.
+    /// Return type: MoveManyParamsResponse
+    MoveManyParams {
+        moves: Vec<(String, Coordinates)>,
+    },
+    /// Calculates the price to teleport the given token to the given
+    /// coordinate. The duration is always zero.
+    /// Return type: MoveParamsResponse
+    TeleportParams {
+        token_id: String,
+        coordinates: Coordinates,
+    },
+    /// Calculates the price to move a token from start to end, whether or
+    /// not a token is at start. Both coordinates must be in bounds.
+    /// Return type: MoveParamsResponse
+    MoveQuote {
+        start: Coordinates,
+        end: Coordinates,
+    },
+    /// Returns the energy the given token currently has available for moves.
+    /// Return type: TokenEnergyResponse
+    TokenEnergy {
+        token_id: String,
+    },
+    /// Returns how many moves the given token has made and the distance
+    /// they covered, including any queued moves that have set off.
+    /// Return type: TokenStatsResponse
+    TokenStats {
+        token_id: String,
+    },
+    /// Returns how many wallets hold 1, 2-5, 6-10, and 11+ tokens. Reads a
+    /// per-wallet token counter maintained on mint and transfer, so the cost
+    /// grows with the number of unique holders rather than the number of tokens.
+    /// Return type: OwnershipDistributionResponse
+    OwnershipDistribution {},
+    /// Returns a swap proposal.
+    /// Return type: SwapProposal
+    SwapProposal {
+        proposal_id: u64,
+    },
+    /// Previews swapping the coordinates of token_id_a and token_id_b as if
+    /// token_id_a's owner proposed it and token_id_b's owner accepted it in
+    /// the current block, applying the same rules ProposeSwap and AcceptSwap
+    /// enforce.
+    /// Return type: SwapParamsResponse
+    SwapParams {
+        token_id_a: String,
+        token_id_b: String,
+    },
+    /// Returns a token's unexpired pilot grant, if any.
+    /// Return type: PilotResponse
+    Pilot {
+        token_id: String,
+    },
+    /// Returns a token's listing for sale.
+    /// Return type: Listing
+    Listing {
+        token_id: String,
+    },
+    /// Lists the tokens listed for sale, ordered by token id.
+    /// Return type: ListingsResponse
+    Listings {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists the offers on a token, ordered by bidder address, including
+    /// expired offers that haven't been withdrawn.
+    /// Return type: OffersResponse
+    OffersByToken {
+        token_id: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists the offers made by a bidder, ordered by token id, including
+    /// expired offers that haven't been withdrawn.
+    /// Return type: OffersResponse
+    OffersByBidder {
+        bidder: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists token holders by descending token count, with holders of equal
+    /// counts in descending address order. Pass the last entry's (count,
+    /// owner) as start_after to continue from it. Reads a leaderboard index
+    /// maintained on mint, transfer, send and burn.
+    /// Return type: OwnerLeaderboardResponse
+    OwnerLeaderboard {
+        start_after: Option<(u64, String)>,
+        limit: Option<u32>,
+    },
+    /// Checks whether two tokens will occupy the same coordinates at the same
+    /// time, from the current block time onward. In-progress moves follow the
+    /// path described by XyzExtension::position_at; arrived tokens are stationary.
+    /// Return type: CollisionCheckResponse
+    CollisionCheck {
+        token_id_a: String,
+        token_id_b: String,
+    },
+    /// Returns the exact bytes the captcha signer must sign to authorize
+    /// sender minting the given coordinates with the given nonce and expiry:
+    /// the JSON-encoded CaptchaPayload. Omit coordinates for a MintRandom.
+    /// Return type: Binary
+    SignablePayload {
+        sender: String,
+        coordinates: Option<Coordinates>,
+        nonce: u64,
+        expiry: Timestamp,
+    },
+    /// Checks a captcha signature exactly as minting would for minter at
+    /// the current block, without using up its nonce, so a frontend can
+    /// catch a bad signature before paying gas.
+    /// Return type: VerifyCaptchaResponse
+    VerifyCaptcha {
+        minter: String,
+        coordinates: Coordinates,
+        captcha_signature: String,
+        nonce: u64,
+        expiry: Timestamp,
+    },
+    /// Returns the moves queued behind a token's in-progress move that
+    /// haven't started yet, in the order they'll be made.
+    /// Return type: MoveQueueResponse
+    MoveQueue {
+        token_id: String,
+    },
+    /// Returns a token's move history in the order it happened, including
+    /// for burned tokens. start_after is the sequence number of the last
+    /// entry from the previous page.
+    /// Return type: MoveHistoryResponse
+    MoveHistory {
+        token_id: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Lists the tokens still moving as of the current block, ordered by
+    /// arrival time and then token_id. start_after is the arrival time and
+    /// token_id of the last token on the previous page.
+    /// Return type: TokensInTransitResponse
+    TokensInTransit {
+        start_after: Option<(Timestamp, String)>,
+        limit: Option<u32>,
+    },
+    /// Returns the number of public mints still allowed in the current block.
+    /// Return type: PublicMintsRemainingResponse
+    PublicMintsRemaining {},
+    /// Returns the occupancy of every cell in the box bounded by min and max
+    /// (inclusive) as a bit-packed grid, where a set bit means the cell's
+    /// coordinates are claimed. Cells are ordered row-major over x, y, z, so
+    /// cell (x, y, z) is at index
+    ///   ((x - min.x) * len_y + (y - min.y)) * len_z + (z - min.z)
+    /// where len_y and len_z are the box's side lengths along those axes.
+    /// Index i is stored in byte i / 8 at bit i % 8, counting from the least
+    /// significant bit. The box may contain at most 65536 cells.
+    /// Return type: Binary
+    OccupancyBitmap {
+        min: Coordinates,
+        max: Coordinates,
+    },
+    /// Lists the tokens positioned inside the box bounded by min and max
+    /// (inclusive), ordered by the coordinates they were matched at and then
+    /// by token_id. transit_match (default destination) picks which end of
+    /// an in-progress move a moving token is matched by; with both, a token
+    /// with both ends inside the box is listed once, at its destination.
+    /// start_after must be a token that the same query would return.
+    /// Return type: XyzTokensResponse
+    XyzTokensInRange {
+        min: Coordinates,
+        max: Coordinates,
+        transit_match: Option<TransitMatch>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists the tokens within max_distance of coordinates, under the
+    /// configured distance metric, along with each token's distance. A token at the coordinates themselves is included, at
+    /// distance 0. Moving tokens are matched by their destination. Results
+    /// are ordered and paginated as for XyzTokensInRange, and max_distance
+    /// is capped at 1000.
+    /// Return type: XyzTokensNearbyResponse
+    XyzTokensNearby {
+        coordinates: Coordinates,
+        max_distance: u64,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Divides the box bounded by min and max into cubes with edges
+    /// bucket_size long, starting from min, and counts the tokens in each.
+    /// Moving tokens are counted at their destination. Only buckets with
+    /// tokens are returned, in coordinate order, and the box can span at
+    /// most 1000 buckets.
+    /// Return type: DensityResponse
+    Density {
+        min: Coordinates,
+        max: Coordinates,
+        bucket_size: u64,
+    },
+    /// Returns the number of tokens ever minted, which the supply cap
+    /// applies to, and the number still alive after burns.
+    /// Return type: SupplyResponse
+    Supply {},
+    /// Returns the price of the next mint, before any scaling by
+    /// Config::mint_fee_distance_curve, and the number of tokens that can be
+    /// minted at that price.
+    /// Return type: CurrentMintFeeResponse
+    CurrentMintFee {},
+    /// Returns every price accepted for the next public mint, any one of
+    /// which pays for it, before any scaling by
+    /// Config::mint_fee_distance_curve.
+    /// Return type: MintPricesResponse
+    MintPrices {},
+    /// Returns every price accepted for the next public mint at
+    /// coordinates, after scaling by Config::mint_fee_distance_curve and, if
+    /// minter is given, any adjacency discount minter would get.
+    /// Return type: MintPricesResponse
+    MintPrice {
+        coordinates: Coordinates,
+        minter: Option<String>,
+    },
+    /// Checks whether minter could publicly mint a token at coordinates,
+    /// running the same checks as a mint apart from the captcha signature
+    /// and the payment, and returns the fee minter would owe.
+    /// Return type: SimulateMintResponse
+    SimulateMint {
+        minter: String,
+        coordinates: Coordinates,
+    },
+    /// Returns the current allowlist merkle root.
+    /// Return type: AllowlistMerkleRootResponse
+    AllowlistMerkleRoot {},
+    /// Checks whether proof shows address is on the allowlist, and how many
+    /// more allowlist mints the address has left.
+    /// Return type: AllowlistEligibilityResponse
+    AllowlistEligibility {
+        address: String,
+        proof: Vec<String>,
+    },
+    /// Returns the royalty owed when token_id sells for sale_price and the
+    /// address it's owed to, as defined by the cw2981 royalty extension.
+    /// Return type: RoyaltiesInfoResponse
+    RoyaltyInfo {
+        token_id: String,
+        sale_price: Uint128,
+    },
+    /// Returns whether royalties are owed on sales, as defined by the cw2981
+    /// royalty extension.
+    /// Return type: CheckRoyaltiesResponse
+    CheckRoyalties {},
+    /// Returns the native balances the owner can withdraw, less the funds
+    /// held in escrow for offers and swaps.
+    /// Return type: WithdrawableBalanceResponse
+    WithdrawableBalance {},
+    /// Returns the fees collected and not yet withdrawn, and the part of
+    /// them a withdrawal would pay the owner under Config::fee_split.
+    /// Return type: FeeBalancesResponse
+    FeeBalances {},
+    /// Returns lifetime counts of mints, burns, moves, distance traveled,
+    /// and fees collected.
+    /// Return type: StatsResponse
+    Stats {},
+
+    /// Returns the approval of spender to transfer token_id, as defined by
+    /// the current cw721 spec. The token's owner is always approved. Fails
+    /// if spender isn't approved, or if the approval has expired and
+    /// include_expired isn't set.
+    /// Return type: ApprovalResponse
+    Approval {
+        token_id: String,
+        spender: String,
+        include_expired: Option<bool>,
+    },
+    /// Returns every approval to transfer token_id, as defined by the
+    /// current cw721 spec. Expired approvals are left out unless
+    /// include_expired is set.
+    /// Return type: ApprovalsResponse
+    Approvals {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+    /// Returns the operators approved to transfer any of owner's tokens, as
+    /// defined by the current cw721 spec, ordered by operator address. Shares
+    /// its implementation with ApprovedForAll. Expired approvals are left out
+    /// unless include_expired is set.
+    /// Return type: OperatorsResponse
+    AllOperators {
+        owner: String,
+        include_expired: Option<bool>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
 
     // BELOW ARE COPIED FROM CW721-BASE
     OwnerOf {
@@ -391,6 +2686,9 @@ pub enum QueryMsg {
     },
     NumTokens {},
     ContractInfo {},
+    /// Returns ERC721-style metadata generated from the token's current
+    /// position, including any queued moves that have started.
+    /// Return type: Cw721NftInfoResponse
     NftInfo {
         token_id: String,
     },
@@ -409,21 +2707,28 @@ pub enum QueryMsg {
     },
 }
 
-impl From<QueryMsg> for CW721QueryMsg {
-    fn from(msg: QueryMsg) -> CW721QueryMsg {
-        match msg {
+impl TryFrom<QueryMsg> for CW721QueryMsg {
+    type Error = StdError;
+
+    fn try_from(msg: QueryMsg) -> StdResult<CW721QueryMsg> {
+        Ok(match msg {
             QueryMsg::XyzTokens {
                 owner,
                 start_after,
                 limit,
+                order: None | Some(SortOrder::Ascending),
             } => CW721QueryMsg::Tokens {
                 owner,
                 start_after,
                 limit,
             },
-            QueryMsg::AllXyzTokens { start_after, limit } => {
-                CW721QueryMsg::AllTokens { start_after, limit }
-            }
+            QueryMsg::AllXyzTokens {
+                start_after,
+                limit,
+                order_by: None | Some(TokenOrder::TokenId),
+                start_after_coords: None,
+                order: None | Some(SortOrder::Ascending),
+            } => CW721QueryMsg::AllTokens { start_after, limit },
             QueryMsg::XyzNftInfo { token_id } => CW721QueryMsg::NftInfo { token_id },
             QueryMsg::OwnerOf {
                 token_id,
@@ -465,20 +2770,636 @@ impl From<QueryMsg> for CW721QueryMsg {
             QueryMsg::AllTokens { start_after, limit } => {
                 CW721QueryMsg::AllTokens { start_after, limit }
             }
-            _ => panic!("cannot covert {:?} to CW721QueryMsg", msg),
-        }
+            _ => {
+                return Err(StdError::generic_err(format!(
+                    "cannot convert {} to CW721QueryMsg",
+                    variant_name(&msg)
+                )))
+            }
+        })
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct XyzNftInfoBatchEntry {
+    pub token_id: String,
+    /// The token's info as returned by XyzNftInfo, or None if the token
+    /// doesn't exist
+    pub info: Option<XyzTokenInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct XyzNftInfoBatchResponse {
+    pub tokens: Vec<XyzNftInfoBatchEntry>,
+}
+
+/// The role coordinates play for a token. A move claims its destination as
+/// soon as it starts, or as soon as it's queued, and frees its origin at the
+/// same time: the origin can be minted or moved onto while the token is still
+/// travelling from it, and the destination can't be claimed by another token
+/// before it arrives.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CoordinateRole {
+    /// The token is at the coordinates
+    Current,
+    /// The token is moving, or has a queued move, to the coordinates, which
+    /// are claimed for it
+    ReservedDestination,
+    /// The token is moving from the coordinates, which are free
+    VacatedOrigin,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct XyzNftInfoByCoordsResponse {
+    pub token: XyzTokenInfo,
+    pub role: CoordinateRole,
+}
+
+/// The availability of a set of coordinates. Coordinates are free to mint or
+/// move onto if they're in bounds and neither occupied nor reserved as a
+/// move destination, and, for anyone but the owner, not reserved.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CoordinateStatus {
+    pub coordinates: Coordinates,
+    pub in_bounds: bool,
+    /// Whether a token is at the coordinates. A token moving away from them
+    /// doesn't occupy them.
+    pub occupied: bool,
+    /// Whether a token is moving, or has a queued move, to the coordinates
+    pub reserved_destination: bool,
+    /// Whether the coordinates are reserved for the owner to mint
+    pub reserved: bool,
+    /// The token occupying the coordinates or moving to them, if any
+    pub token_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CoordinatesStatusResponse {
+    pub statuses: Vec<CoordinateStatus>,
+}
+
+/// A token along with its id, which XyzTokenInfo doesn't include
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct XyzTokenEntry {
+    pub token_id: String,
+    pub info: XyzTokenInfo,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct XyzTokensResponse {
-    pub tokens: Vec<XyzTokenInfo>,
+    pub tokens: Vec<XyzTokenEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct NearbyToken {
+    /// The distance from the queried coordinates to the token, under the
+    /// configured distance metric
+    pub distance: u64,
+    pub token: XyzTokenInfo,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DensityBucket {
+    /// The bucket's corner nearest the box's min
+    pub origin: Coordinates,
+    pub count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DensityResponse {
+    pub buckets: Vec<DensityBucket>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct XyzTokensNearbyResponse {
+    pub tokens: Vec<NearbyToken>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ApprovalResponse {
+    pub approval: cw721::Approval,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ApprovalsResponse {
+    pub approvals: Vec<cw721::Approval>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct OperatorsResponse {
+    pub operators: Vec<cw721::Approval>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct MoveParamsResponse {
     pub fee: Coin,
     pub duration_nanos: u64,
+    /// While the token is cooling down from its last move, the time at which
+    /// it can move again. Always None for quotes.
+    #[serde(default)]
+    pub cooldown_until: Option<Timestamp>,
+    /// The farthest the move can travel, or zero if it's unlimited
+    #[serde(default)]
+    pub max_move_distance: u64,
+    /// The version of the config the fee and duration were computed under
+    #[serde(default)]
+    pub config_version: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SwapParamsResponse {
+    /// The move fees both owners would pay for their legs of the swap
+    pub fee: Coin,
+    pub allowed: bool,
+    /// Why the swap isn't allowed, if it isn't
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MoveManyParamsResponse {
+    /// The sum of the moves' fees
+    pub fee: Coin,
+    /// The parameters of each move, in the order given
+    pub moves: Vec<MoveParamsResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TokenEnergyResponse {
+    pub energy: u32,
+    pub energy_max: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TokenStatsResponse {
+    pub moves_count: u64,
+    pub total_distance: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct OwnerTokenSummaryResponse {
+    /// The number of tokens the address owns
+    pub total: u64,
+    /// The number of those tokens still moving, including tokens with
+    /// queued moves yet to finish
+    pub in_transit: u64,
+    /// The number of those tokens at their final destination
+    pub arrived: u64,
+    /// Whether the address is exempt from Config::wallet_limit
+    pub is_exempt: bool,
+}
+
+/// One token's side of a swap proposal, as it was when proposed
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SwapLeg {
+    pub token_id: String,
+    pub owner: Addr,
+    pub coordinates: Coordinates,
+    pub arrival: Timestamp,
+}
+
+/// A proposal to swap two tokens' coordinates
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SwapProposal {
+    pub id: u64,
+    pub proposer: SwapLeg,
+    pub counterparty: SwapLeg,
+    /// The proposer's move fee, held in escrow
+    pub fee: Coin,
+    pub expires: Expiration,
+}
+
+impl SwapProposal {
+    /// Returns whether the tokens are still as they were when proposed,
+    /// neither having changed owner or moved since.
+    pub fn is_current(&self, proposer: &XyzTokenInfo, counterparty: &XyzTokenInfo) -> bool {
+        [
+            (&self.proposer, proposer),
+            (&self.counterparty, counterparty),
+        ]
+        .iter()
+        .all(|(leg, token)| {
+            token.owner == leg.owner
+                && token.extension.coordinates == leg.coordinates
+                && token.extension.arrival == leg.arrival
+        })
+    }
+}
+
+/// A grant letting pilot move a token until expires
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PilotGrant {
+    pub pilot: Addr,
+    pub expires: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PilotResponse {
+    pub grant: Option<PilotGrant>,
+}
+
+/// A token listed for sale
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Listing {
+    pub token_id: String,
+    /// The token's owner when it was listed, who is paid when it's bought
+    pub seller: Addr,
+    pub price: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ListingsResponse {
+    pub listings: Vec<Listing>,
+}
+
+/// An offer to buy a token, with its price held in escrow
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Offer {
+    pub token_id: String,
+    pub bidder: Addr,
+    pub price: Coin,
+    pub expires: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct OffersResponse {
+    pub offers: Vec<Offer>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct OwnerLeaderboardEntry {
+    pub owner: String,
+    /// The number of tokens the owner holds
+    pub count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct OwnerLeaderboardResponse {
+    pub entries: Vec<OwnerLeaderboardEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct OwnershipDistributionResponse {
+    /// The number of wallets holding at least one token
+    pub unique_holders: u64,
+    /// The number of wallets holding exactly one token
+    pub one: u64,
+    /// The number of wallets holding between two and five tokens
+    pub two_to_five: u64,
+    /// The number of wallets holding between six and ten tokens
+    pub six_to_ten: u64,
+    /// The number of wallets holding eleven or more tokens
+    pub eleven_or_more: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct VerifyCaptchaResponse {
+    pub valid: bool,
+    /// Why minting would reject the signature, if it's invalid
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CollisionCheckResponse {
+    pub collides: bool,
+    /// Where the tokens first meet, if they collide
+    pub coordinates: Option<Coordinates>,
+    /// When the tokens first meet, if they collide
+    pub time: Option<Timestamp>,
+}
+
+/// A move that will start once the token arrives at its previous destination
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct QueuedMove {
+    pub coordinates: Coordinates,
+    pub arrival: Timestamp,
+    /// The fee paid upfront for the move
+    pub fee: Coin,
+    /// Who paid the fee, and so is refunded if the move is cancelled. None
+    /// for moves queued before payers were recorded, whose fees are
+    /// refunded to the token's owner.
+    #[serde(default)]
+    pub payer: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MoveQueueResponse {
+    pub moves: Vec<QueuedMove>,
+}
+
+/// An event in a token's move history
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveRecord {
+    /// A move was made, including teleports, which arrive as they depart,
+    /// and swap legs. A queued move is recorded when it's queued, departing
+    /// once the move before it arrives.
+    Move {
+        origin: Coordinates,
+        destination: Coordinates,
+        departure: Timestamp,
+        arrival: Timestamp,
+        fee: Coin,
+    },
+    /// The in-progress move from origin to destination was cancelled,
+    /// returning the token to origin and dropping any queued moves
+    Cancel {
+        origin: Coordinates,
+        destination: Coordinates,
+        cancelled_at: Timestamp,
+        refund: Vec<Coin>,
+    },
+    /// The in-progress move was expedited to arrive at arrival, bringing
+    /// any queued moves forward by as much
+    Expedite { arrival: Timestamp, fee: Coin },
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MoveHistoryEntry {
+    /// The entry's position in the token's history, counting from 0
+    pub sequence: u64,
+    pub record: MoveRecord,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MoveHistoryResponse {
+    pub entries: Vec<MoveHistoryEntry>,
+}
+
+/// A token partway through a move
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TokenInTransit {
+    pub token_id: String,
+    pub owner: Addr,
+    /// The coordinates the token is moving to
+    pub coordinates: Coordinates,
+    /// The coordinates the token is moving from
+    pub prev_coordinates: Option<Coordinates>,
+    pub arrival: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TokensInTransitResponse {
+    pub tokens: Vec<TokenInTransit>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SupplyResponse {
+    /// The number of tokens ever minted, including any since burned
+    pub minted: u64,
+    /// The number of tokens not burned
+    pub live: u64,
+    /// The maximum number of tokens that can ever be minted
+    pub token_supply: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CurrentMintFeeResponse {
+    pub fee: Coin,
+    /// The number of tokens left to mint before the price changes or the
+    /// supply runs out
+    pub remaining_in_tier: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MintPricesResponse {
+    pub prices: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SimulateMintResponse {
+    /// Whether the mint would succeed, given a valid captcha signature and
+    /// the fee
+    pub success: bool,
+    /// The fee the minter would owe, which is zero for the contract owner
+    pub fee: Coin,
+    /// The number of tokens left to mint before the supply runs out
+    pub remaining_supply: u64,
+    /// Why the mint would fail, if it would
+    pub failure_reason: Option<MintFailureReason>,
+}
+
+/// Why a simulated mint would fail
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MintFailureReason {
+    Paused,
+    PublicMintingDisabled,
+    OutsideMintWindow,
+    BlockMintLimit,
+    MintCooldown,
+    SupplyExhausted,
+    WalletLimit,
+    OutOfBounds,
+    Claimed,
+    Reserved,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AllowlistMerkleRootResponse {
+    pub merkle_root: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AllowlistEligibilityResponse {
+    /// True if the proof is valid and the address has allowlist mints left
+    pub eligible: bool,
+    /// The number of allowlist mints the address has left, whether or not
+    /// the proof is valid
+    pub remaining: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct HooksResponse {
+    pub hooks: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PausedResponse {
+    pub actions: Vec<PausableAction>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ReservedCoordinatesResponse {
+    pub coordinates: Vec<Coordinates>,
+}
+
+/// Coordinates set aside for the owner
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Reservation {
+    pub coordinates: Coordinates,
+    /// When the reservation lapses, or None if it's permanent
+    #[serde(default)]
+    pub expires: Option<Timestamp>,
+}
+
+impl Reservation {
+    /// Returns whether the reservation still holds at the given time. It
+    /// expires in the block whose time equals its expiry.
+    pub fn is_active(&self, time: Timestamp) -> bool {
+        self.expires.map_or(true, |expires| time < expires)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ActiveReservationsResponse {
+    pub reservations: Vec<Reservation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct WalletLimitExemptionsResponse {
+    pub addresses: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ConfigResponse {
+    pub config: Config,
+    /// If true, the config can no longer be changed
+    pub frozen: bool,
+}
+
+/// A record of when the config last changed. The version starts at 1 on
+/// instantiation and increases by one with every config update or migration.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ConfigVersion {
+    pub version: u64,
+    pub updated_at: Timestamp,
+    pub updated_height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ConfigInfoResponse {
+    pub config: Config,
+    pub version: u64,
+    /// The block time of the last config change
+    pub updated_at: Timestamp,
+    /// The block height of the last config change
+    pub updated_height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct OwnershipResponse {
+    pub owner: String,
+    /// The address proposed as the next owner, if any
+    pub pending_owner: Option<String>,
+    /// When the pending proposal expires. None if it never expires.
+    pub pending_expires: Option<Expiration>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MinterResponse {
+    /// The contract owner, the only address that can always mint. Empty once
+    /// ownership has been renounced.
+    pub minter: String,
+    pub public_minting_enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MintStatusResponse {
+    /// Whether public mints are accepted: public minting is enabled and the
+    /// block time is within the mint window
+    pub open: bool,
+    pub public_minting_enabled: bool,
+    pub mint_start: Option<Timestamp>,
+    pub mint_end: Option<Timestamp>,
+    /// When open next changes as the mint window opens or closes. None if
+    /// it won't change without a config update.
+    pub next_transition: Option<Timestamp>,
+    /// The nanoseconds from the block time until next_transition
+    pub nanos_until_transition: Option<u64>,
+}
+
+/// The signature scheme a captcha public key signs under. Each scheme signs
+/// the JSON-encoded CaptchaPayload.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptchaKeyAlgorithm {
+    /// PKCS#1 v1.5 signatures over the payload's sha256 hash, with a
+    /// PEM-encoded public key
+    #[default]
+    Rsa,
+    /// 64-byte compact signatures over the payload's sha256 hash, with a
+    /// base64-encoded 33-byte compressed or 65-byte uncompressed public key
+    Secp256k1,
+    /// 64-byte signatures over the payload itself, with a base64-encoded
+    /// 32-byte public key
+    Ed25519,
+}
+
+/// A public key accepted for captcha verification
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CaptchaPublicKey {
+    /// The public key, encoded as its algorithm requires
+    pub public_key: String,
+    #[serde(default)]
+    pub algorithm: CaptchaKeyAlgorithm,
+    /// When the key stops being accepted. None if it never expires.
+    pub expires: Option<Expiration>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CaptchaPublicKeysResponse {
+    pub keys: Vec<CaptchaPublicKey>,
+}
+
+// ----------------- begin CW2981 ^0.10 shim ----------------- //
+
+// adapted from: https://github.com/CosmWasm/cw-nfts/blob/v0.10.0/contracts/cw2981-royalties/src/msg.rs
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RoyaltiesInfoResponse {
+    pub address: String,
+    // Note: this must be same denom as that passed in to RoyaltyInfo
+    // rounding up or down is at the discretion of the implementer
+    pub royalty_amount: Uint128,
+}
+
+/// Shows if the contract implements royalties
+/// if royalty_payments is true, marketplaces should pay them
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CheckRoyaltiesResponse {
+    pub royalty_payments: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct WithdrawableBalanceResponse {
+    /// The withdrawable amount of each denom, leaving out denoms with
+    /// nothing to withdraw
+    pub balances: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FeeBalancesResponse {
+    /// The contract's native balance of each denom less the funds held in
+    /// escrow, sorted by denom and leaving out empty denoms
+    pub balances: Vec<Coin>,
+    /// The part of balances a withdrawal without a recipient would pay the
+    /// owner, which is all of it if no fee split is configured
+    pub owner_share: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct StatsResponse {
+    /// Tokens ever minted, including burned tokens
+    pub mints: u64,
+    pub burns: u64,
+    /// Moves ever started, including queued moves, teleports, and both moves
+    /// of each swap. Cancelled moves still count.
+    pub moves: u64,
+    /// The total distance covered by moves
+    pub distance_traveled: Uint128,
+    /// Native fees ever collected, sorted by denom, before refunds of
+    /// cancelled moves
+    pub fees_collected: Vec<Coin>,
+    /// CW20 fees ever collected, sorted by token address
+    pub cw20_fees_collected: Vec<Cw20Coin>,
+}
+
+// ----------------- end CW2981 ^0.10 shim ----------------- //
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PublicMintsRemainingResponse {
+    /// None if public mints per block are unlimited
+    pub remaining: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -500,18 +3421,29 @@ mod tests {
             extension: XyzExtension {
                 coordinates: Coordinates { x: 1, y: 2, z: 3 },
                 prev_coordinates: None,
-                arrival: Timestamp::from_nanos(1),
+                arrival: Timestamp::from_seconds(2),
+                energy: 0,
+                energy_updated_at: Timestamp::from_nanos(1),
+                level: 0,
+                token_uri: None,
+                minted_at: Timestamp::from_seconds(1),
+                minted_height: 5,
+                rerolled: false,
+                name: None,
+                description: None,
+                moves_count: 2,
+                total_distance: 7,
             },
         };
 
         assert_eq!(
-            info.as_cw721_nft_info(),
+            info.as_cw721_nft_info(&test_config(), Timestamp::from_seconds(1)),
             Cw721NftInfoResponse {
                 token_uri: None,
                 extension: Cw721Metadata {
                     name: Some("xyz #1".to_string()),
                     description: Some("test description".to_string()),
-                    image: Some("data:image/svg+xml;base64,PHN2ZyB4bWxucz0iaHR0cDovL3d3dy53My5vcmcvMjAwMC9zdmciIHByZXNlcnZlQXNwZWN0UmF0aW89InhNaW5ZTWluIG1lZXQiIHZpZXdCb3g9IjAgMCAyNDAgMjQwIj48ZyBjbGFzcz0iY29udGFpbmVyIj48cmVjdCBzdHlsZT0id2lkdGg6MjQwcHg7aGVpZ2h0OjI0MHB4O2ZpbGw6IzAwMDsiLz48dGV4dCB4PSIxMjAiIHk9IjEyMCIgZG9taW5hbnQtYmFzZWxpbmU9Im1pZGRsZSIgdGV4dC1hbmNob3I9Im1pZGRsZSIgc3R5bGU9ImZpbGw6I2ZmZjtmb250LWZhbWlseTpzZXJpZjtmb250LXNpemU6MTZweDt0ZXh0LWFsaWduOmNlbnRlcjsiPlsxLCAyLCAzXTwvdGV4dD48L2c+PC9zdmc+".to_string()),
+                    image: Some(base64_token_image(&info.extension.coordinates)),
                     attributes: Some(vec![
                         Cw721Trait {
                             display_type: None,
@@ -528,6 +3460,36 @@ mod tests {
                             trait_type: "z".to_string(),
                             value: "3".to_string(),
                         },
+                        Cw721Trait {
+                            display_type: None,
+                            trait_type: "in_transit".to_string(),
+                            value: "true".to_string(),
+                        },
+                        Cw721Trait {
+                            display_type: Some("date".to_string()),
+                            trait_type: "arrival".to_string(),
+                            value: "2".to_string(),
+                        },
+                        Cw721Trait {
+                            display_type: Some("date".to_string()),
+                            trait_type: "minted_at".to_string(),
+                            value: "1".to_string(),
+                        },
+                        Cw721Trait {
+                            display_type: None,
+                            trait_type: "minted_height".to_string(),
+                            value: "5".to_string(),
+                        },
+                        Cw721Trait {
+                            display_type: Some("number".to_string()),
+                            trait_type: "moves_count".to_string(),
+                            value: "2".to_string(),
+                        },
+                        Cw721Trait {
+                            display_type: Some("number".to_string()),
+                            trait_type: "total_distance".to_string(),
+                            value: "7".to_string(),
+                        },
                     ]),
                     image_data: None,
                     animation_url: None,
@@ -538,4 +3500,1370 @@ mod tests {
             }
         )
     }
+
+    fn test_config() -> Config {
+        Config {
+            public_minting_enabled: true,
+            bounds: CoordinateBounds::symmetric(1000),
+            token_supply: 10000,
+            wallet_limit: 5,
+            mint_fee: Coin::new(0, "uluna"),
+            base_move_nanos: 10,
+            move_nanos_per_step: 1,
+            base_move_fee: Coin::new(100, "uluna"),
+            move_fee_per_step: Uint128::new(1),
+            energy_max: 100,
+            energy_regen_nanos: 10,
+            burn_mint_fees: false,
+            default_query_limit: 10,
+            max_query_limit: 30,
+            max_merge_level: 0,
+            max_public_mints_per_block: None,
+            refund_overpayment: true,
+            move_cancel_refund_bps: 0,
+            max_queued_moves: 0,
+            allowlist_mint_enabled: false,
+            allowlist_mint_fee: None,
+            allowlist_mint_quota: 1,
+            mint_fee_tiers: vec![],
+            mint_fee_alternatives: vec![],
+            cw20_mint_fee: None,
+            cw20_move_fee: None,
+            royalty_bps: 0,
+            royalty_payment_address: None,
+            fee_split: vec![],
+            max_captcha_age_nanos: 600_000_000_000,
+            embed_token_image: true,
+            move_fee_discounts: vec![],
+            teleport_enabled: false,
+            teleport_fee_multiplier: 1,
+            expedite_bps: 0,
+            expedite_min_remaining_nanos: 0,
+            move_cooldown_nanos: 0,
+            max_move_distance: 0,
+            mint_for_wallet_limit: false,
+            reroll_enabled: false,
+            reroll_window_nanos: 0,
+            distance_metric: DistanceMetric::Manhattan,
+            transfers_locked_during_move: false,
+            mint_start: None,
+            mint_end: None,
+            per_address_mint_cooldown_nanos: 0,
+            token_profiles_enabled: true,
+            base_token_uri: None,
+            unique_coordinates: true,
+            mint_fee_distance_curve: None,
+            adjacent_mint_discount_bps: 0,
+            fee_distribution: vec![],
+        }
+    }
+
+    #[test]
+    fn custom_execute_msgs_dont_convert_to_cw721() {
+        let coordinates = Coordinates { x: 1, y: 2, z: 3 };
+        let token_id = "xyz #1".to_string();
+        let msgs = vec![
+            (
+                "Mint",
+                ExecuteMsg::Mint {
+                    coordinates,
+                    captcha_signature: "".to_string(),
+                    nonce: 0,
+                    expiry: Timestamp::from_nanos(0),
+                    token_uri: None,
+                    proof: None,
+                },
+            ),
+            (
+                "MintFor",
+                ExecuteMsg::MintFor {
+                    recipient: "recipient".to_string(),
+                    coordinates,
+                },
+            ),
+            (
+                "MintRandom",
+                ExecuteMsg::MintRandom {
+                    captcha_signature: "".to_string(),
+                    nonce: 0,
+                    expiry: Timestamp::from_nanos(0),
+                },
+            ),
+            (
+                "BatchMint",
+                ExecuteMsg::BatchMint {
+                    mints: vec![MintRequest {
+                        coordinates,
+                        captcha_signature: "".to_string(),
+                        nonce: 0,
+                        expiry: Timestamp::from_nanos(0),
+                    }],
+                },
+            ),
+            (
+                "Move",
+                ExecuteMsg::Move {
+                    token_id: token_id.clone(),
+                    coordinates,
+                },
+            ),
+            (
+                "MoveMany",
+                ExecuteMsg::MoveMany {
+                    moves: vec![(token_id.clone(), coordinates)],
+                },
+            ),
+            (
+                "RerollCoordinates",
+                ExecuteMsg::RerollCoordinates {
+                    token_id: token_id.clone(),
+                    coordinates,
+                },
+            ),
+            (
+                "Teleport",
+                ExecuteMsg::Teleport {
+                    token_id: token_id.clone(),
+                    coordinates,
+                },
+            ),
+            (
+                "ExpediteMove",
+                ExecuteMsg::ExpediteMove {
+                    token_id: token_id.clone(),
+                },
+            ),
+            (
+                "CancelMove",
+                ExecuteMsg::CancelMove {
+                    token_id: token_id.clone(),
+                },
+            ),
+            (
+                "SetTokenProfile",
+                ExecuteMsg::SetTokenProfile {
+                    token_id: token_id.clone(),
+                    name: Some("Haven".to_string()),
+                    description: None,
+                },
+            ),
+            (
+                "ListForSale",
+                ExecuteMsg::ListForSale {
+                    token_id: token_id.clone(),
+                    price: Coin::new(1, "uluna"),
+                },
+            ),
+            (
+                "CancelListing",
+                ExecuteMsg::CancelListing {
+                    token_id: token_id.clone(),
+                },
+            ),
+            (
+                "Buy",
+                ExecuteMsg::Buy {
+                    token_id: token_id.clone(),
+                },
+            ),
+            (
+                "MakeOffer",
+                ExecuteMsg::MakeOffer {
+                    token_id: token_id.clone(),
+                    expires: None,
+                },
+            ),
+            (
+                "WithdrawOffer",
+                ExecuteMsg::WithdrawOffer {
+                    token_id: token_id.clone(),
+                    bidder: None,
+                },
+            ),
+            (
+                "AcceptOffer",
+                ExecuteMsg::AcceptOffer {
+                    token_id: token_id.clone(),
+                    bidder: "bidder".to_string(),
+                },
+            ),
+            (
+                "GrantPilot",
+                ExecuteMsg::GrantPilot {
+                    token_id: token_id.clone(),
+                    pilot: "pilot".to_string(),
+                    expires: Expiration::Never {},
+                },
+            ),
+            (
+                "RevokePilot",
+                ExecuteMsg::RevokePilot {
+                    token_id: token_id.clone(),
+                },
+            ),
+            (
+                "TransferMany",
+                ExecuteMsg::TransferMany {
+                    recipient: "recipient".to_string(),
+                    token_ids: vec![token_id.clone()],
+                },
+            ),
+            (
+                "SendMany",
+                ExecuteMsg::SendMany {
+                    contract: "contract".to_string(),
+                    token_ids: vec![token_id.clone()],
+                    msg: Binary::default(),
+                },
+            ),
+            (
+                "ProposeSwap",
+                ExecuteMsg::ProposeSwap {
+                    my_token_id: token_id.clone(),
+                    their_token_id: "xyz #2".to_string(),
+                    expires: None,
+                },
+            ),
+            ("AcceptSwap", ExecuteMsg::AcceptSwap { proposal_id: 1 }),
+            ("CancelSwap", ExecuteMsg::CancelSwap { proposal_id: 1 }),
+            (
+                "MoveFormation",
+                ExecuteMsg::MoveFormation {
+                    token_ids: vec![token_id.clone()],
+                    offset: Coordinates { x: 1, y: 0, z: 0 },
+                },
+            ),
+            (
+                "FinalizeMove",
+                ExecuteMsg::FinalizeMove {
+                    token_id: token_id.clone(),
+                },
+            ),
+            (
+                "Merge",
+                ExecuteMsg::Merge {
+                    token_id_keep: token_id.clone(),
+                    token_id_burn: token_id.clone(),
+                },
+            ),
+            (
+                "Burn",
+                ExecuteMsg::Burn {
+                    token_id: token_id.clone(),
+                },
+            ),
+            (
+                "SetTokenUri",
+                ExecuteMsg::SetTokenUri {
+                    token_id,
+                    token_uri: None,
+                },
+            ),
+            (
+                "UpdateConfig",
+                ExecuteMsg::UpdateConfig {
+                    config: test_config(),
+                },
+            ),
+            (
+                "UpdateConfigPartial",
+                ExecuteMsg::UpdateConfigPartial {
+                    config: ConfigUpdate::default(),
+                },
+            ),
+            (
+                "UpdateCaptchaPublicKey",
+                ExecuteMsg::UpdateCaptchaPublicKey {
+                    public_key: "".to_string(),
+                    algorithm: CaptchaKeyAlgorithm::Rsa,
+                },
+            ),
+            (
+                "AddCaptchaPublicKey",
+                ExecuteMsg::AddCaptchaPublicKey {
+                    public_key: "".to_string(),
+                    algorithm: CaptchaKeyAlgorithm::Rsa,
+                    expires: None,
+                },
+            ),
+            (
+                "RemoveCaptchaPublicKey",
+                ExecuteMsg::RemoveCaptchaPublicKey {
+                    public_key: "".to_string(),
+                    algorithm: CaptchaKeyAlgorithm::Rsa,
+                },
+            ),
+            (
+                "UpdateAllowlistMerkleRoot",
+                ExecuteMsg::UpdateAllowlistMerkleRoot { merkle_root: None },
+            ),
+            (
+                "Withdraw",
+                ExecuteMsg::Withdraw {
+                    amount: vec![],
+                    cw20_amount: vec![],
+                    recipient: None,
+                    balance: None,
+                },
+            ),
+            (
+                "ProposeNewOwner",
+                ExecuteMsg::ProposeNewOwner {
+                    address: "address".to_string(),
+                    expires: None,
+                },
+            ),
+            ("AcceptOwnership", ExecuteMsg::AcceptOwnership {}),
+            (
+                "AddHook",
+                ExecuteMsg::AddHook {
+                    addr: "addr".to_string(),
+                },
+            ),
+            (
+                "RemoveHook",
+                ExecuteMsg::RemoveHook {
+                    addr: "addr".to_string(),
+                },
+            ),
+            (
+                "ReserveCoordinates",
+                ExecuteMsg::ReserveCoordinates {
+                    reservations: vec![Reservation {
+                        coordinates,
+                        expires: None,
+                    }],
+                },
+            ),
+            (
+                "ReleaseCoordinates",
+                ExecuteMsg::ReleaseCoordinates {
+                    coordinates: vec![coordinates],
+                },
+            ),
+            (
+                "SetWalletLimitExemption",
+                ExecuteMsg::SetWalletLimitExemption {
+                    address: "address".to_string(),
+                    exempt: true,
+                },
+            ),
+            (
+                "RevokeOwnershipProposal",
+                ExecuteMsg::RevokeOwnershipProposal {},
+            ),
+            ("FreezeConfig", ExecuteMsg::FreezeConfig {}),
+            ("RenounceOwnership", ExecuteMsg::RenounceOwnership {}),
+            ("Pause", ExecuteMsg::Pause { actions: None }),
+            ("Unpause", ExecuteMsg::Unpause {}),
+            (
+                "Receive",
+                ExecuteMsg::Receive(Cw20ReceiveMsg {
+                    sender: "sender".to_string(),
+                    amount: Uint128::zero(),
+                    msg: Binary::default(),
+                }),
+            ),
+        ];
+        for (name, msg) in msgs {
+            let err = CW721ExecuteMsg::<XyzExtension>::try_from(msg).unwrap_err();
+            assert_eq!(
+                err,
+                StdError::generic_err(format!("cannot convert {} to CW721ExecuteMsg", name))
+            );
+        }
+
+        // cw721 messages still convert
+        let msg = CW721ExecuteMsg::<XyzExtension>::try_from(ExecuteMsg::RevokeAll {
+            operator: "operator".to_string(),
+        })
+        .unwrap();
+        assert_eq!(
+            msg,
+            CW721ExecuteMsg::RevokeAll {
+                operator: "operator".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn custom_query_msgs_dont_convert_to_cw721() {
+        let coordinates = Coordinates { x: 1, y: 2, z: 3 };
+        let token_id = "xyz #1".to_string();
+        let msgs = vec![
+            ("Config", QueryMsg::Config {}),
+            ("ConfigInfo", QueryMsg::ConfigInfo {}),
+            (
+                "Approval",
+                QueryMsg::Approval {
+                    token_id: token_id.clone(),
+                    spender: "spender".to_string(),
+                    include_expired: None,
+                },
+            ),
+            (
+                "Approvals",
+                QueryMsg::Approvals {
+                    token_id: token_id.clone(),
+                    include_expired: None,
+                },
+            ),
+            (
+                "AllOperators",
+                QueryMsg::AllOperators {
+                    owner: "owner".to_string(),
+                    include_expired: None,
+                    start_after: None,
+                    limit: None,
+                },
+            ),
+            ("CaptchaPublicKey", QueryMsg::CaptchaPublicKey {}),
+            ("CaptchaPublicKeys", QueryMsg::CaptchaPublicKeys {}),
+            ("Hooks", QueryMsg::Hooks {}),
+            ("Paused", QueryMsg::Paused {}),
+            (
+                "ReservedCoordinates",
+                QueryMsg::ReservedCoordinates {
+                    start_after: None,
+                    limit: None,
+                },
+            ),
+            (
+                "ActiveReservations",
+                QueryMsg::ActiveReservations {
+                    start_after: None,
+                    limit: None,
+                },
+            ),
+            (
+                "WalletLimitExemptions",
+                QueryMsg::WalletLimitExemptions {
+                    start_after: None,
+                    limit: None,
+                },
+            ),
+            (
+                "XyzNftInfoByCoords",
+                QueryMsg::XyzNftInfoByCoords { coordinates },
+            ),
+            (
+                "XyzNftInfosByCoords",
+                QueryMsg::XyzNftInfosByCoords {
+                    coordinates,
+                    start_after: None,
+                    limit: None,
+                },
+            ),
+            (
+                "XyzNftInfoBatch",
+                QueryMsg::XyzNftInfoBatch {
+                    token_ids: vec![token_id.clone()],
+                },
+            ),
+            (
+                "CoordinatesStatus",
+                QueryMsg::CoordinatesStatus {
+                    coordinates: vec![coordinates],
+                },
+            ),
+            (
+                "NumTokensForOwner",
+                QueryMsg::NumTokensForOwner {
+                    owner: "owner".to_string(),
+                },
+            ),
+            (
+                "OwnerTokenSummary",
+                QueryMsg::OwnerTokenSummary {
+                    owner: "owner".to_string(),
+                },
+            ),
+            (
+                "MoveParams",
+                QueryMsg::MoveParams {
+                    token_id: token_id.clone(),
+                    coordinates,
+                },
+            ),
+            (
+                "MoveManyParams",
+                QueryMsg::MoveManyParams {
+                    moves: vec![(token_id.clone(), coordinates)],
+                },
+            ),
+            (
+                "TeleportParams",
+                QueryMsg::TeleportParams {
+                    token_id: token_id.clone(),
+                    coordinates,
+                },
+            ),
+            (
+                "MoveQuote",
+                QueryMsg::MoveQuote {
+                    start: coordinates,
+                    end: coordinates,
+                },
+            ),
+            (
+                "TokenEnergy",
+                QueryMsg::TokenEnergy {
+                    token_id: token_id.clone(),
+                },
+            ),
+            (
+                "TokenStats",
+                QueryMsg::TokenStats {
+                    token_id: token_id.clone(),
+                },
+            ),
+            ("OwnershipDistribution", QueryMsg::OwnershipDistribution {}),
+            ("MintStatus", QueryMsg::MintStatus {}),
+            ("SwapProposal", QueryMsg::SwapProposal { proposal_id: 1 }),
+            (
+                "SwapParams",
+                QueryMsg::SwapParams {
+                    token_id_a: "xyz #1".to_string(),
+                    token_id_b: "xyz #2".to_string(),
+                },
+            ),
+            (
+                "Pilot",
+                QueryMsg::Pilot {
+                    token_id: "xyz #1".to_string(),
+                },
+            ),
+            (
+                "Listing",
+                QueryMsg::Listing {
+                    token_id: token_id.clone(),
+                },
+            ),
+            (
+                "Listings",
+                QueryMsg::Listings {
+                    start_after: None,
+                    limit: None,
+                },
+            ),
+            (
+                "OffersByToken",
+                QueryMsg::OffersByToken {
+                    token_id: "xyz #1".to_string(),
+                    start_after: None,
+                    limit: None,
+                },
+            ),
+            (
+                "OffersByBidder",
+                QueryMsg::OffersByBidder {
+                    bidder: "bidder".to_string(),
+                    start_after: None,
+                    limit: None,
+                },
+            ),
+            (
+                "OwnerLeaderboard",
+                QueryMsg::OwnerLeaderboard {
+                    start_after: Some((1, "owner".to_string())),
+                    limit: None,
+                },
+            ),
+            (
+                "CollisionCheck",
+                QueryMsg::CollisionCheck {
+                    token_id_a: token_id.clone(),
+                    token_id_b: token_id.clone(),
+                },
+            ),
+            (
+                "SignablePayload",
+                QueryMsg::SignablePayload {
+                    sender: "sender".to_string(),
+                    coordinates: Some(coordinates),
+                    nonce: 0,
+                    expiry: Timestamp::from_nanos(0),
+                },
+            ),
+            (
+                "VerifyCaptcha",
+                QueryMsg::VerifyCaptcha {
+                    minter: "minter".to_string(),
+                    coordinates,
+                    captcha_signature: "".to_string(),
+                    nonce: 0,
+                    expiry: Timestamp::from_nanos(0),
+                },
+            ),
+            (
+                "MoveHistory",
+                QueryMsg::MoveHistory {
+                    token_id: token_id.clone(),
+                    start_after: None,
+                    limit: None,
+                },
+            ),
+            ("MoveQueue", QueryMsg::MoveQueue { token_id }),
+            (
+                "TokensInTransit",
+                QueryMsg::TokensInTransit {
+                    start_after: None,
+                    limit: None,
+                },
+            ),
+            ("PublicMintsRemaining", QueryMsg::PublicMintsRemaining {}),
+            (
+                "OccupancyBitmap",
+                QueryMsg::OccupancyBitmap {
+                    min: coordinates,
+                    max: coordinates,
+                },
+            ),
+            ("CurrentMintFee", QueryMsg::CurrentMintFee {}),
+            ("MintPrices", QueryMsg::MintPrices {}),
+            (
+                "MintPrice",
+                QueryMsg::MintPrice {
+                    coordinates,
+                    minter: None,
+                },
+            ),
+            ("Supply", QueryMsg::Supply {}),
+            (
+                "SimulateMint",
+                QueryMsg::SimulateMint {
+                    minter: "minter".to_string(),
+                    coordinates,
+                },
+            ),
+            ("AllowlistMerkleRoot", QueryMsg::AllowlistMerkleRoot {}),
+            ("Ownership", QueryMsg::Ownership {}),
+            ("Minter", QueryMsg::Minter {}),
+            (
+                "RoyaltyInfo",
+                QueryMsg::RoyaltyInfo {
+                    token_id: "xyz #1".to_string(),
+                    sale_price: Uint128::new(100),
+                },
+            ),
+            ("CheckRoyalties", QueryMsg::CheckRoyalties {}),
+            ("WithdrawableBalance", QueryMsg::WithdrawableBalance {}),
+            ("FeeBalances", QueryMsg::FeeBalances {}),
+            ("Stats", QueryMsg::Stats {}),
+            (
+                "AllowlistEligibility",
+                QueryMsg::AllowlistEligibility {
+                    address: "address".to_string(),
+                    proof: vec![],
+                },
+            ),
+            (
+                "Density",
+                QueryMsg::Density {
+                    min: coordinates,
+                    max: coordinates,
+                    bucket_size: 1,
+                },
+            ),
+            (
+                "XyzTokensNearby",
+                QueryMsg::XyzTokensNearby {
+                    coordinates,
+                    max_distance: 1,
+                    start_after: None,
+                    limit: None,
+                },
+            ),
+            (
+                "XyzTokensInRange",
+                QueryMsg::XyzTokensInRange {
+                    min: coordinates,
+                    max: coordinates,
+                    transit_match: None,
+                    start_after: None,
+                    limit: None,
+                },
+            ),
+        ];
+        for (name, msg) in msgs {
+            let err = CW721QueryMsg::try_from(msg).unwrap_err();
+            assert_eq!(
+                err,
+                StdError::generic_err(format!("cannot convert {} to CW721QueryMsg", name))
+            );
+        }
+
+        // xyz-prefixed queries convert to their cw721 equivalents
+        let msg = CW721QueryMsg::try_from(QueryMsg::XyzNftInfo {
+            token_id: "1".to_string(),
+        })
+        .unwrap();
+        assert_eq!(
+            msg,
+            CW721QueryMsg::NftInfo {
+                token_id: "1".to_string()
+            }
+        );
+        let all_xyz_tokens = |order_by| QueryMsg::AllXyzTokens {
+            start_after: None,
+            limit: Some(10),
+            order_by,
+            start_after_coords: None,
+            order: None,
+        };
+        for order_by in [None, Some(TokenOrder::TokenId)] {
+            assert_eq!(
+                CW721QueryMsg::try_from(all_xyz_tokens(order_by)).unwrap(),
+                CW721QueryMsg::AllTokens {
+                    start_after: None,
+                    limit: Some(10),
+                }
+            );
+        }
+
+        // cw721 has no equivalent for listing tokens by coordinates
+        CW721QueryMsg::try_from(all_xyz_tokens(Some(TokenOrder::Coordinates))).unwrap_err();
+
+        // or for listing tokens in descending order
+        CW721QueryMsg::try_from(QueryMsg::XyzTokens {
+            owner: "owner".to_string(),
+            start_after: None,
+            limit: None,
+            order: Some(SortOrder::Descending),
+        })
+        .unwrap_err();
+    }
+
+    #[test]
+    fn coordinates_bytes_order_preserving() {
+        let values = [i64::MIN, -1_000, -1, 0, 1, 1_000, i64::MAX];
+        let mut coords = vec![];
+        for &x in values.iter() {
+            for &y in values.iter() {
+                for &z in values.iter() {
+                    coords.push(Coordinates { x, y, z });
+                }
+            }
+        }
+
+        // coords is in (x, y, z) order, so the encodings should be too
+        let encoded: Vec<Vec<u8>> = coords.iter().map(|c| c.to_bytes()).collect();
+        for pair in encoded.windows(2) {
+            assert!(pair[0] < pair[1]);
+        }
+
+        for (c, bytes) in coords.iter().zip(encoded.iter()) {
+            assert_eq!(Coordinates::from_bytes(bytes).unwrap(), *c);
+        }
+        Coordinates::from_bytes(&[0u8; 23]).unwrap_err();
+    }
+
+    #[test]
+    fn coordinates_distance_large_bounds() {
+        let mut config = test_config();
+        config.bounds = CoordinateBounds::symmetric(1_000_000_000);
+        let max = config.bounds.x_max;
+        let min = Coordinates {
+            x: -max,
+            y: -max,
+            z: -max,
+        };
+        let max = Coordinates {
+            x: max,
+            y: max,
+            z: max,
+        };
+        assert_eq!(min.distance(max), 6_000_000_000);
+        assert_eq!(
+            config.get_move_fee(min, max).unwrap(),
+            Coin::new(100 + 6_000_000_000, "uluna")
+        );
+        assert_eq!(config.get_move_nanos(min, max).unwrap(), 10 + 6_000_000_000);
+
+        // overflowing fees and durations are errors rather than panics
+        config.move_fee_per_step = Uint128::MAX;
+        config.get_move_fee(min, max).unwrap_err();
+        config.move_nanos_per_step = u64::MAX;
+        config.get_move_nanos(min, max).unwrap_err();
+
+        // points at opposite extremes of the i64 range saturate
+        let min = Coordinates {
+            x: i64::MIN,
+            y: i64::MIN,
+            z: i64::MIN,
+        };
+        let max = Coordinates {
+            x: i64::MAX,
+            y: i64::MAX,
+            z: i64::MAX,
+        };
+        assert_eq!(min.distance(max), u64::MAX);
+        assert_eq!(min.step_toward(max, u64::MAX).x, i64::MAX);
+    }
+
+    #[test]
+    fn move_math_overflow_boundary() {
+        let mut config = test_config();
+        let start = Coordinates { x: 0, y: 0, z: 0 };
+        let end = Coordinates { x: 1, y: 1, z: 0 };
+
+        // the largest per-step fee that fits: 2 * step + 100 <= Uint128::MAX
+        config.move_fee_per_step = Uint128::new((u128::MAX - 100) / 2);
+        assert_eq!(
+            config.get_move_fee(start, end).unwrap().amount,
+            Uint128::new(u128::MAX - 1)
+        );
+        config.base_move_fee.amount = Uint128::new(101);
+        assert_eq!(
+            config.get_move_fee(start, end).unwrap().amount,
+            Uint128::MAX
+        );
+        config.base_move_fee.amount = Uint128::new(102);
+        assert!(matches!(
+            config.get_move_fee(start, end),
+            Err(StdError::Overflow { .. })
+        ));
+        config.base_move_fee.amount = Uint128::new(100);
+        config.move_fee_per_step += Uint128::new(1);
+        assert!(matches!(
+            config.get_move_fee(start, end),
+            Err(StdError::Overflow { .. })
+        ));
+
+        // the largest per-step duration that fits: 2 * step + 10 <= u64::MAX
+        config.move_nanos_per_step = (u64::MAX - 10) / 2;
+        assert_eq!(config.get_move_nanos(start, end).unwrap(), u64::MAX - 1);
+        config.base_move_nanos = 11;
+        assert_eq!(config.get_move_nanos(start, end).unwrap(), u64::MAX);
+        config.base_move_nanos = 12;
+        assert!(matches!(
+            config.get_move_nanos(start, end),
+            Err(StdError::GenericErr { .. })
+        ));
+        config.base_move_nanos = 10;
+        config.move_nanos_per_step += 1;
+        config.get_move_nanos(start, end).unwrap_err();
+    }
+
+    #[test]
+    fn isqrt_rounds_down() {
+        for (n, root) in [(0, 0), (1, 1), (3, 1), (4, 2), (15, 3), (16, 4), (17, 4)] {
+            assert_eq!(isqrt(n), root);
+        }
+        assert_eq!(isqrt(u128::MAX), u64::MAX as u128);
+    }
+
+    #[test]
+    fn distance_metrics() {
+        let origin = Coordinates { x: 0, y: 0, z: 0 };
+        let point = Coordinates { x: 1, y: -2, z: 3 };
+        assert_eq!(DistanceMetric::Manhattan.distance(origin, point), 6);
+        assert_eq!(DistanceMetric::Chebyshev.distance(origin, point), 3);
+        // sqrt(14) rounds up to 4
+        assert_eq!(DistanceMetric::EuclideanRounded.distance(origin, point), 4);
+        let point = Coordinates { x: 3, y: 4, z: 0 };
+        assert_eq!(DistanceMetric::EuclideanRounded.distance(origin, point), 5);
+        assert_eq!(DistanceMetric::EuclideanRounded.distance(point, point), 0);
+
+        let min = Coordinates {
+            x: i64::MIN,
+            y: i64::MIN,
+            z: i64::MIN,
+        };
+        let max = Coordinates {
+            x: i64::MAX,
+            y: i64::MAX,
+            z: i64::MAX,
+        };
+        assert_eq!(DistanceMetric::Chebyshev.distance(min, max), u64::MAX);
+        assert_eq!(
+            DistanceMetric::EuclideanRounded.distance(min, max),
+            u64::MAX
+        );
+
+        // the configured metric drives move fees and durations
+        let mut config = test_config();
+        config.distance_metric = DistanceMetric::Chebyshev;
+        let point = Coordinates { x: 1, y: -2, z: 3 };
+        assert_eq!(
+            config.get_move_fee(origin, point).unwrap(),
+            Coin::new(103, "uluna")
+        );
+        assert_eq!(config.get_move_nanos(origin, point).unwrap(), 13);
+    }
+
+    #[test]
+    fn config_bounds() {
+        let mut config = test_config();
+        config.bounds = CoordinateBounds {
+            x_min: -1_000_000,
+            x_max: 1_000_000,
+            y_min: -1_000_000,
+            y_max: 1_000_000,
+            z_min: 0,
+            z_max: 100,
+        };
+        config.validate().unwrap();
+        config
+            .check_bounds(Coordinates {
+                x: -1_000_000,
+                y: 1_000_000,
+                z: 100,
+            })
+            .unwrap();
+        assert_eq!(
+            config
+                .check_bounds(Coordinates { x: 0, y: 0, z: -1 })
+                .unwrap_err(),
+            StdError::generic_err("z coordinate must be between 0 and 100")
+        );
+        assert_eq!(
+            config
+                .check_bounds(Coordinates {
+                    x: 1_000_001,
+                    y: 0,
+                    z: 0
+                })
+                .unwrap_err(),
+            StdError::generic_err("x coordinate must be between -1000000 and 1000000")
+        );
+
+        config.bounds.y_min = 1_000_001;
+        assert_eq!(
+            config.validate().unwrap_err(),
+            StdError::generic_err("y_min must not exceed y_max")
+        );
+    }
+
+    #[test]
+    fn config_validation() {
+        test_config().validate().unwrap();
+        let invalid = |update: &dyn Fn(&mut Config)| {
+            let mut config = test_config();
+            update(&mut config);
+            match config.validate().unwrap_err() {
+                StdError::GenericErr { msg, .. } => msg,
+                err => panic!("unexpected error {}", err),
+            }
+        };
+
+        assert_eq!(
+            invalid(&|c| c.bounds.x_min = i64::MIN),
+            format!("x_min must be greater than {}", i64::MIN)
+        );
+        assert_eq!(
+            invalid(&|c| c.token_supply = 0),
+            "token_supply must be positive"
+        );
+        assert_eq!(
+            invalid(&|c| c.max_query_limit = 0),
+            "max_query_limit must be positive"
+        );
+        assert_eq!(
+            invalid(&|c| c.default_query_limit = c.max_query_limit + 1),
+            "default_query_limit must not exceed max_query_limit"
+        );
+        assert_eq!(
+            invalid(&|c| c.mint_fee.denom = "".to_string()),
+            "mint_fee denom must not be empty"
+        );
+        assert_eq!(
+            invalid(&|c| c.base_move_fee.denom = "".to_string()),
+            "base_move_fee denom must not be empty"
+        );
+        assert_eq!(
+            invalid(&|c| c.allowlist_mint_fee = Some(Coin::new(1, ""))),
+            "allowlist_mint_fee denom must not be empty"
+        );
+        assert_eq!(
+            invalid(&|c| c.mint_fee_alternatives = vec![Coin::new(1, "")]),
+            "mint fee alternative denoms must not be empty"
+        );
+        assert_eq!(
+            invalid(&|c| c.base_token_uri = Some("".to_string())),
+            "base_token_uri must not be empty"
+        );
+        assert_eq!(
+            invalid(&|c| c.base_token_uri = Some("x".repeat(513))),
+            "base_token_uri must be at most 512 bytes"
+        );
+        assert_eq!(
+            invalid(&|c| {
+                c.mint_start = Some(Timestamp::from_seconds(10));
+                c.mint_end = Some(Timestamp::from_seconds(10));
+            }),
+            "mint_end must be after mint_start"
+        );
+        assert_eq!(
+            invalid(&|c| {
+                c.burn_mint_fees = true;
+                c.fee_distribution = vec![(FeeDestination::Burn, 1_000)];
+            }),
+            "fee_distribution must be empty while burn_mint_fees is set"
+        );
+
+        // the move across the 2001-wide bounds is 6000 steps long
+        let mut config = test_config();
+        config.move_fee_per_step = Uint128::new((u128::MAX - 100) / 6000);
+        config.move_nanos_per_step = (u64::MAX - 10) / 6000;
+        config.validate().unwrap();
+        assert_eq!(
+            invalid(&|c| c.move_fee_per_step = Uint128::new((u128::MAX - 100) / 6000 + 1)),
+            "move fee must not overflow for a move across the bounds"
+        );
+        assert_eq!(
+            invalid(&|c| c.move_nanos_per_step = (u64::MAX - 10) / 6000 + 1),
+            "move duration must not overflow for a move across the bounds"
+        );
+        assert_eq!(
+            invalid(&|c| c.bounds = CoordinateBounds::symmetric(i64::MAX)),
+            "move duration must not overflow for a move across the bounds"
+        );
+    }
+
+    #[test]
+    fn config_mint_fee_tiers() {
+        let mut config = test_config();
+        config.token_supply = 100;
+        config.mint_fee = Coin::new(10, "uluna");
+        config.mint_fee_tiers = vec![(5, Coin::new(20, "uluna")), (10, Coin::new(30, "uluna"))];
+        config.validate().unwrap();
+
+        assert_eq!(config.mint_fee_at(0), &Coin::new(10, "uluna"));
+        assert_eq!(config.mint_fee_at(5), &Coin::new(20, "uluna"));
+        assert_eq!(config.mint_fee_at(99), &Coin::new(30, "uluna"));
+        // batches spanning tiers pay each token's own tier price
+        assert_eq!(
+            config.mint_fee_for(4, 3).unwrap(),
+            Coin::new(10 + 20 + 20, "uluna")
+        );
+        assert_eq!(config.mints_left_in_tier(3), 2);
+        assert_eq!(config.mints_left_in_tier(10), 90);
+
+        config.mint_fee_tiers = vec![(5, Coin::new(20, "uluna")), (5, Coin::new(30, "uluna"))];
+        assert_eq!(
+            config.validate().unwrap_err(),
+            StdError::generic_err("mint fee tiers must be sorted by increasing start")
+        );
+        config.mint_fee_tiers = vec![(5, Coin::new(20, "uusd"))];
+        assert_eq!(
+            config.validate().unwrap_err(),
+            StdError::generic_err("mint fee tiers must use the mint fee denom")
+        );
+    }
+
+    #[test]
+    fn config_mint_fee_alternatives() {
+        let mut config = test_config();
+        config.mint_fee = Coin::new(10, "uluna");
+        config.mint_fee_tiers = vec![(5, Coin::new(20, "uluna"))];
+        config.mint_fee_alternatives = vec![Coin::new(3, "uusd")];
+        config.validate().unwrap();
+        assert_eq!(
+            config.mint_prices_for(4, 2).unwrap(),
+            vec![Coin::new(30, "uluna"), Coin::new(6, "uusd")]
+        );
+
+        config.mint_fee_alternatives.push(Coin::new(4, "uluna"));
+        assert_eq!(
+            config.validate().unwrap_err(),
+            StdError::generic_err("mint fee alternatives must each use a different denom")
+        );
+    }
+
+    #[test]
+    fn config_mint_fee_distance_curve() {
+        let mut config = test_config();
+        config.mint_fee = Coin::new(100, "uluna");
+        config.mint_fee_alternatives = vec![Coin::new(10, "uusd")];
+        let at = |x: i64| Coordinates { x, y: 0, z: 0 };
+        config.mint_fee_distance_curve = Some(MintFeeDistanceCurve {
+            anchor: at(10),
+            bands: vec![(5, 30_000), (50, 10_000), (3_010, 5_000)],
+        });
+        config.validate().unwrap();
+
+        // bands are inclusive of their distance, measured from the anchor
+        assert_eq!(config.mint_fee_bps_at(at(15)), 30_000);
+        assert_eq!(config.mint_fee_bps_at(at(16)), 10_000);
+        assert_eq!(config.mint_fee_bps_at(at(-1000)), 5_000);
+        // each token in a batch is scaled by its own band, in every denom
+        assert_eq!(
+            config
+                .mint_prices_at(0, &[(at(10), false), (at(100), false)])
+                .unwrap(),
+            vec![Coin::new(300 + 50, "uluna"), Coin::new(30 + 5, "uusd")]
+        );
+        assert_eq!(
+            config
+                .scale_mint_fee(Uint128::new(3), at(100), false)
+                .unwrap(),
+            Uint128::new(1)
+        );
+        // without a curve, prices match mint_prices_for
+        let unscaled = Config {
+            mint_fee_distance_curve: None,
+            ..config.clone()
+        };
+        assert_eq!(
+            unscaled
+                .mint_prices_at(0, &[(at(10), false), (at(100), false)])
+                .unwrap(),
+            unscaled.mint_prices_for(0, 2).unwrap()
+        );
+
+        let invalid = |bands: Vec<(u64, u32)>| {
+            let mut config = config.clone();
+            config.mint_fee_distance_curve.as_mut().unwrap().bands = bands;
+            config.validate().unwrap_err()
+        };
+        assert_eq!(
+            invalid(vec![(5, 30_000), (5, 10_000), (3_010, 5_000)]),
+            StdError::generic_err("mint fee distance bands must be sorted by increasing distance")
+        );
+        // the corner at (-1000, -1000, -1000) is 3010 away
+        assert_eq!(
+            invalid(vec![(5, 30_000), (3_009, 5_000)]),
+            StdError::generic_err("mint fee distance bands must cover every in-bounds distance")
+        );
+        assert_eq!(
+            invalid(vec![]),
+            StdError::generic_err("mint fee distance bands must cover every in-bounds distance")
+        );
+    }
+
+    #[test]
+    fn config_royalties() {
+        let mut config = test_config();
+        assert_eq!(config.royalty_amount(Uint128::new(1000)), Uint128::zero());
+
+        // royalties are rounded down
+        config.royalty_bps = 250;
+        assert_eq!(config.royalty_amount(Uint128::new(1000)), Uint128::new(25));
+        assert_eq!(config.royalty_amount(Uint128::new(101)), Uint128::new(2));
+        assert_eq!(config.royalty_amount(Uint128::new(39)), Uint128::zero());
+
+        config.royalty_bps = 10_000;
+        config.validate().unwrap();
+        assert_eq!(config.royalty_amount(Uint128::new(101)), Uint128::new(101));
+        config.royalty_bps = 10_001;
+        assert_eq!(
+            config.validate().unwrap_err(),
+            StdError::generic_err("royalty_bps must not exceed 10000")
+        );
+    }
+
+    #[test]
+    fn config_query_limit() {
+        let mut config = test_config();
+        config.default_query_limit = 5;
+        config.max_query_limit = 20;
+        assert_eq!(config.query_limit(None), 5);
+        assert_eq!(config.query_limit(Some(15)), 15);
+        assert_eq!(config.query_limit(Some(25)), 20);
+
+        // configs stored before query limits were configurable fall back to
+        // the previously hardcoded limits
+        let config: Config = cosmwasm_std::from_slice(
+            br#"{
+                "public_minting_enabled": true,
+                "bounds": {
+                    "x_min": -1000, "x_max": 1000,
+                    "y_min": -1000, "y_max": 1000,
+                    "z_min": -1000, "z_max": 1000
+                },
+                "token_supply": 10000,
+                "wallet_limit": 5,
+                "mint_fee": { "denom": "uluna", "amount": "0" },
+                "base_move_nanos": 10,
+                "move_nanos_per_step": 1,
+                "base_move_fee": { "denom": "uluna", "amount": "100" },
+                "move_fee_per_step": "1"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.default_query_limit, 10);
+        assert_eq!(config.max_query_limit, 30);
+    }
+
+    #[test]
+    fn config_move_fee_discounts() {
+        let mut config = test_config();
+        config.move_fee_discounts = vec![(100, 2500), (50, 1000), (200, 2000)];
+        config.validate().unwrap();
+        assert_eq!(config.move_fee_discount_bps(49), 0);
+        assert_eq!(config.move_fee_discount_bps(50), 1000);
+        assert_eq!(config.move_fee_discount_bps(150), 2500);
+        assert_eq!(config.move_fee_discount_bps(u64::MAX), 2500);
+
+        // the discount rounds down, so the fee rounds up
+        let start = Coordinates { x: 0, y: 0, z: 0 };
+        let end = Coordinates { x: 1, y: 0, z: 0 };
+        assert_eq!(
+            config.get_discounted_move_fee(start, end, 50).unwrap(),
+            Coin::new(101 - 10, "uluna")
+        );
+        assert_eq!(
+            config.get_discounted_move_fee(start, end, 100).unwrap(),
+            Coin::new(101 - 25, "uluna")
+        );
+
+        config.move_fee_discounts = vec![(100, 10_001)];
+        config.validate().unwrap_err();
+    }
+
+    #[test]
+    fn config_apply_update() {
+        let mut config = test_config();
+        config.max_public_mints_per_block = Some(3);
+
+        // only the given fields change, and unchanged values aren't reported
+        let changed = config.apply_update(ConfigUpdate {
+            mint_fee: Some(Coin::new(5, "uluna")),
+            wallet_limit: Some(test_config().wallet_limit),
+            ..ConfigUpdate::default()
+        });
+        assert_eq!(changed, vec!["mint_fee"]);
+        let mut expected = test_config();
+        expected.max_public_mints_per_block = Some(3);
+        expected.mint_fee = Coin::new(5, "uluna");
+        assert_eq!(config, expected);
+
+        // a null optional field clears it, while an absent one is kept
+        let update: ConfigUpdate = cosmwasm_std::from_slice(
+            br#"{ "max_public_mints_per_block": null, "move_nanos_per_step": 7 }"#,
+        )
+        .unwrap();
+        assert_eq!(update.max_public_mints_per_block, Some(None));
+        assert_eq!(update.allowlist_mint_fee, None);
+        let changed = config.apply_update(update);
+        assert_eq!(
+            changed,
+            vec!["move_nanos_per_step", "max_public_mints_per_block"]
+        );
+        assert_eq!(config.max_public_mints_per_block, None);
+        assert_eq!(config.move_nanos_per_step, 7);
+    }
+
+    #[test]
+    fn xyz_extension_current_energy() {
+        let mut config = test_config();
+        let extension = XyzExtension {
+            coordinates: Coordinates { x: 1, y: 2, z: 3 },
+            prev_coordinates: None,
+            arrival: Timestamp::from_nanos(0),
+            energy: 40,
+            energy_updated_at: Timestamp::from_nanos(1000),
+            level: 0,
+            token_uri: None,
+            minted_at: Timestamp::from_nanos(0),
+            minted_height: 0,
+            rerolled: false,
+            name: None,
+            description: None,
+            moves_count: 0,
+            total_distance: 0,
+        };
+
+        // no energy is regenerated before a full regen period elapses
+        assert_eq!(
+            extension.current_energy(&config, Timestamp::from_nanos(1009)),
+            40
+        );
+        // one unit of energy is regenerated per regen period
+        assert_eq!(
+            extension.current_energy(&config, Timestamp::from_nanos(1250)),
+            65
+        );
+        // regeneration is capped at the configured maximum
+        assert_eq!(
+            extension.current_energy(&config, Timestamp::from_nanos(100000)),
+            100
+        );
+
+        // energy is always full when regeneration is instant
+        config.energy_regen_nanos = 0;
+        assert_eq!(
+            extension.current_energy(&config, Timestamp::from_nanos(1000)),
+            100
+        );
+    }
+
+    #[test]
+    fn coordinates_step_toward() {
+        let start = Coordinates { x: 0, y: 0, z: 0 };
+        let end = Coordinates { x: -2, y: 1, z: 3 };
+        let path: Vec<Coordinates> = (0..=7).map(|steps| start.step_toward(end, steps)).collect();
+        assert_eq!(
+            path,
+            vec![
+                Coordinates { x: 0, y: 0, z: 0 },
+                Coordinates { x: -1, y: 0, z: 0 },
+                Coordinates { x: -2, y: 0, z: 0 },
+                Coordinates { x: -2, y: 1, z: 0 },
+                Coordinates { x: -2, y: 1, z: 1 },
+                Coordinates { x: -2, y: 1, z: 2 },
+                Coordinates { x: -2, y: 1, z: 3 },
+                // steps beyond the destination stay put
+                Coordinates { x: -2, y: 1, z: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn xyz_extension_position_at() {
+        let mut config = test_config();
+        config.base_move_nanos = 10;
+        config.move_nanos_per_step = 5;
+
+        // a 3-step move departing at 100 arrives at 100 + 10 + 3 * 5
+        let extension = XyzExtension {
+            coordinates: Coordinates { x: 1, y: 2, z: 0 },
+            prev_coordinates: Some(Coordinates { x: 0, y: 0, z: 0 }),
+            arrival: Timestamp::from_nanos(125),
+            energy: 0,
+            energy_updated_at: Timestamp::from_nanos(100),
+            level: 0,
+            token_uri: None,
+            minted_at: Timestamp::from_nanos(0),
+            minted_height: 0,
+            rerolled: false,
+            name: None,
+            description: None,
+            moves_count: 0,
+            total_distance: 0,
+        };
+        let at = |nanos| extension.position_at(&config, Timestamp::from_nanos(nanos));
+        let next = |nanos| extension.next_step_at(&config, Timestamp::from_nanos(nanos));
+
+        // waits at the start during the base move time
+        assert_eq!(at(100), Coordinates { x: 0, y: 0, z: 0 });
+        assert_eq!(at(114), Coordinates { x: 0, y: 0, z: 0 });
+        assert_eq!(next(100), Some(Timestamp::from_nanos(115)));
+
+        // then steps along x, then y
+        assert_eq!(at(115), Coordinates { x: 1, y: 0, z: 0 });
+        assert_eq!(next(115), Some(Timestamp::from_nanos(120)));
+        assert_eq!(at(124), Coordinates { x: 1, y: 1, z: 0 });
+        assert_eq!(next(124), Some(Timestamp::from_nanos(125)));
+
+        // and is stationary once it arrives
+        assert_eq!(at(125), Coordinates { x: 1, y: 2, z: 0 });
+        assert_eq!(at(1000), Coordinates { x: 1, y: 2, z: 0 });
+        assert_eq!(next(125), None);
+
+        // never-moved tokens are stationary
+        let stationary = XyzExtension {
+            prev_coordinates: None,
+            ..extension
+        };
+        assert_eq!(
+            stationary.position_at(&config, Timestamp::from_nanos(100)),
+            Coordinates { x: 1, y: 2, z: 0 }
+        );
+        assert_eq!(
+            stationary.next_step_at(&config, Timestamp::from_nanos(100)),
+            None
+        );
+    }
 }